@@ -0,0 +1,65 @@
+//! Price lookups over a long price history: the dated lookup of
+//! `PriceDB` against a scan of the same prices.
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+use rust_decimal::Decimal;
+
+use ledger::misc;
+use ledger::pricedb::PriceDB;
+use ledger::quantity::Quantity;
+use ledger::symbol::Symbol;
+
+/// Number of `P` records of the commodity.
+const PRICES: i64 = 10_000;
+
+fn prices() -> Vec<(NaiveDateTime, Quantity)> {
+    let start = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+    let usd = Symbol::new("$");
+    (0..PRICES)
+        .map(|i| {
+            let at = misc::to_datetime(start + Duration::days(2 * i));
+            let price = Quantity {
+                q: Decimal::from(i),
+                s: usd,
+            };
+            (at, price)
+        })
+        .collect()
+}
+
+fn price_as_of(c: &mut Criterion) {
+    let s = Symbol::new("BENCH");
+    let prices = prices();
+    let mut db = PriceDB::new();
+    for (at, price) in &prices {
+        db.upsert_price(s, *at, *price);
+    }
+    // one lookup every week of the history
+    let dates: Vec<_> = prices.iter().step_by(4).map(|(at, _)| *at).collect();
+
+    let mut group = c.benchmark_group("price_as_of");
+    group.bench_function("pricedb", |b| {
+        b.iter(|| {
+            for at in &dates {
+                black_box(db.uprice_as_of(s, *at));
+            }
+        })
+    });
+    group.bench_function("linear_scan", |b| {
+        b.iter(|| {
+            for at in &dates {
+                let price = prices
+                    .iter()
+                    .filter(|(d, _)| d <= at)
+                    .max_by_key(|(d, _)| *d)
+                    .map(|(_, p)| *p);
+                black_box(price);
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, price_as_of);
+criterion_main!(benches);
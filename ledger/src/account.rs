@@ -16,7 +16,7 @@ use crate::{
 /// Provides access to postings for a specific account.
 pub trait AccPostingSrc<'a> {
     fn acc_name(&self) -> &AccName;
-    fn postings(&self) -> Box<dyn Iterator<Item = &'a Posting> + 'a>;
+    fn postings(&self) -> Box<dyn Iterator<Item = &'a Posting> + '_>;
 }
 
 /// An `Account` acts as a container for the set of all debits and
@@ -28,12 +28,15 @@ pub trait AccPostingSrc<'a> {
 pub struct Account<'a> {
     /// the full name
     name: AccName,
-    postings: Box<dyn AccPostingSrc<'a> + 'a>,
+    postings: Box<dyn AccPostingSrc<'a> + Send + Sync + 'a>,
 }
 
 impl<'a> Account<'a> {
     /// Creates a new account from the given name and postings.
-    pub fn from_postings(name: AccName, ps: impl AccPostingSrc<'a> + 'a) -> Account<'a> {
+    pub fn from_postings(
+        name: AccName,
+        ps: impl AccPostingSrc<'a> + Send + Sync + 'a,
+    ) -> Account<'a> {
         Account {
             name,
             postings: Box::new(ps),
@@ -64,18 +67,15 @@ impl<'a> Account<'a> {
             .filter(|p| p.date <= date)
             .map(|p| {
                 let b = p.lot_uprice.price;
-                let m = price_db
-                    .uprice_as_of(p.quantity.s, to_datetime(date))
-                    .unwrap();
-                let h = price_db
-                    .uprice_as_of(p.quantity.s, to_datetime(p.date))
-                    .unwrap();
+                let m = price_db.uprice_or(p.quantity.s, to_datetime(date), p.uprice);
+                let h = price_db.uprice_or(p.quantity.s, to_datetime(p.date), p.uprice);
 
                 AvgPosition {
                     qty: p.quantity,
                     m_uprice: m.to_amount(),
                     h_uprice: h.to_amount(),
                     b_uprice: b.to_amount(),
+                    precision: price_db.valuation_precision(),
                 }
             })
             .sum()
@@ -126,8 +126,10 @@ mod tests {
     use crate::holdings::{AvgPosition, Holdings};
     use crate::journal::{self, JrnIO};
     use crate::ledger;
+    use crate::misc;
     use crate::pricedb;
     use crate::quantity;
+    use crate::query::Query;
     use crate::util;
     use chrono::NaiveDate;
     use rust_decimal::dec;
@@ -151,7 +153,7 @@ mod tests {
 ";
         let (journal, price_db) = build_journal(input);
         let ledger = ledger::Ledger::from_journal(&journal);
-        let bal = balance::Balance::from_ledger(&ledger, &[]);
+        let bal = balance::Balance::from_ledger(&ledger, &Query::All);
 
         let cash_name = AccName::from("Assets:Cash");
         let account = bal
@@ -177,7 +179,7 @@ mod tests {
 ";
         let (journal, price_db) = build_journal(input);
         let ledger = ledger::Ledger::from_journal(&journal);
-        let bal = balance::Balance::from_ledger(&ledger, &[]);
+        let bal = balance::Balance::from_ledger(&ledger, &Query::All);
 
         let cash_name = AccName::from("Assets:Cash");
         let account = bal
@@ -193,6 +195,7 @@ mod tests {
                 m_uprice: uprice.clone(),
                 h_uprice: uprice.clone(),
                 b_uprice: uprice,
+                precision: misc::DEFAULT_VALUATION_PRECISION,
             }])
         );
     }
@@ -206,7 +209,7 @@ mod tests {
 ";
         let (journal, price_db) = build_journal(input);
         let ledger = ledger::Ledger::from_journal(&journal);
-        let bal = balance::Balance::from_ledger(&ledger, &[]);
+        let bal = balance::Balance::from_ledger(&ledger, &Query::All);
 
         let cash_name = AccName::from("Assets:Cash");
         let account = bal.account(&cash_name).expect("Assets:Cash not found");
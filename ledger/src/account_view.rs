@@ -154,6 +154,21 @@ pub struct FlatAccountView<T: Arithmetic + TsBasket> {
     balance: T,
 }
 
+impl<T> FlatAccountView<T>
+where
+    T: Arithmetic + TsBasket,
+{
+    /// Negates the balance of this account.
+    pub fn flip_sign(&mut self) {
+        self.balance = T::default() - mem::take(&mut self.balance);
+    }
+
+    /// Returns a mutable reference to the balance of this account.
+    pub fn balance_mut(&mut self) -> &mut T {
+        &mut self.balance
+    }
+}
+
 impl<T> AccountView for FlatAccountView<T>
 where
     T: Arithmetic + TsBasket,
@@ -572,6 +587,10 @@ pub mod utils {
         left
     }
 
+    /// Drops the sub-accounts below `deep` levels. The balance of a
+    /// hierarchical account already includes its sub-accounts', so
+    /// the pruned balances stay rolled up in the account at the
+    /// cutoff and the total is unchanged.
     pub fn limit_accounts_depth(
         acc: &mut HierAccountView<impl Arithmetic + TsBasket>,
         deep: usize,
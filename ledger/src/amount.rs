@@ -1,10 +1,10 @@
 use chrono::NaiveDate;
 use rust_decimal::Decimal;
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::fmt::{self, Debug};
 use std::iter::Sum;
-use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Sub, SubAssign};
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 
 use crate::balance::Valuation;
 use crate::holdings::AvgPosition;
@@ -15,10 +15,10 @@ use crate::symbol::Symbol;
 use crate::tamount::TAmount;
 
 /// An amount representing a collection of quantities of different
-/// commodities.
+/// commodities, kept in commodity order.
 #[derive(Default, Clone, PartialEq, Eq)]
 pub struct Amount {
-    qs: HashMap<Symbol, Decimal>,
+    qs: BTreeMap<Symbol, Decimal>,
 }
 
 impl Zero for Amount {
@@ -31,6 +31,7 @@ impl Zero for Amount {
 }
 
 impl Quantities for Amount {
+    /// In commodity order, see [`Symbol`]'s `Ord`.
     fn quantities(&self) -> impl Iterator<Item = Quantity> {
         self.qs.iter().map(|(s, q)| Quantity { q: *q, s: *s })
     }
@@ -65,7 +66,7 @@ impl Amount {
             return Amount::new();
         }
 
-        let mut qs = HashMap::new();
+        let mut qs = BTreeMap::new();
         qs.insert(q.s, q.q);
 
         Amount { qs }
@@ -81,6 +82,41 @@ impl Amount {
         Some(Quantity { s: *s, q: *q })
     }
 
+    /// Rounds every quantity to `dp` decimal places. Commodities that
+    /// round to zero are removed.
+    pub fn round_dp(mut self, dp: u32) -> Amount {
+        self.qs.values_mut().for_each(|q| *q = q.round_dp(dp));
+        self.remove_zeros();
+        self
+    }
+
+    /// The quantity of each commodity in the amount, ordered by
+    /// commodity name. Commodities with a zero quantity are left out.
+    pub fn iter_quantities(&self) -> impl Iterator<Item = Quantity> {
+        self.quantities().filter(|q| !q.q.is_zero())
+    }
+
+    /// `self - rhs`, or `None` if a quantity overflows.
+    pub fn checked_sub(&self, rhs: &Amount) -> Option<Amount> {
+        let mut res = self.clone();
+        for (s, q) in &rhs.qs {
+            let entry = res.qs.entry(*s).or_insert(Decimal::ZERO);
+            *entry = entry.checked_sub(*q)?;
+        }
+        res.remove_zeros();
+        Some(res)
+    }
+
+    /// A key to order amounts by size: the sum of the absolute
+    /// quantities. Quantities of different commodities are added as
+    /// plain numbers, so for a multi-commodity amount the key is not a
+    /// quantity of any commodity. Only use it for sorting, never report
+    /// it; value the amount in a single commodity first when the order
+    /// must be meaningful.
+    pub fn size_key(&self) -> Decimal {
+        self.qs.values().map(|q| q.abs()).sum()
+    }
+
     /// remove all commodity that have zero quantity
     fn remove_zeros(&mut self) {
         self.qs.retain(|_, &mut v| v != Decimal::ZERO);
@@ -188,8 +224,7 @@ impl Sub<&Amount> for &Amount {
 impl Add<Quantity> for Amount {
     type Output = Amount;
     fn add(mut self, rhs: Quantity) -> Amount {
-        *self.qs.entry(rhs.s).or_insert(Decimal::ZERO) += rhs.q;
-        self.remove_zeros();
+        self += rhs;
         self
     }
 }
@@ -251,6 +286,14 @@ impl MulAssign<Decimal> for Amount {
     }
 }
 
+impl Neg for Amount {
+    type Output = Amount;
+    fn neg(mut self) -> Self::Output {
+        self.qs.values_mut().for_each(|q| *q = -*q);
+        self
+    }
+}
+
 impl Debug for Amount {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:#?}", self.qs)
@@ -298,8 +341,49 @@ mod test {
     use super::*;
 
     use crate::amount;
+    use crate::misc;
     use rust_decimal::dec;
 
+    #[test]
+    fn neg_flips_every_commodity() {
+        let a = amount!(10, "$") + amount!(-3, "EUR");
+        assert_eq!(-a, amount!(-10, "$") + amount!(3, "EUR"));
+    }
+
+    #[test]
+    fn checked_sub_of_snapshots() {
+        let before = amount!(100, "$") + amount!(5, "AAPL");
+        let after = amount!(150, "$") + amount!(5, "AAPL");
+        assert_eq!(after.checked_sub(&before), Some(amount!(50, "$")));
+
+        let max = Amount::from_quantity(Quantity {
+            q: Decimal::MAX,
+            s: Symbol::new("$"),
+        });
+        assert_eq!(max.checked_sub(&amount!(-1, "$")), None);
+    }
+
+    #[test]
+    fn iter_quantities_by_commodity_name() {
+        let a = amount!(1, "EUR") + amount!(2, "$") + amount!(3, "AAPL");
+        let names: Vec<String> = a.iter_quantities().map(|q| q.s.name()).collect();
+        assert_eq!(names, vec!["$", "AAPL", "EUR"]);
+    }
+
+    #[test]
+    fn prints_commodities_in_the_same_order_however_built() {
+        let show = |a: &Amount| {
+            a.quantities()
+                .map(|q| q.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let a = amount!(1, "ORDER-B") + amount!(2, "ORDER-A");
+        let b = amount!(2, "ORDER-A") + amount!(1, "ORDER-B");
+        assert_eq!(show(&a), show(&b));
+        assert!(show(&a).find("ORDER-A") < show(&a).find("ORDER-B"));
+    }
+
     // --- valued_in returns self.clone() ---
 
     #[test]
@@ -308,6 +392,18 @@ mod test {
         assert_eq!(a.valued_in(Valuation::Market), a);
     }
 
+    #[test]
+    fn round_dp_rounds_each_commodity() {
+        let a = amount!(10, "$") / dec!(3) + amount!(2, "EUR") / dec!(3);
+        assert_eq!(a.round_dp(2), amount!(3.33, "$") + amount!(0.67, "EUR"));
+    }
+
+    #[test]
+    fn round_dp_drops_commodities_rounded_to_zero() {
+        let a = amount!(0.001, "$") + amount!(1, "EUR");
+        assert_eq!(a.round_dp(2), amount!(1, "EUR"));
+    }
+
     // --- Add<&Amount> for Amount ---
 
     #[test]
@@ -381,6 +477,7 @@ mod test {
             m_uprice: Amount::new(),
             h_uprice: Amount::new(),
             b_uprice: Amount::new(),
+            precision: misc::DEFAULT_VALUATION_PRECISION,
         };
 
         let c = a + lot;
@@ -451,6 +548,7 @@ mod test {
                 m_uprice: amount!(10, "$"),
                 h_uprice: Amount::new(),
                 b_uprice: Amount::new(),
+                precision: misc::DEFAULT_VALUATION_PRECISION,
             },
             AvgPosition {
                 qty: Quantity {
@@ -460,6 +558,7 @@ mod test {
                 m_uprice: amount!(20, "$"),
                 h_uprice: Amount::new(),
                 b_uprice: Amount::new(),
+                precision: misc::DEFAULT_VALUATION_PRECISION,
             },
         ];
 
@@ -480,7 +579,7 @@ mod test {
 
     #[test]
     fn sum_ref_amounts() {
-        let amounts = vec![amount!(10, "$"), amount!(20, "$"), amount!(30, "$")];
+        let amounts = [amount!(10, "$"), amount!(20, "$"), amount!(30, "$")];
         let total: Amount = amounts.iter().sum();
         assert_eq!(total, amount!(60, "$"));
     }
@@ -495,6 +594,13 @@ mod test {
         assert!(json.contains("$"));
     }
 
+    #[test]
+    fn size_key_adds_absolute_quantities() {
+        assert_eq!(amount!(-10, "$").size_key(), dec!(10));
+        assert_eq!((amount!(-10, "$") + amount!(3, "EUR")).size_key(), dec!(13));
+        assert_eq!(Amount::new().size_key(), dec!(0));
+    }
+
     // --- to_quantity ---
 
     #[test]
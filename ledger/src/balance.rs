@@ -2,7 +2,6 @@ use std::collections::BTreeMap;
 use std::iter::Sum;
 
 use chrono::NaiveDate;
-use regex::Regex;
 
 use crate::{
     account::{AccPostingSrc, Account},
@@ -13,6 +12,7 @@ use crate::{
     ledger::Ledger,
     ntypes::{Arithmetic, Basket, Valuable},
     pricedb::PriceDB,
+    query::Query,
     tamount::TAmount,
 };
 
@@ -20,7 +20,7 @@ use crate::{
 ///
 /// Each variant answers a different question about a position in
 /// a commodity.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Valuation {
     /// Book value: the literal cost recorded in the transaction via
     /// `@` / `@@`. Answers "how much did I actually pay?".
@@ -63,12 +63,12 @@ impl<'a> Balance<'a> {
         Self::default()
     }
 
-    /// Creates a new balance from the given ledger and optional regex
-    pub fn from_ledger<'b>(ledger: &'b Ledger, qry: &[Regex]) -> Balance<'b> {
+    /// Creates a new balance from the postings of the given ledger that
+    /// match `query`.
+    pub fn from_ledger<'b>(ledger: &'b Ledger, query: &Query) -> Balance<'b> {
         Balance {
             accnts: ledger
-                .get_all_posting()
-                .filter(|ps| qry.is_empty() || qry.iter().any(|r| r.is_match(ps.acc_name())))
+                .get_matching_postings(query)
                 .map(|ps| {
                     (
                         ps.acc_name().clone(),
@@ -135,6 +135,21 @@ impl<'a> Balance<'a> {
         date: NaiveDate,
         price_db: &PriceDB,
     ) -> BalanceView<HierAccountView<TAmount<V>>>
+    where
+        V: Arithmetic + Basket + Valuable + Sum<AvgPosition> + Send,
+    {
+        #[cfg(feature = "parallel")]
+        return self.par_balance_view_as_of(date, price_db);
+        #[cfg(not(feature = "parallel"))]
+        self.seq_balance_view_as_of(date, price_db)
+    }
+
+    #[cfg_attr(feature = "parallel", allow(dead_code))]
+    fn seq_balance_view_as_of<V>(
+        &self,
+        date: NaiveDate,
+        price_db: &PriceDB,
+    ) -> BalanceView<HierAccountView<TAmount<V>>>
     where
         V: Arithmetic + Basket + Valuable + Sum<AvgPosition>,
     {
@@ -145,6 +160,35 @@ impl<'a> Balance<'a> {
         })
     }
 
+    /// Like [`Balance::seq_balance_view_as_of`], with the accounts
+    /// valued across threads. The views are then added in account
+    /// order, as the sequential path does: average prices are
+    /// divisions, so adding them in another order could change the
+    /// last digits.
+    #[cfg(feature = "parallel")]
+    fn par_balance_view_as_of<V>(
+        &self,
+        date: NaiveDate,
+        price_db: &PriceDB,
+    ) -> BalanceView<HierAccountView<TAmount<V>>>
+    where
+        V: Arithmetic + Basket + Valuable + Sum<AvgPosition> + Send,
+    {
+        use rayon::prelude::*;
+
+        let accounts: Vec<&Account<'a>> = self.accounts().collect();
+        let views: Vec<_> = accounts
+            .par_iter()
+            .map(|acc| acc.to_hier_view_as_of(date, price_db))
+            .collect();
+        views
+            .into_iter()
+            .fold(BalanceView::new(), |mut balv, hier| {
+                balv += hier;
+                balv
+            })
+    }
+
     /// Returns a hierarchical balance view of all accounts at the
     /// given dates.
     pub fn to_balance_view_at_dates<V>(
@@ -153,7 +197,7 @@ impl<'a> Balance<'a> {
         at: impl Iterator<Item = NaiveDate>,
     ) -> BalanceView<HierAccountView<TAmount<V>>>
     where
-        V: Basket + Arithmetic + Valuable + Sum<AvgPosition>,
+        V: Basket + Arithmetic + Valuable + Sum<AvgPosition> + Send,
     {
         at.fold(
             BalanceView::<FlatAccountView<TAmount<V>>>::new(),
@@ -175,6 +219,37 @@ mod test {
     use crate::ntypes::Zero;
     use crate::{misc, util};
 
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_balance_view_matches_sequential() {
+        use crate::journal::Journal;
+
+        let mut input = String::new();
+        for i in 0..500 {
+            input += &format!(
+                "2026-01-{:02} xact {i}\n  Assets:Acc{}:Sub{}    {} AAPL @ $1{}.25\n  Expenses:E{}    ${}.{:02}\n  Equity:Opening\n\n",
+                i % 28 + 1,
+                i % 37,
+                i % 5,
+                i % 9 + 1,
+                i % 10,
+                i % 11,
+                i % 90 + 1,
+                i % 100,
+            );
+        }
+        let journal = Journal::parse(&input).unwrap();
+        let price_db = PriceDB::from_journal(&journal);
+        let ledger = Ledger::from_journal(&journal);
+        let bal = Balance::from_ledger(&ledger, &Query::All);
+
+        let at = NaiveDate::from_ymd_opt(2026, 1, 20).unwrap();
+        assert_eq!(
+            bal.par_balance_view_as_of::<Holdings>(at, &price_db),
+            bal.seq_balance_view_as_of::<Holdings>(at, &price_db)
+        );
+    }
+
     #[test]
     fn test_balance() {
         let input = "\
@@ -187,7 +262,7 @@ mod test {
                 .unwrap();
         let ledger = Ledger::from_journal(&journal);
 
-        let bal = Balance::from_ledger(&ledger, &[]);
+        let bal = Balance::from_ledger(&ledger, &Query::All);
         let total = bal.balance::<Holdings>(&price_db);
         assert!(total.is_zero());
 
@@ -222,8 +297,8 @@ mod test {
         let ledger = Ledger::from_journal(&journal);
 
         // Filter to only Assets accounts
-        let regex = Regex::new("Assets").unwrap();
-        let bal = Balance::from_ledger(&ledger, &[regex]);
+        let query = Query::parse(["Assets"]).unwrap();
+        let bal = Balance::from_ledger(&ledger, &query);
 
         assert_eq!(bal.accounts().count(), 1);
         assert!(bal.account(&AccName::from("Assets:Cash")).is_some());
@@ -242,7 +317,7 @@ mod test {
                 .unwrap();
         let ledger = Ledger::from_journal(&journal);
 
-        let bal = Balance::from_ledger(&ledger, &[]);
+        let bal = Balance::from_ledger(&ledger, &Query::All);
         assert_eq!(bal.accounts().count(), 2);
     }
 
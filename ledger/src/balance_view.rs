@@ -1,19 +1,72 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::mem;
 use std::ops::AddAssign;
 
+use regex::Regex;
+use rust_decimal::Decimal;
+
 use crate::account_view::{
     self, AccountView, CompactAccountView, FlatAccountView, HierAccountView, ValuebleAccountView,
 };
 
+use crate::amount::Amount;
 use crate::balance::Valuation;
+use crate::holdings::{AvgPosition, Holdings};
 use crate::journal::AccName;
-use crate::ntypes::{Arithmetic, TsBasket, Valuable};
+use crate::misc;
+use crate::ntypes::{Arithmetic, Basket, Quantities, TsBasket, Valuable};
+use crate::pricedb::PriceDB;
+use crate::quantity::Quantity;
+use crate::symbol::Symbol;
+use crate::tamount::TAmount;
 
 /// Represents a collection of `AccountView`
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
 pub struct BalanceView<T: AccountView> {
     accnts: BTreeMap<AccName, T>,
+    order: AccountOrder,
+}
+
+/// Order in which sibling accounts of a [`BalanceView`] are listed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum AccountOrder {
+    /// Alphabetically by name.
+    #[default]
+    Name,
+    /// By the magnitude of the balance at the last date, valued in
+    /// `valuation`; largest first when `descending`. Ties keep name
+    /// order.
+    Amount {
+        valuation: Valuation,
+        descending: bool,
+    },
+}
+
+impl AccountOrder {
+    /// Returns `accounts` (siblings in name order) in this order.
+    pub fn sort<'a, T>(&self, accounts: impl Iterator<Item = &'a T>) -> Vec<&'a T>
+    where
+        T: AccountView + 'a,
+        T::TsValue: TsBasket<B: Valuable>,
+    {
+        let mut accounts: Vec<_> = accounts.collect();
+        if let AccountOrder::Amount {
+            valuation,
+            descending,
+        } = *self
+        {
+            accounts.sort_by_cached_key(|a| {
+                let m = a
+                    .balance()
+                    .iter_baskets()
+                    .last()
+                    .map(|(_, b)| b.valued_in(valuation).size_key())
+                    .unwrap_or_default();
+                if descending { -m } else { m }
+            });
+        }
+        accounts
+    }
 }
 
 impl<T> BalanceView<T>
@@ -31,7 +84,10 @@ where
             .map(|(name, acc)| (name.clone(), acc.valued_in(v)))
             .collect();
 
-        BalanceView { accnts }
+        BalanceView {
+            accnts,
+            order: self.order,
+        }
     }
 }
 
@@ -39,6 +95,7 @@ impl<T: AccountView> BalanceView<T> {
     pub fn new() -> Self {
         BalanceView {
             accnts: BTreeMap::new(),
+            order: AccountOrder::Name,
         }
     }
 
@@ -57,6 +114,18 @@ impl<T: AccountView> BalanceView<T> {
         self.accnts.values()
     }
 
+    /// Order in which printers list sibling accounts.
+    pub fn order(&self) -> AccountOrder {
+        self.order
+    }
+
+    /// Sets the order in which printers list sibling accounts. The
+    /// order carries over to the flat, hierarchical, compact and
+    /// valued conversions of this view.
+    pub fn set_order(&mut self, order: AccountOrder) {
+        self.order = order;
+    }
+
     /// Consumes the balance and returns an iterator over its accounts.
     pub fn into_accounts(self) -> impl Iterator<Item = T> {
         self.accnts.into_values()
@@ -67,13 +136,16 @@ impl<T: AccountView> BalanceView<T> {
     /// All hierarchical accounts are flattened, resulting in a
     /// `Balance<FlatAccount>` where each account has a fully qualified name.
     pub fn to_flat(self) -> BalanceView<FlatAccountView<T::TsValue>> {
-        self.into_accounts().flat_map(|acc| acc.to_flat()).fold(
+        let order = self.order;
+        let mut flat = self.into_accounts().flat_map(|acc| acc.to_flat()).fold(
             BalanceView::new(),
             |mut bal, acc| {
                 bal += acc;
                 bal
             },
-        )
+        );
+        flat.order = order;
+        flat
     }
 
     /// Converts this balance into a fully hierarchical balance.
@@ -81,16 +153,21 @@ impl<T: AccountView> BalanceView<T> {
     /// Each account is expanded into a hierarchical representation
     /// (`HierAccountView`), preserving the full structure.
     pub fn to_hier(self) -> BalanceView<HierAccountView<T::TsValue>> {
-        self.into_accounts()
-            .map(|a| a.to_hier())
-            .fold(BalanceView::new(), |mut bal, acc| {
-                bal += acc;
-                bal
-            })
+        let order = self.order;
+        let mut hier =
+            self.into_accounts()
+                .map(|a| a.to_hier())
+                .fold(BalanceView::new(), |mut bal, acc| {
+                    bal += acc;
+                    bal
+                });
+        hier.order = order;
+        hier
     }
 
     /// Converts this balance into a compact hierarchical balance.
     pub fn to_compact(self) -> BalanceView<CompactAccountView<T::TsValue>> {
+        let order = self.order;
         let compact = self
             .to_hier()
             .into_accounts()
@@ -102,7 +179,10 @@ impl<T: AccountView> BalanceView<T> {
             })
             .collect();
 
-        BalanceView { accnts: compact }
+        BalanceView {
+            accnts: compact,
+            order,
+        }
     }
 }
 
@@ -129,6 +209,105 @@ where
 
         h.to_flat()
     }
+
+    /// Negates the balance of every account whose name matches `re`.
+    pub fn flip_sign(&mut self, re: &Regex) {
+        self.accnts
+            .values_mut()
+            .filter(|acc| re.is_match(acc.name()))
+            .for_each(|acc| acc.flip_sign());
+    }
+
+    /// Negates the balance of every account.
+    pub fn invert(&mut self) {
+        self.accnts.values_mut().for_each(|acc| acc.flip_sign());
+    }
+}
+
+impl<V> BalanceView<FlatAccountView<TAmount<V>>>
+where
+    V: Arithmetic + Basket,
+{
+    /// Replaces each account's time series with the change between
+    /// consecutive dates (see [`TAmount::deltas`]). The earliest date
+    /// becomes the opening point and is dropped from the view.
+    pub fn to_changes(&mut self) {
+        self.accnts.values_mut().for_each(|acc| {
+            let deltas = acc.balance().deltas();
+            *acc.balance_mut() = deltas;
+        });
+    }
+}
+
+impl BalanceView<FlatAccountView<TAmount<Holdings>>> {
+    /// Converts every balance into `target`. Each basket is first
+    /// valued in `v`, then the commodities other than `target` are
+    /// exchanged at the rate in effect on the basket's date (see
+    /// [`PriceDB::convert`]).
+    ///
+    /// On failure the view is left untouched and the `(from, to)`
+    /// pairs without a rate are returned, sorted by commodity name.
+    pub fn exchange(
+        &mut self,
+        target: Symbol,
+        v: Valuation,
+        price_db: &PriceDB,
+    ) -> Result<(), Vec<(Symbol, Symbol)>> {
+        let mut missing = BTreeSet::new();
+        let exchanged: Vec<TAmount<Holdings>> = self
+            .accnts
+            .values()
+            .map(|acc| {
+                acc.balance()
+                    .iter_baskets()
+                    .map(|(date, h)| {
+                        let at = misc::to_datetime(date);
+                        let mut total = Decimal::ZERO;
+                        for q in h.valued_in(v).quantities() {
+                            match price_db.convert(q, target, at) {
+                                Some(c) => total += c.q,
+                                None => {
+                                    missing.insert((q.s.name(), q.s));
+                                }
+                            }
+                        }
+                        (
+                            date,
+                            exchanged_holdings(total, target, price_db.valuation_precision()),
+                        )
+                    })
+                    .collect()
+            })
+            .collect();
+
+        if !missing.is_empty() {
+            return Err(missing.into_iter().map(|(_, s)| (s, target)).collect());
+        }
+        self.accnts
+            .values_mut()
+            .zip(exchanged)
+            .for_each(|(acc, balance)| *acc.balance_mut() = balance);
+        Ok(())
+    }
+}
+
+/// A single position of `q` units of `target`, worth itself under
+/// every valuation. `q` is rounded to `dp` places.
+fn exchanged_holdings(q: Decimal, target: Symbol, dp: u32) -> Holdings {
+    let unit = Amount::from_quantity(Quantity {
+        q: Decimal::ONE,
+        s: target,
+    });
+    Holdings::from_positions([AvgPosition {
+        qty: Quantity {
+            q: q.round_dp(dp),
+            s: target,
+        },
+        m_uprice: unit.clone(),
+        h_uprice: unit.clone(),
+        b_uprice: unit,
+        precision: dp,
+    }])
 }
 
 impl<T> BalanceView<HierAccountView<T>>
@@ -201,7 +380,7 @@ where
     V: Arithmetic + TsBasket,
 {
     fn add_assign(&mut self, rhs: HierAccountView<V>) {
-        if let Some(entry) = self.accnts.get_mut(&rhs.name()) {
+        if let Some(entry) = self.accnts.get_mut(rhs.name()) {
             *entry = account_view::utils::merge_hier_account(mem::take(entry), rhs);
         } else {
             self.accnts.insert(rhs.name().clone(), rhs);
@@ -242,7 +421,7 @@ where
     V: Arithmetic + TsBasket,
 {
     fn add_assign(&mut self, rhs: FlatAccountView<V>) {
-        if let Some(entry) = self.accnts.get_mut(&rhs.name()) {
+        if let Some(entry) = self.accnts.get_mut(rhs.name()) {
             *entry = account_view::utils::merge_flat_account(mem::take(entry), rhs);
         } else {
             self.accnts.insert(rhs.name().clone(), rhs);
@@ -284,10 +463,10 @@ mod test {
     use crate::amount;
     use crate::amount::Amount;
     use crate::balance::Valuation;
-    use crate::balance_view::BalanceView;
+    use crate::balance_view::{AccountOrder, BalanceView};
     use crate::holdings::{AvgPosition, Holdings};
     use crate::journal::AccName;
-    use crate::misc::today;
+    use crate::misc::{self, today};
     use crate::ntypes::TsBasket;
     use crate::quantity::Quantity;
     use crate::symbol::Symbol;
@@ -315,6 +494,7 @@ mod test {
             m_uprice: uprice(m),
             h_uprice: uprice(h),
             b_uprice: uprice(b),
+            precision: misc::DEFAULT_VALUATION_PRECISION,
         }
     }
 
@@ -497,6 +677,21 @@ mod test {
         );
     }
 
+    #[test]
+    fn flat_flip_sign_negates_matching_accounts() {
+        let mut bv: BalanceView<FlatAccountView<TAmount<Amount>>> = BalanceView::new();
+        bv += build_hier_account(AccName::from("Assets:Checking"), tamount!(100, "$")).unwrap();
+        bv += build_hier_account(AccName::from("Liabilities:Card"), tamount!(-40, "$")).unwrap();
+
+        bv.flip_sign(&regex::Regex::new("^Liabilities").unwrap());
+
+        let card = bv.account(&AccName::from("Liabilities:Card")).unwrap();
+        assert_eq!(card.balance(), &tamount!(40, "$"));
+        let checking = bv.account(&AccName::from("Assets:Checking")).unwrap();
+        assert_eq!(checking.balance(), &tamount!(100, "$"));
+        assert_eq!(bv.balance(), tamount!(140, "$"));
+    }
+
     #[test]
     fn flat_limit_depth_zero_returns_unchanged() {
         let mut bv: BalanceView<FlatAccountView<TAmount<Amount>>> = BalanceView::new();
@@ -512,6 +707,26 @@ mod test {
         );
     }
 
+    #[test]
+    fn limit_depth_preserves_the_total() {
+        let mut bv: BalanceView<FlatAccountView<TAmount<Amount>>> = BalanceView::new();
+        bv += build_hier_account(AccName::from("Assets:Bank"), tamount!(3, "$")).unwrap();
+        bv += build_hier_account(AccName::from("Assets:Bank:Checking"), tamount!(5, "$")).unwrap();
+        bv += build_hier_account(AccName::from("Assets:Bank:Checking:Sub"), tamount!(10, "$"))
+            .unwrap();
+        bv += build_hier_account(AccName::from("Assets:Cash"), tamount!(2, "$")).unwrap();
+        let total = bv.balance();
+
+        for depth in 1..=4 {
+            let flat = bv.clone().limit_accounts_depth(depth);
+            assert_eq!(flat.balance(), total, "flat, depth {depth}");
+            let hier = bv.clone().to_hier().limit_accounts_depth(depth);
+            assert_eq!(hier.balance(), total, "hier, depth {depth}");
+            let compact = bv.clone().to_compact().limit_accounts_depth(depth);
+            assert_eq!(compact.balance(), total, "compact, depth {depth}");
+        }
+    }
+
     #[test]
     fn flat_limit_depth_nonzero_trims_hierarchy() {
         let mut bv: BalanceView<FlatAccountView<TAmount<Amount>>> = BalanceView::new();
@@ -691,4 +906,32 @@ mod test {
             ])),
         );
     }
+
+    #[test]
+    fn amount_order_uses_the_valuation_and_survives_to_flat() {
+        let mut bv: BalanceView<HierAccountView<TAmount<Holdings>>> = BalanceView::new();
+        bv += hier("A", [lot("AAPL", dec!(10), dec!(50), dec!(50), dec!(50))]);
+        bv += hier("B", [lot("MSFT", dec!(5), dec!(200), dec!(200), dec!(200))]);
+
+        let names = |bv: &BalanceView<FlatAccountView<TAmount<Holdings>>>| -> Vec<String> {
+            bv.order()
+                .sort(bv.accounts())
+                .iter()
+                .map(|a| a.name().to_string())
+                .collect()
+        };
+
+        let mut by_qty = bv.clone();
+        by_qty.set_order(AccountOrder::Amount {
+            valuation: Valuation::Quantity,
+            descending: true,
+        });
+        assert_eq!(names(&by_qty.to_flat()), ["A", "B"]);
+
+        bv.set_order(AccountOrder::Amount {
+            valuation: Valuation::Market,
+            descending: true,
+        });
+        assert_eq!(names(&bv.to_flat()), ["B", "A"]);
+    }
 }
@@ -0,0 +1,161 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use crate::{
+    journal::{AccName, Journal, Virtuality},
+    misc::{self, Step},
+    quantity::Quantity,
+    symbol::Symbol,
+};
+
+/// Budgeted and actual amounts of one account, commodity and period.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BudgetRow {
+    /// First day of the period.
+    pub period: NaiveDate,
+    pub acc_name: AccName,
+    /// Sum of the periodic transaction postings falling in the period.
+    pub budgeted: Quantity,
+    /// Sum of the real postings falling in the period.
+    pub actual: Quantity,
+}
+
+impl BudgetRow {
+    /// What is left of the budget, `budgeted - actual`.
+    pub fn remaining(&self) -> Quantity {
+        Quantity {
+            q: self.budgeted.q - self.actual.q,
+            s: self.budgeted.s,
+        }
+    }
+}
+
+/// Compares the periodic transactions (`~ Monthly`) of `journal` with
+/// its real postings, bucketed in periods of kind `step`.
+///
+/// Only the accounts posted to by a periodic transaction are
+/// reported, leaving out its balancing posting (the one written
+/// without an amount, e.g. `Assets:Checking`); postings to their
+/// subaccounts count as actuals of the budgeted account. A budgeted
+/// account without activity in a period is still reported, with the
+/// whole budget remaining. The report spans `begin..=end`, each bound
+/// defaulting to the first and last transaction of the journal. Rows
+/// are sorted by period, account and commodity.
+pub fn budget(
+    journal: &Journal,
+    step: Step,
+    begin: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+) -> Vec<BudgetRow> {
+    let range = journal.date_range();
+    let (Some(begin), Some(end)) = (begin.or(range.map(|r| r.0)), end.or(range.map(|r| r.1)))
+    else {
+        return Vec::new();
+    };
+
+    // (period, account, commodity name) -> (budgeted, actual, commodity)
+    let mut rows = BTreeMap::<(NaiveDate, AccName, String), (Decimal, Decimal, Symbol)>::new();
+    let periodic = journal.generate_budget(misc::period_start(begin, step), end);
+    for p in periodic.iter().flat_map(|x| x.postings.iter()) {
+        let key = (
+            misc::period_start(p.date, step),
+            p.acc_name.clone(),
+            p.quantity.s.name(),
+        );
+        rows.entry(key)
+            .or_insert((Decimal::ZERO, Decimal::ZERO, p.quantity.s))
+            .0 += p.quantity.q;
+    }
+
+    let budgeted: BTreeSet<AccName> = rows.keys().map(|(_, acc, _)| acc.clone()).collect();
+    let actuals = journal
+        .postings()
+        .map(|(_, p)| p)
+        .filter(|p| p.virtuality == Virtuality::Real)
+        .filter(|p| p.date >= begin && p.date <= end);
+    for p in actuals {
+        let matching = budgeted
+            .iter()
+            .filter(|acc| **acc == p.acc_name || p.acc_name.is_descendant_of(acc));
+        for acc in matching {
+            let key = (
+                misc::period_start(p.date, step),
+                acc.clone(),
+                p.quantity.s.name(),
+            );
+            rows.entry(key)
+                .or_insert((Decimal::ZERO, Decimal::ZERO, p.quantity.s))
+                .1 += p.quantity.q;
+        }
+    }
+
+    rows.into_iter()
+        .map(|((period, acc_name, _), (budgeted, actual, s))| BudgetRow {
+            period,
+            acc_name,
+            budgeted: Quantity { q: budgeted, s },
+            actual: Quantity { q: actual, s },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::journal::JrnIO;
+    use crate::quantity;
+    use crate::util;
+
+    fn d(y: i32, m: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, day).unwrap()
+    }
+
+    #[test]
+    fn budget_without_activity_is_fully_remaining() {
+        let input = "\
+~ Monthly
+    Expenses:Food          $300
+    Expenses:Rent          $1,000.00
+    Assets:Checking
+
+2025-01-10 groceries
+  Expenses:Food:Market    $30
+  Assets:Checking
+
+2025-02-10 groceries
+  Expenses:Food    $50
+  Assets:Checking
+";
+        let (journal, _) =
+            util::read_journal_and_price_db(JrnIO::Reader(Box::new(input.as_bytes())), None)
+                .unwrap();
+
+        let rows = budget(&journal, Step::Months(1), None, None);
+        let food: Vec<_> = rows
+            .iter()
+            .filter(|r| &*r.acc_name == "Expenses:Food")
+            .map(|r| (r.period, r.remaining()))
+            .collect();
+        assert_eq!(
+            food,
+            vec![
+                (d(2025, 1, 1), quantity!(270, "$")),
+                (d(2025, 2, 1), quantity!(250, "$")),
+            ]
+        );
+
+        let rent = rows
+            .iter()
+            .find(|r| &*r.acc_name == "Expenses:Rent" && r.period == d(2025, 2, 1))
+            .unwrap();
+        assert_eq!(rent.actual, quantity!(0, "$"));
+        assert_eq!(rent.remaining(), quantity!(1000, "$"));
+
+        // the balancing posting is not a budget line
+        assert!(rows.iter().all(|r| &*r.acc_name != "Assets:Checking"));
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use chrono::NaiveDate;
+
+use crate::journal::Xact;
+
+/// A structural problem of a journal found by [`check`]. Each one
+/// names the transaction it was found in by date and payee.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// The transaction reuses the code of an earlier one, dated
+    /// `first` with payee `first_payee`.
+    DuplicateCode {
+        code: String,
+        date: NaiveDate,
+        payee: String,
+        first: NaiveDate,
+        first_payee: String,
+    },
+    /// The transaction is dated after today.
+    FutureDate { date: NaiveDate, payee: String },
+    /// The effective date comes before the transaction date.
+    EffectiveBeforeDate {
+        date: NaiveDate,
+        efdate: NaiveDate,
+        payee: String,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::DuplicateCode {
+                code,
+                date,
+                payee,
+                first,
+                first_payee,
+            } => write!(
+                f,
+                "{date} '{payee}': code ({code}) already used by {first} '{first_payee}'"
+            ),
+            Violation::FutureDate { date, payee } => {
+                write!(f, "{date} '{payee}': dated after today")
+            }
+            Violation::EffectiveBeforeDate {
+                date,
+                efdate,
+                payee,
+            } => write!(
+                f,
+                "{date} '{payee}': effective date {efdate} is before the transaction date"
+            ),
+        }
+    }
+}
+
+/// Checks the invariants that span transactions, which parsing
+/// doesn't cover (each transaction is already known to balance):
+///
+/// - no two transactions share a code; transactions without one are
+///   left out
+/// - no effective date comes before its transaction date
+/// - with `today` set, no transaction is dated after it
+///
+/// Violations are listed in the order of `xacts`.
+pub fn check<'a>(
+    xacts: impl Iterator<Item = &'a Xact>,
+    today: Option<NaiveDate>,
+) -> Vec<Violation> {
+    let mut codes: HashMap<&str, &Xact> = HashMap::new();
+    let mut violations = Vec::new();
+    for xact in xacts {
+        let date = xact.date.txdate;
+        if !xact.code.is_empty() {
+            if let Some(first) = codes.get(xact.code.as_str()) {
+                violations.push(Violation::DuplicateCode {
+                    code: xact.code.clone(),
+                    date,
+                    payee: xact.payee.clone(),
+                    first: first.date.txdate,
+                    first_payee: first.payee.clone(),
+                });
+            } else {
+                codes.insert(&xact.code, xact);
+            }
+        }
+        if let Some(efdate) = xact.date.efdate
+            && efdate < date
+        {
+            violations.push(Violation::EffectiveBeforeDate {
+                date,
+                efdate,
+                payee: xact.payee.clone(),
+            });
+        }
+        if let Some(today) = today
+            && date > today
+        {
+            violations.push(Violation::FutureDate {
+                date,
+                payee: xact.payee.clone(),
+            });
+        }
+    }
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::journal::Journal;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn reports_cross_transaction_violations() {
+        let journal = Journal::parse(
+            "\
+2025-01-01 (101) rent
+  Expenses:Rent    $500
+  Assets:Cash
+
+2025-01-05=2025-01-03 groceries
+  Expenses:Food    $10
+  Assets:Cash
+
+2025-02-01 (101) rent again
+  Expenses:Rent    $500
+  Assets:Cash
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            check(journal.xacts(), Some(date(2025, 1, 31))),
+            vec![
+                Violation::EffectiveBeforeDate {
+                    date: date(2025, 1, 5),
+                    efdate: date(2025, 1, 3),
+                    payee: "groceries".to_owned(),
+                },
+                Violation::DuplicateCode {
+                    code: "101".to_owned(),
+                    date: date(2025, 2, 1),
+                    payee: "rent again".to_owned(),
+                    first: date(2025, 1, 1),
+                    first_payee: "rent".to_owned(),
+                },
+                Violation::FutureDate {
+                    date: date(2025, 2, 1),
+                    payee: "rent again".to_owned(),
+                },
+            ]
+        );
+        assert_eq!(check(journal.xacts(), None).len(), 2);
+    }
+}
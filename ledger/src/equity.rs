@@ -0,0 +1,196 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use crate::{
+    account_view::{AccountView, FlatAccountView},
+    balance_view::BalanceView,
+    holdings::{AvgPosition, Holdings},
+    journal::{AccName, LotPrice, Posting, Provenance, State, Virtuality, Xact, XactDate},
+    ntypes::TsBasket,
+    pricedb::PriceType,
+    quantity::Quantity,
+    tamount::TAmount,
+};
+
+/// Account the opening balances are offset against.
+pub const OPENING_ACCOUNT: &str = "Equity:Opening Balances";
+
+/// Builds a transaction dated `date` that opens every account of
+/// `bal` at its balance on `date`, one posting per commodity.
+///
+/// Accounts are listed by name and commodities by symbol name. A
+/// holding with a book price in another commodity keeps it (`10 AAPL @
+/// $100`), so it is offset at cost. The offset goes to
+/// [`OPENING_ACCOUNT`], one posting per commodity whose total isn't
+/// zero, so the transaction always balances.
+pub fn opening_xact(
+    bal: &BalanceView<FlatAccountView<TAmount<Holdings>>>,
+    date: NaiveDate,
+) -> Xact {
+    let mut postings = Vec::new();
+    let mut totals: BTreeMap<String, Quantity> = BTreeMap::new();
+    for acc in bal.accounts() {
+        let Some(holdings) = acc.balance().at(date) else {
+            continue;
+        };
+        let mut positions: Vec<&AvgPosition> = holdings
+            .iter_positions()
+            .map(|(_, p)| p)
+            .filter(|p| !p.qty.q.is_zero())
+            .collect();
+        positions.sort_by_cached_key(|p| p.qty.s.name());
+        for pos in positions {
+            let uprice = pos
+                .b_uprice
+                .to_quantity()
+                .filter(|u| u.s != pos.qty.s)
+                .map(|u| Quantity {
+                    q: u.q.round_dp(pos.precision),
+                    s: u.s,
+                })
+                .unwrap_or(Quantity {
+                    q: Decimal::ONE,
+                    s: pos.qty.s,
+                });
+            let value = uprice * pos.qty.q;
+            totals
+                .entry(value.s.name())
+                .and_modify(|t| t.q += value.q)
+                .or_insert(value);
+            postings.push(posting(acc.name().clone(), pos.qty, uprice, date));
+        }
+    }
+
+    for total in totals.into_values().filter(|t| !t.q.is_zero()) {
+        let offset = Quantity {
+            q: Decimal::ZERO - total.q,
+            s: total.s,
+        };
+        let unit = Quantity {
+            q: Decimal::ONE,
+            s: offset.s,
+        };
+        postings.push(posting(AccName::from(OPENING_ACCOUNT), offset, unit, date));
+    }
+
+    Xact {
+        id: 0,
+        state: State::None,
+        code: String::new(),
+        date: XactDate {
+            txdate: date,
+            efdate: None,
+            effective: false,
+        },
+        payee: String::from("Opening Balances"),
+        comment: String::new(),
+        postings,
+        tags: Vec::new(),
+        vtags: HashMap::new(),
+    }
+}
+
+fn posting(acc_name: AccName, quantity: Quantity, uprice: Quantity, date: NaiveDate) -> Posting {
+    Posting {
+        date,
+        state: State::None,
+        acc_name,
+        virtuality: Virtuality::Real,
+        quantity,
+        uprice,
+        lot_uprice: LotPrice {
+            price: uprice,
+            ptype: PriceType::Floating,
+        },
+        total_price: None,
+        total_lot_price: None,
+        written: None,
+        lot_date: None,
+        lot_note: String::new(),
+        assertion: None,
+        comment: String::new(),
+        tags: Vec::new(),
+        vtags: HashMap::new(),
+        provenance: Provenance::Equity,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::balance::Balance;
+    use crate::journal::JrnIO;
+    use crate::ledger::Ledger;
+    use crate::quantity;
+    use crate::query::Query;
+    use crate::util;
+
+    #[test]
+    fn opening_xact_balances_at_cost() {
+        let input = "\
+2026-01-01 buy
+  Assets:Broker    10 AAPL @ $100
+  Assets:Cash      $-400
+  Liabilities:Card
+";
+        let bytes = input.to_owned().into_bytes();
+        let (journal, price_db) =
+            util::read_journal_and_price_db(JrnIO::Reader(Box::new(Cursor::new(bytes))), None)
+                .unwrap();
+        let date = NaiveDate::from_ymd_opt(2026, 2, 1).unwrap();
+        let bal = Balance::from_ledger(&Ledger::from_journal(&journal), &Query::All)
+            .to_balance_view_at_dates::<Holdings>(&price_db, std::iter::once(date))
+            .to_flat();
+
+        let xact = opening_xact(&bal, date);
+        let total: Decimal = xact.postings.iter().map(|p| p.book_value().q).sum();
+        assert_eq!(total, Decimal::ZERO);
+        assert!(
+            xact.postings
+                .iter()
+                .all(|p| &*p.acc_name != OPENING_ACCOUNT)
+        );
+        assert!(
+            xact.postings
+                .iter()
+                .all(|p| p.provenance == Provenance::Equity && p.is_generated())
+        );
+    }
+
+    #[test]
+    fn opening_xact_offsets_a_partial_balance() {
+        let input = "\
+2026-01-01 buy
+  Assets:Broker    10 AAPL @ $100
+  Assets:Cash      $-400
+  Liabilities:Card
+";
+        let bytes = input.to_owned().into_bytes();
+        let (journal, price_db) =
+            util::read_journal_and_price_db(JrnIO::Reader(Box::new(Cursor::new(bytes))), None)
+                .unwrap();
+        let date = NaiveDate::from_ymd_opt(2026, 2, 1).unwrap();
+        let query = Query::parse(["Assets"]).unwrap();
+        let bal = Balance::from_ledger(&Ledger::from_journal(&journal), &query)
+            .to_balance_view_at_dates::<Holdings>(&price_db, std::iter::once(date))
+            .to_flat();
+
+        let xact = opening_xact(&bal, date);
+        let offsets: Vec<_> = xact
+            .postings
+            .iter()
+            .filter(|p| &*p.acc_name == OPENING_ACCOUNT)
+            .collect();
+        assert_eq!(offsets.len(), 1);
+        assert_eq!(offsets[0].quantity, quantity!(-600, "$"));
+        assert_eq!(offsets[0].provenance, Provenance::Equity);
+        let total: Decimal = xact.postings.iter().map(|p| p.book_value().q).sum();
+        assert_eq!(total, Decimal::ZERO);
+    }
+}
@@ -0,0 +1,390 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use crate::{
+    journal::{AccName, Posting, Xact},
+    quantity::Quantity,
+    query::Query,
+    symbol::{Styles, Symbol},
+};
+
+/// The part of a sale matched against one open lot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Realized {
+    /// Date of the sale.
+    pub date: NaiveDate,
+    /// Account the units were sold from.
+    pub acc_name: AccName,
+    /// Units sold out of the lot, always positive.
+    pub qty: Quantity,
+    /// Date the lot was acquired: its lot date when the buy carries
+    /// one (`[2026-01-01]`), the posting date otherwise.
+    pub acquired: NaiveDate,
+    /// Unit cost of the lot.
+    pub cost: Quantity,
+    /// Unit sale price (`@ $120`).
+    pub proceeds: Quantity,
+}
+
+impl Realized {
+    /// Realized gain, `(proceeds - cost) * qty`, in the cost
+    /// commodity and rounded to `dp` places. `None` when the lot was
+    /// bought and sold in different commodities.
+    pub fn gain(&self, dp: u32) -> Option<Quantity> {
+        if self.proceeds.s != self.cost.s {
+            return None;
+        }
+        let q = (self.proceeds.q - self.cost.q) * self.qty.q;
+        Some(Quantity {
+            q: q.round_dp(dp),
+            s: self.cost.s,
+        })
+    }
+}
+
+/// A sale for more units than the account held in matching lots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OversoldError {
+    pub date: NaiveDate,
+    pub acc_name: AccName,
+    /// Units the sale asked for.
+    pub wanted: Quantity,
+    /// Units held in the lots the sale could match.
+    pub held: Quantity,
+}
+
+impl OversoldError {
+    /// The error message, its amounts written as in the journal whose
+    /// `styles` are given.
+    pub fn message(&self, styles: &Styles) -> String {
+        format!(
+            "sale of {} from {} on {} exceeds the {} held",
+            self.wanted.display(styles),
+            self.acc_name,
+            self.date,
+            self.held.display(styles)
+        )
+    }
+}
+
+impl fmt::Display for OversoldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message(&Styles::default()))
+    }
+}
+
+impl std::error::Error for OversoldError {}
+
+/// How a sale picks the open lots it closes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum LotStrategy {
+    /// Oldest acquisition first.
+    #[default]
+    Fifo,
+    /// Newest acquisition first.
+    Lifo,
+    /// The lots are merged into one at their weighted-average cost
+    /// before each sale.
+    Average,
+}
+
+#[derive(Debug)]
+struct OpenLot {
+    qty: Decimal,
+    cost: Quantity,
+    acquired: NaiveDate,
+    lot_date: Option<NaiveDate>,
+}
+
+/// Computes the realized gains of the postings matching `query`,
+/// matching each sale against the open lots as `strategy` says.
+///
+/// Lots are the postings whose cost is in another commodity (e.g. `10
+/// AAPL @ $100` or `10 AAPL {$100}`). Transactions are applied in date
+/// order. A sale that names a lot price or date (`-5 AAPL {$100}
+/// [2026-01-01] @ $120`) only consumes the lots with that price and
+/// date. Only sales dated within `begin..=end` are reported, but
+/// every earlier buy is taken into account.
+pub fn realized<'a>(
+    xacts: impl Iterator<Item = &'a Xact>,
+    query: &Query,
+    begin: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+    strategy: LotStrategy,
+) -> Result<Vec<Realized>, OversoldError> {
+    let mut postings: Vec<&Posting> = xacts
+        .flat_map(|x| x.postings.iter().map(move |p| (x, p)))
+        .filter(|(x, p)| p.lot_uprice.price.s != p.quantity.s && query.eval(x, p))
+        .map(|(_, p)| p)
+        .filter(|p| end.is_none_or(|e| p.date <= e))
+        .collect();
+    postings.sort_by_key(|p| p.date);
+
+    let mut open = HashMap::<(AccName, Symbol), Vec<OpenLot>>::new();
+    let mut realized = Vec::new();
+    for p in postings {
+        let lots = open.entry((p.acc_name.clone(), p.quantity.s)).or_default();
+        if p.quantity.q.is_sign_positive() {
+            let lot = OpenLot {
+                qty: p.quantity.q,
+                cost: p.lot_uprice.price,
+                acquired: p.lot_date.unwrap_or(p.date),
+                lot_date: p.lot_date,
+            };
+            // stable: lots acquired the same day keep their order
+            let at = lots.partition_point(|l| l.acquired <= lot.acquired);
+            lots.insert(at, lot);
+            continue;
+        }
+
+        let matches = |l: &OpenLot| {
+            (p.lot_date.is_none() || l.lot_date == p.lot_date)
+                && (!p.names_lot_price() || l.cost == p.lot_uprice.price)
+        };
+        let wanted = -p.quantity.q;
+        let held: Decimal = lots.iter().filter(|l| matches(l)).map(|l| l.qty).sum();
+        if held < wanted {
+            return Err(OversoldError {
+                date: p.date,
+                acc_name: p.acc_name.clone(),
+                wanted: Quantity {
+                    q: wanted,
+                    s: p.quantity.s,
+                },
+                held: Quantity {
+                    q: held,
+                    s: p.quantity.s,
+                },
+            });
+        }
+
+        if strategy == LotStrategy::Average {
+            average_lots(lots, matches);
+        }
+        let candidates: Box<dyn Iterator<Item = &mut OpenLot>> = match strategy {
+            LotStrategy::Lifo => Box::new(lots.iter_mut().rev()),
+            LotStrategy::Fifo | LotStrategy::Average => Box::new(lots.iter_mut()),
+        };
+
+        let mut left = wanted;
+        for lot in candidates.filter(|l| matches(l)) {
+            if left.is_zero() {
+                break;
+            }
+            let take = left.min(lot.qty);
+            lot.qty -= take;
+            left -= take;
+            if begin.is_none_or(|b| p.date >= b) {
+                realized.push(Realized {
+                    date: p.date,
+                    acc_name: p.acc_name.clone(),
+                    qty: Quantity {
+                        q: take,
+                        s: p.quantity.s,
+                    },
+                    acquired: lot.acquired,
+                    cost: lot.cost,
+                    proceeds: p.uprice,
+                });
+            }
+        }
+        lots.retain(|l| !l.qty.is_zero());
+    }
+
+    Ok(realized)
+}
+
+/// Merges the lots selected by `matches` that share a cost commodity
+/// into one at their weighted-average unit cost, dated at the earliest
+/// acquisition.
+fn average_lots(lots: &mut Vec<OpenLot>, matches: impl Fn(&OpenLot) -> bool) {
+    let (selected, mut rest): (Vec<_>, Vec<_>) = lots.drain(..).partition(|l| matches(l));
+    let mut merged: Vec<OpenLot> = Vec::new();
+    for lot in selected {
+        match merged.iter_mut().find(|m| m.cost.s == lot.cost.s) {
+            Some(m) => {
+                let qty = m.qty + lot.qty;
+                m.cost.q = (m.cost.q * m.qty + lot.cost.q * lot.qty) / qty;
+                m.qty = qty;
+                m.acquired = m.acquired.min(lot.acquired);
+            }
+            None => merged.push(lot),
+        }
+    }
+    rest.extend(merged);
+    rest.sort_by_key(|l| l.acquired);
+    *lots = rest;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::journal::JrnIO;
+    use crate::misc;
+    use crate::quantity;
+    use crate::util;
+
+    fn d(y: i32, m: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, day).unwrap()
+    }
+
+    fn realize(input: &str, strategy: LotStrategy) -> Result<Vec<Realized>, OversoldError> {
+        let bytes = input.to_owned().into_bytes();
+        let (journal, _) =
+            util::read_journal_and_price_db(JrnIO::Reader(Box::new(Cursor::new(bytes))), None)
+                .unwrap();
+        realized(journal.xacts(), &Query::All, None, None, strategy)
+    }
+
+    fn gains(input: &str) -> Result<Vec<Realized>, OversoldError> {
+        realize(input, LotStrategy::Fifo)
+    }
+
+    fn gains_with(input: &str, strategy: LotStrategy) -> Vec<Quantity> {
+        realize(input, strategy)
+            .unwrap()
+            .iter()
+            .map(|r| r.gain(misc::DEFAULT_VALUATION_PRECISION).unwrap())
+            .collect()
+    }
+
+    const BUY_BUY_SELL: &str = "\
+2026-01-01 buy
+  Assets:Broker    10 AAPL @ $100
+  Assets:Cash
+
+2026-02-01 buy
+  Assets:Broker    10 AAPL @ $120
+  Assets:Cash
+
+2026-03-01 sell
+  Assets:Broker    -10 AAPL @ $130
+  Assets:Cash
+";
+
+    #[test]
+    fn strategy_picks_the_lots_a_sale_closes() {
+        assert_eq!(
+            gains_with(BUY_BUY_SELL, LotStrategy::Fifo),
+            vec![quantity!(300, "$")]
+        );
+        assert_eq!(
+            gains_with(BUY_BUY_SELL, LotStrategy::Lifo),
+            vec![quantity!(100, "$")]
+        );
+        assert_eq!(
+            gains_with(BUY_BUY_SELL, LotStrategy::Average),
+            vec![quantity!(200, "$")]
+        );
+    }
+
+    #[test]
+    fn lifo_spills_over_into_older_lots() {
+        let input = BUY_BUY_SELL.replace("-10 AAPL", "-15 AAPL");
+        assert_eq!(
+            gains_with(&input, LotStrategy::Lifo),
+            vec![quantity!(100, "$"), quantity!(150, "$")]
+        );
+    }
+
+    #[test]
+    fn sale_consumes_oldest_lots_first() {
+        let input = "\
+2026-01-01 buy
+  Assets:Broker    10 AAPL @ $100
+  Assets:Cash
+
+2026-02-01 buy
+  Assets:Broker    10 AAPL @ $120
+  Assets:Cash
+
+2026-03-01 sell
+  Assets:Broker    -15 AAPL @ $130
+  Assets:Cash
+";
+        let realized = gains(input).unwrap();
+        assert_eq!(realized.len(), 2);
+        assert_eq!(realized[0].acquired, d(2026, 1, 1));
+        assert_eq!(
+            realized[0].gain(misc::DEFAULT_VALUATION_PRECISION),
+            Some(quantity!(300, "$"))
+        );
+        assert_eq!(realized[1].qty, quantity!(5, "AAPL"));
+        assert_eq!(
+            realized[1].gain(misc::DEFAULT_VALUATION_PRECISION),
+            Some(quantity!(50, "$"))
+        );
+    }
+
+    #[test]
+    fn sale_naming_a_lot_date_only_consumes_that_lot() {
+        let input = "\
+2026-01-01 buy
+  Assets:Broker    10 AAPL {$100} [2026-01-01]
+  Assets:Cash
+
+2026-02-01 buy
+  Assets:Broker    10 AAPL {$120} [2026-02-01]
+  Assets:Cash
+
+2026-03-01 sell
+  Assets:Broker    -5 AAPL {$120} [2026-02-01] @ $130
+  Assets:Cash
+";
+        let realized = gains(input).unwrap();
+        assert_eq!(realized.len(), 1);
+        assert_eq!(realized[0].cost, quantity!(120, "$"));
+        assert_eq!(
+            realized[0].gain(misc::DEFAULT_VALUATION_PRECISION),
+            Some(quantity!(50, "$"))
+        );
+    }
+
+    #[test]
+    fn sale_naming_a_lot_price_only_consumes_that_lot() {
+        let input = "\
+2026-01-01 buy
+  Assets:Broker    10 AAPL {$100}
+  Assets:Cash
+
+2026-02-01 buy
+  Assets:Broker    10 AAPL {$120}
+  Assets:Cash
+
+2026-03-01 sell
+  Assets:Broker    -5 AAPL {$120} @ $130
+  Assets:Cash
+";
+        let realized = gains(input).unwrap();
+        assert_eq!(realized.len(), 1);
+        assert_eq!(realized[0].acquired, d(2026, 2, 1));
+        assert_eq!(realized[0].cost, quantity!(120, "$"));
+        assert_eq!(
+            realized[0].gain(misc::DEFAULT_VALUATION_PRECISION),
+            Some(quantity!(50, "$"))
+        );
+    }
+
+    #[test]
+    fn selling_more_than_held_is_an_error() {
+        let input = "\
+2026-01-01 buy
+  Assets:Broker    5 AAPL @ $100
+  Assets:Cash
+
+2026-03-01 sell
+  Assets:Broker    -6 AAPL @ $130
+  Assets:Cash
+";
+        let err = gains(input).unwrap_err();
+        assert_eq!(err.wanted, quantity!(6, "AAPL"));
+        assert_eq!(err.held, quantity!(5, "AAPL"));
+    }
+}
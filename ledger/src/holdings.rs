@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use std::iter::Sum;
 use std::ops::{Add, AddAssign, Sub, SubAssign};
@@ -24,6 +24,9 @@ pub struct AvgPosition {
     pub h_uprice: Amount,
     /// Book unit price
     pub b_uprice: Amount,
+    /// Decimal places kept on the price-based valuations, see
+    /// [`PriceDB::valuation_precision`](crate::pricedb::PriceDB::valuation_precision).
+    pub precision: u32,
 }
 
 impl AvgPosition {
@@ -36,20 +39,23 @@ impl AvgPosition {
 }
 
 impl Valuable for AvgPosition {
+    /// Price-based valuations are rounded to
+    /// [`AvgPosition::precision`] places.
     fn valued_in(&self, v: Valuation) -> Amount {
         let q = self.qty.q;
-        match v {
-            Valuation::Quantity => self.qty.to_amount(),
+        let value = match v {
+            Valuation::Quantity => return self.qty.to_amount(),
             Valuation::Market => self.m_uprice.clone() * q,
             Valuation::Historical => self.h_uprice.clone() * q,
             Valuation::Basis => self.b_uprice.clone() * q,
-        }
+        };
+        value.round_dp(self.precision)
     }
 }
 
 #[derive(Clone, PartialEq, Eq, Default, Debug)]
 pub struct Holdings {
-    qs: HashMap<Symbol, AvgPosition>,
+    qs: BTreeMap<Symbol, AvgPosition>,
 }
 
 impl Holdings {
@@ -75,8 +81,9 @@ impl Zero for Holdings {
 }
 
 impl Quantities for Holdings {
+    /// In commodity order, see [`Symbol`]'s `Ord`.
     fn quantities(&self) -> impl Iterator<Item = Quantity> {
-        self.qs.iter().map(|(_, l)| l.qty)
+        self.qs.values().map(|l| l.qty)
     }
 }
 
@@ -253,6 +260,7 @@ mod test {
     use rust_decimal::dec;
 
     use super::*;
+    use crate::misc;
 
     /// Constructs an `Amount` denominated in `$` with the given quantity.
     fn uprice(q: rust_decimal::Decimal) -> Amount {
@@ -279,6 +287,7 @@ mod test {
             m_uprice: uprice(m),
             h_uprice: uprice(h),
             b_uprice: uprice(b),
+            precision: misc::DEFAULT_VALUATION_PRECISION,
         }
     }
 
@@ -586,6 +595,21 @@ mod test {
         assert_eq!(h.arity(), 0);
     }
 
+    #[test]
+    fn valued_in_rounds_divided_prices_to_valuation_precision() {
+        // $10 / 3 per unit has a repeating decimal expansion
+        let mut p = lot("AAPL", dec!(1), dec!(10) / dec!(3), dec!(1), dec!(1));
+        assert_eq!(p.valued_in(Valuation::Market), uprice(dec!(3.33333333)));
+        p.precision = 2;
+        assert_eq!(p.valued_in(Valuation::Market), uprice(dec!(3.33)));
+        // quantities are never rounded
+        let p = lot("AAPL", dec!(1) / dec!(3), dec!(1), dec!(1), dec!(1));
+        assert_eq!(
+            p.valued_in(Valuation::Quantity).to_quantity().unwrap().q,
+            dec!(1) / dec!(3)
+        );
+    }
+
     #[test]
     fn arity_single_symbol() {
         let h = Holdings::from_positions([lot("AAPL", dec!(10), dec!(100), dec!(100), dec!(100))]);
@@ -1,7 +1,9 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
 use crate::{
     journal::{AccName, Xact},
+    pricedb::MarketPrice,
+    query::Query,
     symbol::Symbol,
 };
 
@@ -35,6 +37,46 @@ pub fn scan<'a>(xacts: impl Iterator<Item = &'a Xact>) -> JnlInfo {
     }
 }
 
+/// Collects the commodities used by postings, in their amount or their
+/// price, and by `P` price records, sorted by name. Each one comes with
+/// the number of postings that reference it; commodities only seen in
+/// price records have a count of 0.
+pub fn commodities<'a>(
+    xacts: impl Iterator<Item = &'a Xact>,
+    prices: impl Iterator<Item = &'a MarketPrice>,
+) -> Vec<(Symbol, usize)> {
+    let mut counts = BTreeMap::new();
+
+    for p in xacts.flat_map(|x| x.postings.iter()) {
+        *counts.entry(p.quantity.s).or_insert(0) += 1;
+        if p.uprice.s != p.quantity.s {
+            *counts.entry(p.uprice.s).or_insert(0) += 1;
+        }
+    }
+    for mp in prices {
+        counts.entry(mp.sym).or_insert(0);
+        counts.entry(mp.price.s).or_insert(0);
+    }
+
+    let mut commodities: Vec<_> = counts.into_iter().filter(|(s, _)| !s.is_empty()).collect();
+    commodities.sort_by_cached_key(|(s, _)| s.name());
+    commodities
+}
+
+/// Groups the transactions with a posting matching `query` by payee.
+/// Returns each payee with the number of transactions it appears in,
+/// sorted by payee.
+pub fn payees<'a>(xacts: impl Iterator<Item = &'a Xact>, query: &Query) -> Vec<(String, usize)> {
+    let mut counts = BTreeMap::new();
+    for xact in xacts {
+        if !query.matches_xact(xact) {
+            continue;
+        }
+        *counts.entry(xact.payee.clone()).or_insert(0) += 1;
+    }
+    counts.into_iter().collect()
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Cursor;
@@ -113,6 +155,60 @@ mod tests {
         );
     }
 
+    #[test]
+    fn commodities_counted_by_posting() {
+        let input = "\
+2026-01-01 buy
+  Assets:Broker   10 AAPL @ $30
+  Assets:Cash
+
+P 2026-02-01 EUR $1.10
+";
+        let bytes = input.to_owned().into_bytes();
+        let (journal, _) =
+            util::read_journal_and_price_db(JrnIO::Reader(Box::new(Cursor::new(bytes))), None)
+                .unwrap();
+        let commodities = commodities(journal.xacts(), journal.market_prices());
+        assert_eq!(
+            commodities,
+            vec![
+                (Symbol::new("$"), 2),
+                (Symbol::new("AAPL"), 1),
+                (Symbol::new("EUR"), 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn payees_counted_and_filtered() {
+        let input = "\
+2026-01-01 Grocery
+  A   $1
+  B  $-1
+
+2026-01-02 Salary
+  A   $1
+  B  $-1
+
+2026-01-03 Grocery
+  A   $1
+  B  $-1
+";
+        let bytes = input.to_owned().into_bytes();
+        let (journal, _) =
+            util::read_journal_and_price_db(JrnIO::Reader(Box::new(Cursor::new(bytes))), None)
+                .unwrap();
+        assert_eq!(
+            payees(journal.xacts(), &Query::All),
+            vec![("Grocery".to_string(), 2), ("Salary".to_string(), 1)]
+        );
+        let query = Query::parse(["@(?i)^sal"]).unwrap();
+        assert_eq!(
+            payees(journal.xacts(), &query),
+            vec![("Salary".to_string(), 1)]
+        );
+    }
+
     #[test]
     fn empty_symbol_excluded_from_commodities() {
         let input = "\
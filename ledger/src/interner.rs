@@ -5,12 +5,16 @@ use std::collections::HashMap;
 /// Each string is stored only once and assigned a unique index (`usize`),
 /// allowing efficient comparisons and storage using indices instead of full strings.
 /// The empty string is guaranteed to always have index `0` by default.
+///
+/// Interned strings are leaked: they live as long as the program, as
+/// the interners themselves do, so their names can be handed out as
+/// `&'static str` and read without holding a lock.
 #[derive(Default)]
 pub struct Interner {
     /// Maps each string to its unique index
-    map: HashMap<String, usize>,
+    map: HashMap<&'static str, usize>,
     /// Stores interned strings in order
-    vec: Vec<String>,
+    vec: Vec<&'static str>,
 }
 
 impl Interner {
@@ -34,8 +38,9 @@ impl Interner {
             return idx;
         }
         let idx = self.map.len();
-        self.map.insert(name.to_owned(), idx);
-        self.vec.push(name.to_owned());
+        let name: &'static str = Box::leak(name.into());
+        self.map.insert(name, idx);
+        self.vec.push(name);
         idx
     }
 
@@ -43,8 +48,13 @@ impl Interner {
     ///
     /// # Panics
     /// Panics if the index does not exist in the interner.
-    pub fn name(&self, idx: usize) -> &str {
-        self.vec[idx].as_str()
+    pub fn name(&self, idx: usize) -> &'static str {
+        self.vec[idx]
+    }
+
+    /// Number of interned strings, the empty one included.
+    pub fn len(&self) -> usize {
+        self.vec.len()
     }
 }
 
@@ -58,6 +68,15 @@ mod test {
         assert_eq!(interner.name(0), "");
     }
 
+    #[test]
+    fn test_len_counts_distinct_strings() {
+        let mut interner = Interner::with_capacity(10);
+        interner.intern("a");
+        interner.intern("b");
+        interner.intern("a");
+        assert_eq!(interner.len(), 3);
+    }
+
     #[test]
     fn test_intern_empty_string_twice_returns_same_index() {
         let mut interner = Interner::with_capacity(10);
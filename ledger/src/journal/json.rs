@@ -37,7 +37,7 @@ pub fn decode(content: &str) -> Result<Vec<journal::Xact>, parser::ParseError> {
 
 #[cfg(test)]
 mod test {
-    use crate::journal::{parse_xacts_json, parse_xacts_ledger};
+    use crate::journal::{Provenance, parse_xacts_json, parse_xacts_ledger};
     use crate::printing::{self, Fmt};
 
     const SAMPLE: &str = "\
@@ -55,7 +55,7 @@ mod test {
     /// `print --fmt FMT` emits.
     fn render(xacts: &[crate::journal::Xact], fmt: Fmt) -> String {
         let mut buf = Vec::new();
-        printing::print::print(&mut buf, xacts.iter(), fmt).unwrap();
+        printing::print::print(&mut buf, xacts.iter(), fmt, &printing::Options::default()).unwrap();
         String::from_utf8(buf).unwrap()
     }
 
@@ -63,6 +63,7 @@ mod test {
     fn json_roundtrip_matches_tty() {
         let tty = parse_xacts_ledger(SAMPLE).unwrap();
         let json = render(&tty, Fmt::Json);
+        assert_eq!(tty[0].postings[1].provenance, Provenance::Inferred);
         let from_json = parse_xacts_json(&json).unwrap();
         assert_eq!(tty, from_json);
     }
@@ -11,7 +11,7 @@ pub fn decode(content: &str) -> Result<Vec<journal::Xact>, parser::ParseError> {
 
 #[cfg(test)]
 mod test {
-    use crate::journal::{parse_xacts_ledger, parse_xacts_lisp};
+    use crate::journal::{Provenance, parse_xacts_ledger, parse_xacts_lisp};
     use crate::printing::{self, Fmt};
 
     const SAMPLE: &str = "\
@@ -26,7 +26,13 @@ mod test {
 
     fn render_lisp(xacts: &[crate::journal::Xact]) -> String {
         let mut buf = Vec::new();
-        printing::print::print(&mut buf, xacts.iter(), Fmt::Lisp).unwrap();
+        printing::print::print(
+            &mut buf,
+            xacts.iter(),
+            Fmt::Lisp,
+            &printing::Options::default(),
+        )
+        .unwrap();
         String::from_utf8(buf).unwrap()
     }
 
@@ -34,6 +40,7 @@ mod test {
     fn lisp_roundtrip_matches_tty() {
         let tty = parse_xacts_ledger(SAMPLE).unwrap();
         let lisp = render_lisp(&tty);
+        assert_eq!(tty[0].postings[1].provenance, Provenance::Inferred);
         let from_lisp = parse_xacts_lisp(&lisp).unwrap();
         assert_eq!(tty, from_lisp);
     }
@@ -50,4 +57,22 @@ mod test {
             parse_xacts_lisp(&list).unwrap()
         );
     }
+
+    #[test]
+    fn pretty_lisp_reads_back_the_same() {
+        let tty = parse_xacts_ledger(SAMPLE).unwrap();
+        let compact = render_lisp(&tty);
+        let pretty = printing::lisp_pretty(&serde_lexpr::parse::from_str(&compact).unwrap());
+
+        assert!(pretty.lines().count() > 1);
+        assert!(
+            pretty
+                .lines()
+                .all(|l| l.trim_end_matches(')').len() <= printing::LISP_WIDTH)
+        );
+        assert_eq!(
+            parse_xacts_lisp(&pretty).unwrap(),
+            parse_xacts_lisp(&compact).unwrap()
+        );
+    }
 }
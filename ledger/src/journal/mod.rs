@@ -1,11 +1,12 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeSet, HashMap, HashSet},
     convert::From,
     fmt::{self, Debug, Display},
     fs::{File, OpenOptions},
     io::{self, Read, Write},
     iter, mem,
     ops::Deref,
+    str::FromStr,
     sync::Mutex,
 };
 
@@ -14,10 +15,14 @@ use regex::Regex;
 
 use crate::{
     account::AccPostingSrc,
-    misc::BetweenDate,
-    pricedb::{MarketPrice, PriceType},
+    amount::Amount,
+    misc::{self, BetweenDate},
+    ntypes::Zero,
+    parser_number::NumberFormat,
+    pricedb::{MarketPrice, PriceDB, PriceType},
     printing::{self, Fmt},
     quantity::Quantity,
+    symbol::{Conversions, Styles},
     tags::Tag,
 };
 
@@ -25,6 +30,8 @@ mod json;
 mod lisp;
 mod parser;
 
+pub use parser::{AUTO_XACT_TAG, PERIODIC_XACT_TAG, ParseError, ParseOptions, PeriodicXact};
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Default, serde::Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum State {
@@ -34,6 +41,70 @@ pub enum State {
     Pending, // !
 }
 
+/// Whether a posting is real or virtual. Virtual postings are written
+/// with the account in parentheses (`(Budget:Food)`) and are left out
+/// of the balance check; balanced virtual ones, in brackets
+/// (`[Budget:Food]`), must balance among themselves.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Virtuality {
+    #[default]
+    Real,
+    Virtual,
+    BalancedVirtual,
+}
+
+/// Where a posting comes from: written in the journal or synthesized
+/// from something else.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Provenance {
+    /// written in the journal with its amount
+    #[default]
+    Explicit,
+    /// written in the journal without an amount, which was inferred
+    /// to balance the transaction
+    Inferred,
+    /// added by an automated transaction (`= EXPR`)
+    Automated,
+    /// part of a transaction generated from a periodic one (`~ PERIOD`)
+    Periodic,
+    /// opens a balance carried over by the `equity` report
+    Equity,
+    /// the change in market value of the holdings between two dates,
+    /// as in the `<Revalued>` rows of `register -V`
+    Revaluation,
+}
+
+/// Selects postings by their clearing state, as `-C/--cleared`,
+/// `--pending` and `--uncleared` do. A posting without a state of its
+/// own takes the state of its transaction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum StateFilter {
+    #[default]
+    All,
+    Cleared,
+    Pending,
+    /// anything not cleared: pending or without a state
+    Uncleared,
+}
+
+impl StateFilter {
+    /// Whether posting `p` of `xact` passes the filter.
+    pub fn matches(&self, xact: &Xact, p: &Posting) -> bool {
+        let state = match p.state {
+            State::None => xact.state,
+            s => s,
+        };
+        match self {
+            StateFilter::All => true,
+            StateFilter::Cleared => state == State::Cleared,
+            StateFilter::Pending => state == State::Pending,
+            StateFilter::Uncleared => state != State::Cleared,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct LotPrice {
     pub price: Quantity,
@@ -44,6 +115,20 @@ pub struct LotPrice {
 pub struct XactDate {
     pub txdate: NaiveDate,
     pub efdate: Option<NaiveDate>,
+    /// Whether reports file the transaction under `efdate`, as
+    /// [`ParseOptions::effective`] asked when it was read.
+    pub effective: bool,
+}
+
+impl XactDate {
+    /// The date reports file the transaction under: `efdate`, if
+    /// any, when [`XactDate::effective`] is set, else `txdate`.
+    pub fn date(&self) -> NaiveDate {
+        match self.efdate {
+            Some(d) if self.effective => d,
+            _ => self.txdate,
+        }
+    }
 }
 
 /// The name of an account.
@@ -51,6 +136,10 @@ pub struct XactDate {
 /// Account names can use a colon-separated hierarchy to represent
 /// account structure. For example: `"Assets:Bank:Checking"`
 /// and `"Assets:Cash"`.
+///
+/// Names read from a journal are in canonical form (see
+/// [`AccName::normalized`]): no whitespace around the segments and no
+/// empty segment, so `Assets: Bank :Checking` is `Assets:Bank:Checking`.
 #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
 pub struct AccName(String);
 
@@ -58,6 +147,26 @@ impl AccName {
     /// Account name separator
     const SEP: &'static str = ":";
 
+    /// The canonical form of `name`: each segment trimmed of
+    /// surrounding whitespace. `None` if a segment is empty, as in
+    /// `Assets::Checking`.
+    ///
+    /// # Examples
+    /// ```
+    /// use ledger::journal::AccName;
+    ///
+    /// let acc = AccName::normalized(" Assets: Bank :Checking ");
+    /// assert_eq!(acc, Some(AccName::from("Assets:Bank:Checking")));
+    /// assert_eq!(AccName::normalized("Assets::Checking"), None);
+    /// ```
+    pub fn normalized(name: &str) -> Option<AccName> {
+        let parts = name
+            .split(AccName::SEP)
+            .map(|s| Some(s.trim()).filter(|s| !s.is_empty()))
+            .collect::<Option<Vec<_>>>()?;
+        Some(AccName(parts.join(AccName::SEP)))
+    }
+
     /// Returns an iterator over all parent account names of this account,
     /// including the full account name itself.
     ///
@@ -105,10 +214,7 @@ impl AccName {
     /// assert_eq!(acc.parent_account(), Some("Assets"));
     /// ```
     pub fn parent_account(&self) -> Option<&str> {
-        let Some(t) = self.0.find(AccName::SEP) else {
-            return None;
-        };
-
+        let t = self.0.find(AccName::SEP)?;
         Some(&self.0[..t])
     }
 
@@ -183,6 +289,119 @@ impl AccName {
 
         Some(AccName(pop.to_owned()))
     }
+
+    /// Returns the longest common prefix of the two account names,
+    /// matched at segment boundaries. Returns an empty name when the
+    /// accounts share no leading segment.
+    ///
+    /// # Examples
+    /// ```
+    /// use ledger::journal::AccName;
+    ///
+    /// let a = AccName::from("Assets:Bank:Checking");
+    /// let b = AccName::from("Assets:Bank:Savings");
+    /// assert_eq!(a.common_prefix(&b), AccName::from("Assets:Bank"));
+    ///
+    /// // segments are compared whole, `Assets:Bank` is not a prefix of `Assets:Banking`
+    /// let c = AccName::from("Assets:Banking");
+    /// assert_eq!(a.common_prefix(&c), AccName::from("Assets"));
+    ///
+    /// // no common prefix
+    /// let d = AccName::from("Expenses:Food");
+    /// assert_eq!(a.common_prefix(&d), AccName::from(""));
+    ///
+    /// // full match
+    /// assert_eq!(a.common_prefix(&a), a);
+    /// ```
+    pub fn common_prefix(&self, other: &AccName) -> AccName {
+        let n = self
+            .split_parts()
+            .zip(other.split_parts())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        AccName(
+            self.split_parts()
+                .take(n)
+                .collect::<Vec<_>>()
+                .join(AccName::SEP),
+        )
+    }
+
+    /// Keeps the top `depth` levels of the name. A `depth` of 0 keeps
+    /// the whole name.
+    ///
+    /// # Examples
+    /// ```
+    /// use ledger::journal::AccName;
+    ///
+    /// let acc = AccName::from("Assets:Bank:Checking");
+    /// assert_eq!(acc.truncate(2), AccName::from("Assets:Bank"));
+    /// assert_eq!(acc.truncate(5), acc);
+    /// assert_eq!(acc.truncate(0), acc);
+    /// ```
+    pub fn truncate(&self, depth: usize) -> AccName {
+        if depth == 0 {
+            return self.clone();
+        }
+        AccName(
+            self.split_parts()
+                .take(depth)
+                .collect::<Vec<_>>()
+                .join(AccName::SEP),
+        )
+    }
+
+    /// Returns the number of segments of the name, 0 for an empty
+    /// name.
+    ///
+    /// # Examples
+    /// ```
+    /// use ledger::journal::AccName;
+    ///
+    /// assert_eq!(AccName::from("Assets:Bank:Checking").depth(), 3);
+    /// assert_eq!(AccName::from("Assets").depth(), 1);
+    /// assert_eq!(AccName::from("").depth(), 0);
+    /// ```
+    pub fn depth(&self) -> usize {
+        if self.is_empty() {
+            return 0;
+        }
+        self.split_parts().count()
+    }
+
+    /// Returns the last segment of the name.
+    ///
+    /// # Examples
+    /// ```
+    /// use ledger::journal::AccName;
+    ///
+    /// assert_eq!(AccName::from("Assets:Bank:Checking").leaf(), "Checking");
+    /// assert_eq!(AccName::from("Assets").leaf(), "Assets");
+    /// ```
+    pub fn leaf(&self) -> &str {
+        self.0.rsplit(AccName::SEP).next().unwrap()
+    }
+
+    /// Whether this account is a sub-account, at any level, of
+    /// `other`. An account is not a descendant of itself.
+    ///
+    /// # Examples
+    /// ```
+    /// use ledger::journal::AccName;
+    ///
+    /// let bank = AccName::from("Assets:Bank");
+    /// assert!(AccName::from("Assets:Bank:Checking").is_descendant_of(&bank));
+    /// assert!(!bank.is_descendant_of(&bank));
+    ///
+    /// // segments are compared whole
+    /// assert!(!AccName::from("Assets:Bankruptcy").is_descendant_of(&bank));
+    /// ```
+    pub fn is_descendant_of(&self, other: &AccName) -> bool {
+        self.0
+            .strip_prefix(&other.0)
+            .is_some_and(|rest| rest.starts_with(AccName::SEP))
+    }
 }
 
 impl Deref for AccName {
@@ -253,13 +472,15 @@ pub struct Xact {
 #[derive(Debug, PartialEq, Eq)]
 pub struct Posting {
     /// posting date, is the same date as the transaction date
-    /// (Xact::date::txdate)
+    /// ([`XactDate::date`])
     pub date: NaiveDate,
 
     /// posting state
     pub state: State,
     /// name of the account
     pub acc_name: AccName,
+    /// real, `(virtual)` or `[balanced virtual]`
+    pub virtuality: Virtuality,
     /// Debits and credits correspond to positive and negative values,
     /// respectively
     pub quantity: Quantity,
@@ -273,16 +494,31 @@ pub struct Posting {
     /// `uprice` is present.  Otherwise, it defaults to 1 in terms of
     /// the commodity itself (`quantity / quantity`).
     pub lot_uprice: LotPrice,
+    /// The total price as written with `@@`, from which `uprice` was
+    /// derived. Kept so the posting prints back as written, since the
+    /// per-unit price may not be exact.
+    pub total_price: Option<Quantity>,
+    /// Like `total_price`, for a lot price written as `{{total}}`.
+    pub total_lot_price: Option<Quantity>,
+    /// The quantity as written, when a `C` conversion reduced
+    /// `quantity` to another unit (`2 Kg` to `2000 g`). Kept so the
+    /// posting prints back as written.
+    pub written: Option<Quantity>,
     /// lot date
     pub lot_date: Option<NaiveDate>,
     /// lot note
     pub lot_note: String,
+    /// balance assertion (`= AMOUNT`): the expected balance of the
+    /// account in this commodity once the posting is applied
+    pub assertion: Option<Quantity>,
     /// posting comment
     pub comment: String,
     /// posting tags (e.g. `:tag:` or `:tag1:tag2:`)
     pub tags: Vec<Tag>,
     /// posting vtags (value tags) (e.g. `tag1: some value`)
     pub vtags: HashMap<Tag, String>,
+    /// whether the posting was written in the journal or synthesized
+    pub provenance: Provenance,
 }
 
 struct AccPosting<'a> {
@@ -295,7 +531,7 @@ impl<'a> AccPostingSrc<'a> for AccPosting<'a> {
         self.acc_name
     }
 
-    fn postings(&self) -> Box<dyn Iterator<Item = &'a Posting> + 'a> {
+    fn postings(&self) -> Box<dyn Iterator<Item = &'a Posting> + '_> {
         Box::new(
             self.postings
                 .iter()
@@ -304,6 +540,26 @@ impl<'a> AccPostingSrc<'a> for AccPosting<'a> {
     }
 }
 
+impl Xact {
+    /// The value of the value tag `key` (`key: value`), if any.
+    pub fn vtag_str(&self, key: &str) -> Option<&str> {
+        self.vtags.get(&Tag::new(key)).map(|v| v.as_str())
+    }
+
+    /// The value of the value tag `key` read as a date, e.g.
+    /// `Due: 2024-03-01`. `None` if missing or not a date.
+    pub fn vtag_date(&self, key: &str) -> Option<NaiveDate> {
+        parser::parse_date_str(self.vtag_str(key)?)
+    }
+
+    /// The value of the value tag `key` read as an amount in
+    /// [`NumberFormat::Us`], e.g. `Budget: $500.00`. `None` if missing
+    /// or not an amount.
+    pub fn vtag_amount(&self, key: &str) -> Option<Quantity> {
+        parser::parse_amount_str(self.vtag_str(key)?, NumberFormat::Us)
+    }
+}
+
 impl<'a> Xact {
     /// Get all postings group by account
     pub fn get_all_postings(&'a self) -> impl Iterator<Item = impl AccPostingSrc<'a>> {
@@ -321,15 +577,55 @@ impl<'a> Xact {
 }
 
 impl Posting {
+    /// Whether the posting was not written in the journal, but
+    /// generated by an automated or periodic transaction. Postings
+    /// with an inferred amount are not generated.
+    pub fn is_generated(&self) -> bool {
+        !matches!(self.provenance, Provenance::Explicit | Provenance::Inferred)
+    }
+
+    /// Whether the posting names a lot price of its own (`{$100}`)
+    /// rather than taking it from its `@` price. A lot price equal to
+    /// the `@` price can't be told apart from it and is not named.
+    pub fn names_lot_price(&self) -> bool {
+        self.total_lot_price.is_some()
+            || self.lot_uprice.price != self.uprice
+            || self.lot_uprice.ptype == PriceType::Static
+    }
+
     /// compute the value of the posting in terms of lot `{price}`
     pub fn book_value(&self) -> Quantity {
         self.lot_uprice.price * self.quantity.q
     }
+
+    /// The value of the posting at the market price in effect on
+    /// `at`. Without a price on or before `at`, the posting's own
+    /// `uprice` is used instead (see [`PriceDB::uprice_or`]).
+    pub fn historical_value(&self, at: NaiveDate, price_db: &PriceDB) -> Quantity {
+        let uprice = price_db.uprice_or(self.quantity.s, misc::to_datetime(at), self.uprice);
+        let value = uprice * self.quantity.q;
+        Quantity {
+            q: value.q.round_dp(price_db.valuation_precision()),
+            s: value.s,
+        }
+    }
 }
 
+#[derive(Default)]
 pub struct Journal {
     xact: Vec<Xact>,
     market_prices: Vec<MarketPrice>,
+    /// for each market price, the number of transactions written
+    /// before it in the file
+    market_price_pos: Vec<usize>,
+    /// accounts declared with `account` directives
+    declared_accounts: HashSet<AccName>,
+    /// periodic transactions (`~ PERIOD`), only materialized on demand
+    periodic_xacts: Vec<PeriodicXact>,
+    /// how the amounts of each commodity are written in the journal
+    styles: Styles,
+    /// unit conversions declared with `C` directives
+    conversions: Conversions,
 
     /// if None this journal is read-only
     path: Mutex<Option<String>>,
@@ -337,6 +633,9 @@ pub struct Journal {
 
 pub enum JrnIO {
     Path(String),
+    /// several files read as a single journal, see
+    /// [`Journal::with_number_format`]
+    Paths(Vec<String>),
     Reader(Box<dyn Read>),
 }
 
@@ -345,44 +644,149 @@ impl Journal {
     ///
     /// - `JrnIO::Path(p)` — opens the file at `p`, parses it, and
     ///   stores the path so that [`xact_append`] can write back.
+    /// - `JrnIO::Paths(ps)` — parses each file on its own and joins
+    ///   them, see [`Journal::with_number_format`]; the journal is
+    ///   read-only.
     /// - `JrnIO::Reader(r)` — reads from `r`; the journal is
     ///   read-only ([`xact_append`] returns [`JournalError::ReadOnly`]).
     ///
     /// [`xact_append`]: Journal::xact_append
     pub fn new(io: JrnIO) -> Result<Journal, JournalError> {
+        Journal::with_number_format(io, NumberFormat::Us)
+    }
+
+    /// Like [`Journal::new`], but amounts are parsed in `fmt` (e.g.
+    /// `1.234,56` under [`NumberFormat::European`]) until a `D` or
+    /// `commodity` directive in the journal switches the format.
+    ///
+    /// With [`JrnIO::Paths`] every file starts over in `fmt` and
+    /// directives don't carry from one file to the next. The
+    /// transactions of each file get ids after those of the files
+    /// before it. A failure is reported as a [`JournalError::File`]
+    /// naming the file.
+    pub fn with_number_format(io: JrnIO, fmt: NumberFormat) -> Result<Journal, JournalError> {
+        Journal::with_options(io, fmt, ParseOptions::default())
+    }
+
+    /// Like [`Journal::with_number_format`], reading the transactions
+    /// as `opts` says. Transactions that fail to balance are errors
+    /// here even under [`ParseOptions::allow_unbalanced`].
+    pub fn with_options(
+        io: JrnIO,
+        fmt: NumberFormat,
+        opts: ParseOptions,
+    ) -> Result<Journal, JournalError> {
+        let opts = ParseOptions {
+            allow_unbalanced: false,
+            ..opts
+        };
         match io {
             JrnIO::Path(path) => {
                 let file = File::open(&path)?;
-                let jrnl = Journal::from_reader(file)?;
+                let jrnl = Journal::from_reader(file, fmt, opts)?;
                 Ok(Journal {
                     path: Mutex::new(Some(path)),
                     ..jrnl
                 })
             }
-            JrnIO::Reader(r) => Journal::from_reader(r),
+            JrnIO::Paths(paths) => {
+                let mut journal = Journal::default();
+                for path in paths {
+                    let jrnl = File::open(&path)
+                        .map_err(JournalError::from)
+                        .and_then(|file| Journal::from_reader(file, fmt, opts))
+                        .map_err(|err| JournalError::File {
+                            path,
+                            err: Box::new(err),
+                        })?;
+                    journal.join(jrnl);
+                }
+                Ok(journal)
+            }
+            JrnIO::Reader(r) => Journal::from_reader(r, fmt, opts),
         }
     }
 
+    /// Adds the transactions, prices, declarations, periodic
+    /// transactions and commodity styles of `other`, its transaction
+    /// ids shifted past ours.
+    fn join(&mut self, other: Journal) {
+        let offset = self.xact.iter().map(|x| x.id + 1).max().unwrap_or(0);
+        self.xact.extend(other.xact.into_iter().map(|x| Xact {
+            id: x.id + offset,
+            ..x
+        }));
+        self.xact.sort_by_key(|a| a.date.date());
+        self.market_prices.extend(other.market_prices);
+        self.market_price_pos
+            .extend(other.market_price_pos.into_iter().map(|pos| pos + offset));
+        self.declared_accounts.extend(other.declared_accounts);
+        self.periodic_xacts.extend(other.periodic_xacts);
+        self.styles.extend(other.styles);
+        self.conversions.extend(other.conversions);
+    }
+
     /// Parses a journal from any reader. The resulting journal is
     /// read-only: [`xact_append`] will return [`JournalError::ReadOnly`].
     /// Use [`from_path`] when write-back is needed.
     ///
     /// [`xact_append`]: Journal::xact_append
     /// [`from_path`]: Journal::from_path
-    fn from_reader(mut r: impl io::Read) -> Result<Journal, JournalError> {
+    fn from_reader(
+        mut r: impl io::Read,
+        fmt: NumberFormat,
+        opts: ParseOptions,
+    ) -> Result<Journal, JournalError> {
         let mut content = String::new();
         r.read_to_string(&mut content)?;
+        let (journal, _) = Journal::parse_with(&content, fmt, opts)?;
+        Ok(journal)
+    }
+
+    /// Parses a journal from in-memory text. The resulting journal is
+    /// read-only, like one read with [`JrnIO::Reader`]. Same as
+    /// `content.parse::<Journal>()`.
+    pub fn parse(content: &str) -> Result<Journal, JournalError> {
+        let (journal, _) = Journal::parse_with(content, NumberFormat::Us, ParseOptions::default())?;
+        Ok(journal)
+    }
 
-        let mut parsed = parser::parse_journal(&content)?;
-        parsed
-            .xacts
-            .sort_by(|a, b| a.date.txdate.cmp(&b.date.txdate));
+    /// Like [`Journal::parse`], checking the transactions as `opts`
+    /// says. With [`ParseOptions::allow_unbalanced`] the transactions
+    /// that fail to balance are left out of the journal, and their
+    /// errors returned alongside it.
+    pub fn parse_with_options(
+        content: &str,
+        opts: ParseOptions,
+    ) -> Result<(Journal, Vec<ParseError>), JournalError> {
+        Journal::parse_with(content, NumberFormat::Us, opts)
+    }
 
-        Ok(Journal {
+    fn parse_with(
+        content: &str,
+        fmt: NumberFormat,
+        opts: ParseOptions,
+    ) -> Result<(Journal, Vec<ParseError>), JournalError> {
+        let mut parsed = parser::parse_journal_with_options(content, fmt, opts)?;
+        for err in apply_auto_xacts(&mut parsed.xacts, &parsed.auto_xacts) {
+            if !opts.allow_unbalanced {
+                return Err(err.into());
+            }
+            parsed.skipped.push(err);
+        }
+        parsed.xacts.sort_by_key(|a| a.date.date());
+
+        let journal = Journal {
             xact: parsed.xacts,
             market_prices: parsed.market_prices,
+            market_price_pos: parsed.market_price_pos,
+            declared_accounts: parsed.declared_accounts,
+            periodic_xacts: parsed.periodic_xacts,
+            styles: parsed.styles,
+            conversions: parsed.conversions,
             path: Mutex::new(None),
-        })
+        };
+        Ok((journal, parsed.skipped))
     }
 
     /// Appends `xacts` to the journal file and to the in-memory list.
@@ -408,17 +812,19 @@ impl Journal {
             writeln!(file)?;
         }
 
-        let mut id = len + 1;
-        for x in &mut xacts {
+        for (id, x) in (len + 1..).zip(xacts.iter_mut()) {
             // assign id before insert to DB
             x.id = id;
-            id += 1;
         }
 
-        printing::prnt(file, xacts.iter(), Fmt::Tty)?;
+        let opts = printing::Options {
+            styles: self.styles.clone(),
+            ..Default::default()
+        };
+        printing::prnt(file, xacts.iter(), Fmt::Tty, &opts)?;
 
         self.xact.extend(xacts);
-        self.xact.sort_by(|a, b| a.date.txdate.cmp(&b.date.txdate));
+        self.xact.sort_by_key(|a| a.date.date());
 
         Ok(())
     }
@@ -454,7 +860,7 @@ impl Journal {
     ) -> impl Iterator<Item = &'a Xact> + 'a {
         let between = BetweenDate::new(from, to);
         self.filter(move |x| {
-            between.check(x.date.txdate)
+            between.check(x.date.date())
                 && (qry.is_empty()
                     || x.postings
                         .iter()
@@ -470,7 +876,7 @@ impl Journal {
         to: Option<NaiveDate>,
     ) -> impl Iterator<Item = &Xact> {
         let between = BetweenDate::new(from, to);
-        self.filter(move |x| between.check(x.date.txdate))
+        self.filter(move |x| between.check(x.date.date()))
     }
     /// returns the total number of transactions in the journal
     pub fn nxact(&self) -> usize {
@@ -492,10 +898,192 @@ impl Journal {
         self.xact.iter().rev().take(n).rev()
     }
 
+    /// returns the dates of the earliest and latest transactions, or
+    /// `None` if the journal is empty
+    pub fn date_range(&self) -> Option<(NaiveDate, NaiveDate)> {
+        let first = self.xact.iter().map(|x| x.date.date()).min()?;
+        let last = self.xact.iter().map(|x| x.date.date()).max()?;
+        Some((first, last))
+    }
+
+    /// returns the postings whose quantity is zero, together with the
+    /// transaction they belong to. An elided amount that balances to
+    /// zero never produces a posting, so only explicit `0` amounts
+    /// are reported.
+    pub fn zero_postings(&self) -> impl Iterator<Item = (&Xact, &Posting)> {
+        self.postings().filter(|(_, p)| p.quantity.q.is_zero())
+    }
+
+    /// returns the transactions that look entered more than once:
+    /// same date, same payee and the same postings (account and
+    /// amount, in any order). Each group lists the copies in journal
+    /// order; groups are ordered by their first transaction.
+    pub fn find_duplicates(&self) -> Vec<Vec<&Xact>> {
+        let mut index = HashMap::new();
+        let mut groups: Vec<Vec<&Xact>> = Vec::new();
+        for x in &self.xact {
+            let mut postings: Vec<_> = x
+                .postings
+                .iter()
+                .map(|p| (&p.acc_name, p.quantity.s, p.quantity.q.normalize()))
+                .collect();
+            postings.sort();
+            let key = (x.date.txdate, &x.payee, postings);
+            let i = *index.entry(key).or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[i].push(x);
+        }
+        groups.retain(|g| g.len() > 1);
+        groups
+    }
+
+    /// returns an iterator over the postings of every transaction,
+    /// each paired with the transaction it belongs to, in journal
+    /// order
+    pub fn postings(&self) -> impl Iterator<Item = (&Xact, &Posting)> {
+        self.xact
+            .iter()
+            .flat_map(|x| x.postings.iter().map(move |p| (x, p)))
+    }
+
     /// returns an iterator over all market prices in the journal
     pub fn market_prices(&self) -> impl Iterator<Item = &MarketPrice> {
         self.market_prices.iter()
     }
+
+    /// like `market_prices`, but each price is paired with the number
+    /// of transactions written before it in the file, i.e. the `id`
+    /// of the transaction that follows it
+    pub fn market_prices_with_pos(&self) -> impl Iterator<Item = (usize, &MarketPrice)> {
+        self.market_price_pos
+            .iter()
+            .copied()
+            .zip(self.market_prices.iter())
+    }
+
+    /// returns how the amounts of each commodity are written in the
+    /// journal, see [`Styles`]
+    pub fn styles(&self) -> &Styles {
+        &self.styles
+    }
+
+    /// returns the unit conversions declared in the journal, see
+    /// [`Conversions`]
+    pub fn conversions(&self) -> &Conversions {
+        &self.conversions
+    }
+
+    /// returns the accounts declared with `account` directives
+    pub fn declared_accounts(&self) -> &HashSet<AccName> {
+        &self.declared_accounts
+    }
+
+    /// returns the accounts referenced by at least one posting, sorted
+    pub fn used_accounts(&self) -> BTreeSet<&AccName> {
+        self.xact
+            .iter()
+            .flat_map(|x| x.postings.iter().map(|p| &p.acc_name))
+            .collect()
+    }
+
+    /// Materializes the periodic transactions at each boundary of
+    /// their period within `[from, to]` (see [`PeriodicXact::dates`]),
+    /// sorted by date. The generated transactions are tagged with
+    /// [`PERIODIC_XACT_TAG`] and are not part of [`Journal::xacts`].
+    pub fn generate_periodic(&self, from: NaiveDate, to: NaiveDate) -> Vec<Xact> {
+        self.generate_with(from, to, PeriodicXact::instantiate)
+    }
+
+    /// Like [`Journal::generate_periodic`], but leaving out the
+    /// balancing postings, see [`PeriodicXact::instantiate_budget`].
+    pub fn generate_budget(&self, from: NaiveDate, to: NaiveDate) -> Vec<Xact> {
+        self.generate_with(from, to, PeriodicXact::instantiate_budget)
+    }
+
+    fn generate_with(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+        make: impl Fn(&PeriodicXact, NaiveDate, usize) -> Xact,
+    ) -> Vec<Xact> {
+        let mut id = self.xact.len();
+        let mut xacts = Vec::new();
+        for px in &self.periodic_xacts {
+            for date in px.dates(from, to) {
+                xacts.push(make(px, date, id));
+                id += 1;
+            }
+        }
+        xacts.sort_by_key(|x| x.date.date());
+        xacts
+    }
+
+    /// Checks that every posting goes to an account declared with an
+    /// `account` directive. Returns the first undeclared account found,
+    /// in date order.
+    pub fn check_declared_accounts(&self) -> Result<(), parser::ParseError> {
+        let undeclared = self
+            .xact
+            .iter()
+            .flat_map(|x| x.postings.iter())
+            .find(|p| !self.declared_accounts.contains(&p.acc_name));
+
+        match undeclared {
+            Some(p) => Err(parser::ParseError::UndeclaredAccount(p.acc_name.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Adds to each transaction the postings generated by the automated
+/// transactions matching any of its postings. Generated postings are
+/// not matched again.
+///
+/// The real and balanced virtual postings generated for a transaction
+/// must add up to zero on their own, as the transaction balanced
+/// before. The transactions they would unbalance are removed, and
+/// their errors returned.
+fn apply_auto_xacts(xacts: &mut Vec<Xact>, autos: &[parser::AutoXact]) -> Vec<ParseError> {
+    let mut errors = Vec::new();
+    if autos.is_empty() {
+        return errors;
+    }
+
+    xacts.retain_mut(|x| {
+        let generated: Vec<_> = x
+            .postings
+            .iter()
+            .flat_map(|p| {
+                autos
+                    .iter()
+                    .filter(|a| a.matches(&p.acc_name))
+                    .flat_map(|a| a.postings_for(p))
+            })
+            .collect();
+
+        let balanced = [Virtuality::Real, Virtuality::BalancedVirtual]
+            .into_iter()
+            .all(|group| {
+                generated
+                    .iter()
+                    .filter(|p| p.virtuality == group)
+                    .map(|p| p.book_value())
+                    .sum::<Amount>()
+                    .is_zero()
+            });
+        if !balanced {
+            errors.push(ParseError::XactNoBalanced {
+                date: x.date.txdate,
+                payee: x.payee.clone(),
+            });
+            return false;
+        }
+        x.postings.extend(generated);
+        true
+    });
+    errors
 }
 
 #[derive(Debug)]
@@ -503,6 +1091,54 @@ pub enum JournalError {
     Io(io::Error),
     Parser(parser::ParseError),
     ReadOnly,
+    /// reading `path`, one of several journal files, failed
+    File {
+        path: String,
+        err: Box<JournalError>,
+    },
+}
+
+impl fmt::Display for JournalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JournalError::Io(err) => write!(f, "io error: {err}"),
+            JournalError::Parser(err) => write!(f, "parse error: {err}"),
+            JournalError::ReadOnly => write!(f, "journal is read-only"),
+            JournalError::File { path, err } => match err.as_ref() {
+                JournalError::Io(err) => write!(f, "{path}: {}", io_message(err)),
+                err => write!(f, "{path}: {err}"),
+            },
+        }
+    }
+}
+
+/// `err` without the ` (os error N)` that std appends to OS errors,
+/// e.g. `No such file or directory`.
+fn io_message(err: &io::Error) -> String {
+    let msg = err.to_string();
+    match msg.rfind(" (os error ") {
+        Some(i) if err.raw_os_error().is_some() => msg[..i].to_owned(),
+        _ => msg,
+    }
+}
+
+impl std::error::Error for JournalError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            JournalError::Io(err) => Some(err),
+            JournalError::Parser(err) => Some(err),
+            JournalError::ReadOnly => None,
+            JournalError::File { err, .. } => Some(err.as_ref()),
+        }
+    }
+}
+
+impl FromStr for Journal {
+    type Err = JournalError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Journal::parse(s)
+    }
 }
 
 impl From<io::Error> for JournalError {
@@ -523,7 +1159,7 @@ impl From<parser::ParseError> for JournalError {
 /// like `addx` that append to a file want the transactions in the order
 /// they were given.
 pub fn parse_xacts_ledger(content: &str) -> Result<Vec<Xact>, JournalError> {
-    let parsed = parser::parse_journal(&content.to_string()).map_err(JournalError::Parser)?;
+    let parsed = parser::parse_journal(content).map_err(JournalError::Parser)?;
     Ok(parsed
         .xacts
         .into_iter()
@@ -546,8 +1182,9 @@ pub fn parse_xacts_lisp(content: &str) -> Result<Vec<Xact>, JournalError> {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::util;
+    use crate::{quantity, util};
     use chrono::NaiveDate;
+    use rust_decimal::dec;
 
     fn d(y: i32, m: u32, day: u32) -> NaiveDate {
         NaiveDate::from_ymd_opt(y, m, day).unwrap()
@@ -889,19 +1526,813 @@ mod test {
         assert_eq!(tail, vec!["beta", "gamma", "delta"]);
     }
 
+    #[test]
+    fn date_range_spans_first_and_last_xact() {
+        let input = "\
+2025-03-01 middle
+  A          $1
+  B
+
+2025-01-15 first
+  A          $2
+  B
+
+2025-06-30 last
+  A          $3
+  B
+";
+        let (journal, _) =
+            util::read_journal_and_price_db(JrnIO::Reader(Box::new(input.as_bytes())), None)
+                .unwrap();
+
+        assert_eq!(journal.date_range(), Some((d(2025, 1, 15), d(2025, 6, 30))));
+    }
+
+    #[test]
+    fn date_range_of_empty_journal_is_none() {
+        let (journal, _) =
+            util::read_journal_and_price_db(JrnIO::Reader(Box::new("".as_bytes())), None).unwrap();
+        assert_eq!(journal.date_range(), None);
+    }
+
+    #[test]
+    fn parse_journal_from_str() {
+        let journal: Journal = "\
+2025-01-01 groceries
+  Expenses:Food    $10
+  Assets:Cash
+"
+        .parse()
+        .unwrap();
+        assert_eq!(journal.nxact(), 1);
+        assert!(matches!(
+            Journal::parse("2025-01-01 broken\n  Expenses:Food    $10\n"),
+            Err(JournalError::Parser(_))
+        ));
+    }
+
+    #[test]
+    fn unterminated_comment_block() {
+        let input = "\
+2025-01-01 groceries
+  Expenses:Food    $10
+  Assets:Cash
+
+comment
+2025-01-02 rent
+  Expenses:Rent    $500
+  Assets:Cash
+";
+        assert!(matches!(
+            Journal::parse(input),
+            Err(JournalError::Parser(ParseError::UnterminatedComment {
+                line: 5
+            }))
+        ));
+        let closed = format!("{input}end comment\n");
+        assert_eq!(Journal::parse(&closed).unwrap().nxact(), 1);
+    }
+
+    #[test]
+    fn end_apply_account_without_apply() {
+        let input = "\
+apply account Assets
+2025-01-01 groceries
+  Cash    $-10
+  Expenses:Food
+end apply account
+end apply account
+";
+        assert!(matches!(
+            Journal::parse(input),
+            Err(JournalError::Parser(ParseError::UnmatchedEndApply {
+                line: 6
+            }))
+        ));
+    }
+
+    #[test]
+    fn account_names_are_trimmed_per_segment() {
+        let journal = Journal::parse(
+            "\
+account  Assets :Bank
+2025-01-01 deposit
+  Assets: Bank :Checking    $10
+  Assets:Bank:Checking    $5
+  Equity :Opening
+  ( Budget : Food )    $1
+",
+        )
+        .unwrap();
+        let used: Vec<&str> = journal.used_accounts().iter().map(|a| &a[..]).collect();
+        assert_eq!(
+            used,
+            vec!["Assets:Bank:Checking", "Budget:Food", "Equity:Opening"]
+        );
+        assert!(
+            journal
+                .declared_accounts()
+                .contains(&AccName::from("Assets:Bank"))
+        );
+    }
+
+    #[test]
+    fn account_name_with_empty_segment() {
+        let input = "\
+2025-01-01 deposit
+  Assets::Checking    $10
+  Equity
+";
+        assert!(matches!(
+            Journal::parse(input),
+            Err(JournalError::Parser(ParseError::InvalidAccount(name))) if name == "Assets::Checking"
+        ));
+        assert!(matches!(
+            Journal::parse("account Assets:\n"),
+            Err(JournalError::Parser(ParseError::InvalidAccount(_)))
+        ));
+    }
+
+    #[test]
+    fn find_duplicates_groups_identical_xacts() {
+        let journal = Journal::parse(
+            "\
+2025-01-01 coffee
+  Expenses:Food    $3.50
+  Assets:Cash
+
+2025-01-01 coffee
+  Assets:Cash    $-3.5
+  Expenses:Food
+
+2025-01-01 coffee
+  Expenses:Food    $4
+  Assets:Cash
+
+2025-01-02 coffee
+  Expenses:Food    $3.50
+  Assets:Cash
+",
+        )
+        .unwrap();
+        let groups: Vec<Vec<usize>> = journal
+            .find_duplicates()
+            .iter()
+            .map(|g| g.iter().map(|x| x.id).collect())
+            .collect();
+        assert_eq!(groups, vec![vec![0, 1]]);
+    }
+
+    #[test]
+    fn balance_assignment_sets_the_missing_amount() {
+        let journal = Journal::parse(
+            "\
+2025-01-01 opening
+  Assets:Cash    $20
+  Equity:Opening
+
+2025-01-02 count
+  Assets:Cash    $-5
+  Assets:Cash    = $12
+  Expenses:Misc
+",
+        )
+        .unwrap();
+        let count = journal.xacts().nth(1).unwrap();
+        assert_eq!(count.postings[1].quantity, quantity!(-3, "$"));
+        assert_eq!(count.postings[2].quantity, quantity!(8, "$"));
+    }
+
+    #[test]
+    fn parse_with_options_skips_unbalanced_xacts() {
+        let input = "\
+2025-01-01 ok
+  Expenses:Food    $10
+  Assets:Cash
+
+2025-01-02 unbalanced
+  Expenses:Food    $10
+  Assets:Cash    $-9
+
+2025-01-03 two elided
+  Expenses:Food    $10
+  Assets:Cash
+  Assets:Bank
+
+2025-01-04 ok too
+  Expenses:Rent    $500
+  Assets:Cash
+";
+        assert!(Journal::parse(input).is_err());
+
+        let opts = ParseOptions {
+            allow_unbalanced: true,
+            ..Default::default()
+        };
+        let (journal, skipped) = Journal::parse_with_options(input, opts).unwrap();
+        let payees: Vec<&str> = journal.xacts().map(|x| x.payee.as_str()).collect();
+        assert_eq!(payees, vec!["ok", "ok too"]);
+        assert!(matches!(
+            skipped.as_slice(),
+            [
+                ParseError::XactNoBalanced { .. },
+                ParseError::ElidingAmount { .. }
+            ]
+        ));
+    }
+
+    #[test]
+    fn parse_with_options_can_require_every_amount() {
+        let input = "\
+2025-01-01 elided
+  Expenses:Food    $10
+  Assets:Cash
+";
+        let opts = ParseOptions {
+            max_eliding: 0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            Journal::parse_with_options(input, opts),
+            Err(JournalError::Parser(ParseError::ElidingAmount {
+                count: 1,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn parse_with_options_rejects_more_than_one_elided_amount() {
+        let input = "\
+2025-01-01 elided
+  Expenses:Food    $10
+  Assets:Cash
+";
+        let opts = ParseOptions {
+            max_eliding: 2,
+            ..Default::default()
+        };
+        assert!(matches!(
+            Journal::parse_with_options(input, opts),
+            Err(JournalError::Parser(ParseError::InvalidMaxEliding(2)))
+        ));
+    }
+
+    #[test]
+    fn typed_value_tags() {
+        let journal = Journal::parse(
+            "\
+2024-02-01 electricity
+  ; Due: 2024-03-01
+  ; Budget: $500.00
+  ; Note: pay online
+  ; Bad: 2024-13-01
+  Expenses:Utilities    $80
+  Assets:Cash
+",
+        )
+        .unwrap();
+        let x = journal.xacts().next().unwrap();
+        assert_eq!(x.vtag_date("Due"), NaiveDate::from_ymd_opt(2024, 3, 1));
+        assert_eq!(x.vtag_amount("Budget"), Some(quantity!(500, "$")));
+        assert_eq!(x.vtag_str("Note"), Some("pay online"));
+        assert_eq!(x.vtag_date("Note"), None);
+        assert_eq!(x.vtag_amount("Note"), None);
+        assert_eq!(x.vtag_date("Bad"), None);
+        assert_eq!(x.vtag_date("Budget"), None);
+        assert_eq!(x.vtag_str("Missing"), None);
+    }
+
+    #[test]
+    fn postings_pairs_each_posting_with_its_xact() {
+        let journal = Journal::parse(
+            "\
+2025-01-01 groceries
+  Expenses:Food    $10
+  Assets:Cash
+
+2025-01-02 rent
+  Expenses:Rent    $500
+  Assets:Cash
+",
+        )
+        .unwrap();
+        let pairs: Vec<_> = journal
+            .postings()
+            .map(|(x, p)| (x.payee.as_str(), &*p.acc_name))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                ("groceries", "Expenses:Food"),
+                ("groceries", "Assets:Cash"),
+                ("rent", "Expenses:Rent"),
+                ("rent", "Assets:Cash"),
+            ]
+        );
+    }
+
+    #[test]
+    fn zero_postings_flags_explicit_zero_but_not_elided() {
+        let input = "\
+2025-01-01 placeholder
+  Expenses:Food    $0
+  Assets:Cash      $10
+  Income:Gift
+
+2025-01-02 nets to zero
+  A          $5
+  B          $-5
+  C
+";
+        let (journal, _) =
+            util::read_journal_and_price_db(JrnIO::Reader(Box::new(input.as_bytes())), None)
+                .unwrap();
+
+        let zeros: Vec<_> = journal
+            .zero_postings()
+            .map(|(x, p)| (x.date.txdate, p.acc_name.to_string()))
+            .collect();
+        assert_eq!(zeros, vec![(d(2025, 1, 1), "Expenses:Food".to_string())]);
+    }
+
+    #[test]
+    fn account_directives_are_collected() {
+        let input = "\
+account Assets:Bank:Checking
+account Expenses:Food
+    note groceries and restaurants
+
+2025-01-01 lunch
+  Expenses:Food          $10
+  Assets:Bank:Checking
+";
+        let (journal, _) =
+            util::read_journal_and_price_db(JrnIO::Reader(Box::new(input.as_bytes())), None)
+                .unwrap();
+
+        assert_eq!(journal.declared_accounts().len(), 2);
+        assert!(
+            journal
+                .declared_accounts()
+                .contains(&AccName::from("Expenses:Food"))
+        );
+        assert!(journal.check_declared_accounts().is_ok());
+    }
+
+    #[test]
+    fn strict_read_rejects_undeclared_account() {
+        let input = "\
+account Assets:Bank:Checking
+account Expenses:Food
+
+2025-01-01 lunch
+  Expenses:Food          $10
+  Assets:Bnak:Checking
+";
+        let res = util::read_journal(
+            JrnIO::Reader(Box::new(input.as_bytes())),
+            None,
+            true,
+            NumberFormat::Us,
+            ParseOptions::default(),
+        );
+        assert!(matches!(
+            res,
+            Err(util::ReadDbError::JournalError(JournalError::Parser(
+                ParseError::UndeclaredAccount(name)
+            ))) if name == AccName::from("Assets:Bnak:Checking")
+        ));
+
+        // not strict, the typo creates a new account
+        let res = util::read_journal(
+            JrnIO::Reader(Box::new(input.as_bytes())),
+            None,
+            false,
+            NumberFormat::Us,
+            ParseOptions::default(),
+        );
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn effective_dates_are_chosen_when_parsing() {
+        let input = "\
+2025-01-01=2025-01-20 Late
+  Expenses:Food    $10
+  Assets:Cash
+
+2025-01-10 Middle
+  Expenses:Food    $20
+  Assets:Cash
+";
+        let payees = |j: &Journal| j.xacts().map(|x| x.payee.clone()).collect::<Vec<_>>();
+
+        let journal = Journal::parse(input).unwrap();
+        assert_eq!(payees(&journal), ["Late", "Middle"]);
+
+        let opts = ParseOptions {
+            effective: true,
+            ..Default::default()
+        };
+        let (effective, _) = Journal::parse_with_options(input, opts).unwrap();
+        assert_eq!(payees(&effective), ["Middle", "Late"]);
+        let late = effective.xacts().last().unwrap();
+        assert_eq!(
+            late.date.date(),
+            NaiveDate::from_ymd_opt(2025, 1, 20).unwrap()
+        );
+        assert_eq!(late.postings[0].date, late.date.date());
+
+        // reading another journal doesn't change how this one is dated
+        assert_eq!(payees(&Journal::parse(input).unwrap()), ["Late", "Middle"]);
+    }
+
+    #[test]
+    fn conversions_are_per_journal() {
+        let converted = Journal::parse(
+            "\
+C 1.00 Kg = 1,000.00 g
+
+2025-01-01 Buy
+  Assets:Pantry    2 Kg @ $3
+  Assets:Cash
+",
+        )
+        .unwrap();
+        let p = &converted.xacts().next().unwrap().postings[0];
+        assert_eq!(p.quantity, quantity!(2000, "g"));
+        assert_eq!(p.uprice, quantity!(0.003, "$"));
+        assert_eq!(p.written, Some(quantity!(2, "Kg")));
+        assert_eq!(p.book_value(), quantity!(6, "$"));
+
+        // the conversion doesn't leak into a journal read afterwards
+        let plain = Journal::parse("2025-01-01 Buy\n  Assets:Pantry    2 Kg\n  Equity\n").unwrap();
+        let p = &plain.xacts().next().unwrap().postings[0];
+        assert_eq!(p.quantity, quantity!(2, "Kg"));
+        assert_eq!(p.written, None);
+    }
+
+    #[test]
+    fn auto_xact_adds_tagged_postings() {
+        let input = "\
+= /^Expenses:Food/
+    Budget:Food          -1
+    Assets:Budget         1
+    (Expenses:Fees)      $1
+
+2025-01-02 groceries
+  Expenses:Food:Grocery    $30
+  Assets:Checking
+
+2025-01-03 rent
+  Expenses:Rent           $200
+  Assets:Checking
+";
+        let (journal, _) =
+            util::read_journal_and_price_db(JrnIO::Reader(Box::new(input.as_bytes())), None)
+                .unwrap();
+
+        let groceries = journal.xact_find_by_id(0).unwrap();
+        let generated: Vec<_> = groceries
+            .postings
+            .iter()
+            .filter(|p| p.tags.contains(&Tag::new(AUTO_XACT_TAG)))
+            .map(|p| (p.acc_name.to_string(), p.quantity))
+            .collect();
+        assert_eq!(
+            generated,
+            vec![
+                ("Budget:Food".to_string(), quantity!(-30, "$")),
+                ("Assets:Budget".to_string(), quantity!(30, "$")),
+                ("Expenses:Fees".to_string(), quantity!(1, "$")),
+            ]
+        );
+
+        // the rule only matches Expenses:Food
+        let rent = journal.xact_find_by_id(1).unwrap();
+        assert_eq!(rent.postings.len(), 2);
+    }
+
+    #[test]
+    fn auto_xact_postings_must_balance() {
+        let input = "\
+= /^Expenses:Food/
+    Expenses:Fees        $1
+
+2025-01-02 groceries
+  Expenses:Food           $30
+  Assets:Checking
+
+2025-01-03 rent
+  Expenses:Rent           $200
+  Assets:Checking
+";
+        let err = Journal::parse(input).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "parse error: transaction 2025-01-02 'groceries': postings do not balance"
+        );
+
+        let opts = ParseOptions {
+            allow_unbalanced: true,
+            ..ParseOptions::default()
+        };
+        let (journal, skipped) = Journal::parse_with_options(input, opts).unwrap();
+        assert_eq!(journal.xacts().count(), 1);
+        assert_eq!(skipped.len(), 1);
+    }
+
+    #[test]
+    fn generate_periodic_at_each_month() {
+        let input = "\
+~ Monthly
+    Expenses:Food          $300
+    Assets:Checking
+
+2025-01-10 groceries
+  Expenses:Food    $30
+  Assets:Checking
+";
+        let (journal, _) =
+            util::read_journal_and_price_db(JrnIO::Reader(Box::new(input.as_bytes())), None)
+                .unwrap();
+
+        // periodic transactions are not regular transactions
+        assert_eq!(journal.nxact(), 1);
+
+        let generated = journal.generate_periodic(d(2025, 1, 15), d(2025, 3, 31));
+        let dates: Vec<_> = generated.iter().map(|x| x.date.txdate).collect();
+        assert_eq!(dates, vec![d(2025, 2, 1), d(2025, 3, 1)]);
+
+        let x = &generated[0];
+        assert_eq!(x.payee, "Monthly");
+        assert!(x.tags.contains(&Tag::new(PERIODIC_XACT_TAG)));
+        assert_eq!(x.postings[0].quantity, quantity!(300, "$"));
+        assert_eq!(x.postings[1].quantity, quantity!(-300, "$"));
+    }
+
+    #[test]
+    fn generate_periodic_every_n_periods_within_bounds() {
+        let input = "\
+~ every 2 weeks from 2025-01-06 to 2025-02-28
+    Expenses:Rent          $500
+    Assets:Checking
+";
+        let journal = Journal::parse(input).unwrap();
+
+        let generated = journal.generate_periodic(d(2025, 1, 1), d(2025, 3, 31));
+        let dates: Vec<_> = generated.iter().map(|x| x.date.txdate).collect();
+        assert_eq!(
+            dates,
+            vec![d(2025, 1, 6), d(2025, 1, 20), d(2025, 2, 3), d(2025, 2, 17)]
+        );
+        assert_eq!(
+            generated[0].payee,
+            "every 2 weeks from 2025-01-06 to 2025-02-28"
+        );
+
+        // the periods stay counted from the `from` date
+        let generated = journal.generate_periodic(d(2025, 1, 15), d(2025, 3, 31));
+        let dates: Vec<_> = generated.iter().map(|x| x.date.txdate).collect();
+        assert_eq!(dates, vec![d(2025, 1, 20), d(2025, 2, 3), d(2025, 2, 17)]);
+    }
+
+    #[test]
+    fn parse_rejects_an_unknown_period() {
+        for period in [
+            "every 0 weeks",
+            "every fortnight",
+            "monthly from 2025-13-01",
+        ] {
+            let input = format!("~ {period}\n    Expenses:Rent  $500\n    Assets:Checking\n");
+            assert!(matches!(
+                Journal::parse(&input),
+                Err(JournalError::Parser(ParseError::InvalidPeriod(p))) if p == period
+            ));
+        }
+    }
+
+    #[test]
+    fn postings_record_their_provenance() {
+        let input = "\
+= /^Expenses:Food/
+    Budget:Food          -1
+    Assets:Budget         1
+
+~ Monthly
+    Expenses:Food          $300
+    Assets:Checking
+
+2025-01-02 groceries
+  Expenses:Food    $30
+  Assets:Checking
+";
+        let journal = Journal::parse(input).unwrap();
+
+        let provenances: Vec<_> = journal
+            .xact_find_by_id(0)
+            .unwrap()
+            .postings
+            .iter()
+            .map(|p| (p.acc_name.to_string(), p.provenance, p.is_generated()))
+            .collect();
+        assert_eq!(
+            provenances,
+            vec![
+                ("Expenses:Food".to_string(), Provenance::Explicit, false),
+                ("Assets:Checking".to_string(), Provenance::Inferred, false),
+                ("Budget:Food".to_string(), Provenance::Automated, true),
+                ("Assets:Budget".to_string(), Provenance::Automated, true),
+            ]
+        );
+
+        let periodic = journal.generate_periodic(d(2025, 1, 1), d(2025, 1, 31));
+        assert!(
+            periodic[0]
+                .postings
+                .iter()
+                .all(|p| p.provenance == Provenance::Periodic && p.is_generated())
+        );
+    }
+
+    #[test]
+    fn unknown_period_is_an_error() {
+        let input = "\
+~ Fortnightly
+    Expenses:Food          $300
+    Assets:Checking
+";
+        let res = Journal::new(JrnIO::Reader(Box::new(input.as_bytes())));
+        assert!(matches!(
+            res,
+            Err(JournalError::Parser(ParseError::InvalidPeriod(p))) if p == "Fortnightly"
+        ));
+    }
+
     #[test]
     fn read_journal_io_error_returns_err() {
         // An implementation of Read that always fails to trigger JournalError::Io
         struct FailReader;
         impl std::io::Read for FailReader {
             fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
-                Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "forced IO error",
-                ))
+                Err(std::io::Error::other("forced IO error"))
             }
         }
         let result = Journal::new(JrnIO::Reader(Box::new(FailReader)));
         assert!(matches!(result, Err(JournalError::Io(_))));
     }
+
+    #[test]
+    fn used_accounts_are_distinct_and_sorted() {
+        let input = "\
+2026-01-01 * Groceries
+    Expenses:Food    $20
+    Assets:Cash
+
+2026-01-02 * Groceries
+    Expenses:Food    $10
+    Assets:Bank
+";
+        let journal = Journal::new(JrnIO::Reader(Box::new(input.as_bytes()))).unwrap();
+        let used: Vec<&str> = journal.used_accounts().into_iter().map(|a| &**a).collect();
+        assert_eq!(used, vec!["Assets:Bank", "Assets:Cash", "Expenses:Food"]);
+    }
+
+    #[test]
+    fn printed_xacts_parse_back_to_the_same_journal() {
+        let input = "\
+2026-01-01=2026-01-03 * (101) Grocery store  ; :food:
+    ; second line note
+    Expenses:Food              $20.50  ; type: organic
+    ! Assets:Checking
+
+2026-01-05 Broker
+    Assets:Broker    10 AAPL {=$100} [2025-12-01] (note) @ $110
+    Assets:Cash      $-1,100.00
+    Income:Gains
+
+2026-01-06 * Check
+    Assets:Checking    $-10 = $-30.50
+    Expenses:Food
+
+2026-01-07 Broker
+    Assets:Broker    3 AAPL {{$100}} [2026-01-07] @@ $110
+    Assets:Cash      $-110.00
+    Income:Gains
+";
+        let mut journal = Journal::new(JrnIO::Reader(Box::new(input.as_bytes()))).unwrap();
+        let opts = printing::Options {
+            styles: journal.styles().clone(),
+            ..Default::default()
+        };
+        let mut printed = Vec::new();
+        for x in journal.xacts() {
+            printing::print_xact(&mut printed, x, &opts).unwrap();
+        }
+        // the ledger format has no provenance: inferred amounts are
+        // printed, so they parse back as explicit
+        for p in journal.xact.iter_mut().flat_map(|x| x.postings.iter_mut()) {
+            p.provenance = Provenance::Explicit;
+        }
+
+        let reparsed = Journal::new(JrnIO::Reader(Box::new(io::Cursor::new(printed)))).unwrap();
+        let a: Vec<_> = journal.xacts().collect();
+        let b: Vec<_> = reparsed.xacts().collect();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn journal_errors_chain_to_their_source() {
+        use std::error::Error;
+
+        let input = "2026-01-01 * Broken\n    Assets:Cash    $10 $20\n";
+        let Err(err) = Journal::new(JrnIO::Reader(Box::new(input.as_bytes()))) else {
+            panic!("expected a parse error");
+        };
+        let msg = err.to_string();
+        assert!(msg.starts_with("parse error: "), "{msg}");
+        assert!(msg.contains("2:"), "{msg}");
+
+        let parse_err = err.source().unwrap();
+        assert!(parse_err.source().is_some());
+
+        let boxed: Box<dyn Error> = Box::new(err);
+        assert_eq!(boxed.to_string(), msg);
+    }
+
+    #[test]
+    fn several_files_read_as_one_journal() {
+        let dir = std::env::temp_dir().join(format!("ledger-paths-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let a = dir.join("a.ledger");
+        let b = dir.join("b.ledger");
+        std::fs::write(
+            &a,
+            "2026-01-05 * Salary\n    Assets:Cash    $100\n    Income:Salary\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &b,
+            "P 2026-01-02 AAPL $10\n\n2026-01-01 * Opening\n    Assets:Cash    $5\n    Equity:Opening\n",
+        )
+        .unwrap();
+        let paths = vec![
+            a.to_string_lossy().into_owned(),
+            b.to_string_lossy().into_owned(),
+        ];
+
+        let mut journal = Journal::new(JrnIO::Paths(paths.clone())).unwrap();
+        let xacts: Vec<_> = journal.xacts().map(|x| (x.id, x.payee.as_str())).collect();
+        assert_eq!(xacts, vec![(1, "Opening"), (0, "Salary")]);
+        assert_eq!(
+            journal
+                .market_prices_with_pos()
+                .map(|(pos, _)| pos)
+                .collect::<Vec<_>>(),
+            vec![1]
+        );
+        // the journal doesn't belong to any one of the files
+        let more = parse_xacts_ledger("2026-02-01 * More\n    A    $1\n    B\n").unwrap();
+        assert!(matches!(
+            journal.xact_append(more),
+            Err(JournalError::ReadOnly)
+        ));
+
+        let missing = dir.join("missing.ledger").to_string_lossy().into_owned();
+        let Err(err) = Journal::new(JrnIO::Paths(vec![paths[0].clone(), missing.clone()])) else {
+            panic!("expected an error");
+        };
+        assert!(matches!(&err, JournalError::File { path, .. } if *path == missing));
+        let msg = err.to_string();
+        assert!(msg.starts_with(&format!("{missing}: ")));
+        assert!(!msg.contains("os error"), "{msg}");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn state_filter_uses_xact_state_when_posting_has_none() {
+        let input = "\
+2026-01-01 * Cleared
+  A    $1.00
+  ! B
+
+2026-01-02 Plain
+  A    $1.00
+  * B
+";
+        let journal = Journal::new(JrnIO::Reader(Box::new(input.as_bytes()))).unwrap();
+        let states = |f: StateFilter| -> Vec<bool> {
+            journal
+                .xacts()
+                .flat_map(|x| x.postings.iter().map(move |p| f.matches(x, p)))
+                .collect()
+        };
+
+        assert_eq!(states(StateFilter::All), [true, true, true, true]);
+        assert_eq!(states(StateFilter::Cleared), [true, false, false, true]);
+        assert_eq!(states(StateFilter::Pending), [false, true, false, false]);
+        assert_eq!(states(StateFilter::Uncleared), [false, true, true, false]);
+    }
 }
@@ -1,25 +1,72 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::io;
 use std::str::FromStr;
 
-use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime};
 use pest::{self, Parser, iterators::Pair};
 use pest_derive::Parser;
+use regex::Regex;
 use rust_decimal::Decimal;
 use serde::de;
 use serde::{Deserialize, Deserializer};
 
 use crate::amount::Amount;
-use crate::journal::{self, AccName, LotPrice, State, XactDate};
+use crate::journal::{self, AccName, LotPrice, Provenance, State, Virtuality, XactDate};
+use crate::misc::{self, Step};
 use crate::ntypes::{Basket, Quantities};
 use crate::parser_number::{self, NumberFormat};
 use crate::pricedb::{MarketPrice, PriceBasis, PriceType};
 use crate::quantity::Quantity;
-use crate::symbol::Symbol;
+use crate::symbol::{Conversions, Style, Styles, Symbol};
 use crate::tags::Tag;
 
 const MAX_ELIDING_AMOUNT: usize = 1;
 
+/// How strictly [`parse_journal_with_options`] checks transactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Skip the transactions that don't balance, have too many elided
+    /// amounts or no postings, instead of failing the whole parse.
+    /// Their errors are collected in [`ParsedJournal::skipped`].
+    pub allow_unbalanced: bool,
+    /// Most postings without an amount per balance group. `0`
+    /// requires every amount to be written; `1`, the default, is the
+    /// most that can be inferred and larger values are rejected with
+    /// [`ParseError::InvalidMaxEliding`].
+    pub max_eliding: usize,
+    /// Date the transactions by their effective date, when they have
+    /// one (`2025/01/01=2025/01/05`), see [`XactDate::date`].
+    pub effective: bool,
+    /// The date taken as today (`--now`). `MM/DD` dates before any
+    /// `Y` directive are in its year.
+    pub now: NaiveDate,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            allow_unbalanced: false,
+            max_eliding: MAX_ELIDING_AMOUNT,
+            effective: false,
+            now: misc::today(),
+        }
+    }
+}
+
+impl ParseError {
+    /// Whether the error concerns a single transaction, which
+    /// [`ParseOptions::allow_unbalanced`] skips.
+    fn is_xact_error(&self) -> bool {
+        matches!(
+            self,
+            ParseError::ElidingAmount { .. }
+                | ParseError::XactNoBalanced { .. }
+                | ParseError::EmptyTransaction { .. }
+        )
+    }
+}
+
 #[derive(Parser)]
 #[grammar = "./src/grammar.pest"]
 struct LedgerParser;
@@ -29,14 +76,111 @@ pub enum ParseError {
     InvalidDate,
     InvalidNumber(String),
     Parser(pest::error::Error<Rule>),
-    ElidingAmount(usize),
-    XactNoBalanced,
+    /// More postings without an amount than can be inferred.
+    ElidingAmount {
+        date: NaiveDate,
+        payee: String,
+        count: usize,
+    },
+    /// The postings of a transaction don't add up to zero.
+    XactNoBalanced {
+        date: NaiveDate,
+        payee: String,
+    },
+    /// A posting to an account not declared with an `account`
+    /// directive (strict mode only).
+    UndeclaredAccount(AccName),
+    /// The expression of an automated transaction (`= EXPR`) is not
+    /// a valid regular expression.
+    InvalidAutoXactExpr(String),
+    /// A posting of an automated transaction (`= EXPR`) without an
+    /// amount.
+    AutoXactWithoutAmount(String),
+    /// Unknown period of a periodic transaction (`~ PERIOD`): a
+    /// period word (`Monthly`) or `every [N] UNIT`, optionally followed
+    /// by `from DATE` and `to DATE`.
+    InvalidPeriod(String),
+    /// A transaction without any posting.
+    EmptyTransaction {
+        date: NaiveDate,
+        payee: String,
+    },
+    /// A `comment` block without its `end comment`, opened at `line`.
+    UnterminatedComment {
+        line: usize,
+    },
+    /// An `end apply account` without an open `apply account`, at
+    /// `line`.
+    UnmatchedEndApply {
+        line: usize,
+    },
+    /// An account name with an empty segment, like
+    /// `Assets::Checking`.
+    InvalidAccount(String),
     IOErr(io::Error),
     /// Failure while deserializing the json/lisp input of `addx`.
     Deser(String),
+    /// [`ParseOptions::max_eliding`] above the one posting per balance
+    /// group whose amount can be inferred.
+    InvalidMaxEliding(usize),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::InvalidDate => write!(f, "invalid date"),
+            ParseError::InvalidNumber(n) => write!(f, "invalid number '{n}'"),
+            ParseError::Parser(err) => write!(f, "syntax error\n{err}"),
+            ParseError::ElidingAmount { date, payee, .. } => write!(
+                f,
+                "transaction {date} '{payee}': too many postings without amounts"
+            ),
+            ParseError::XactNoBalanced { date, payee } => {
+                write!(f, "transaction {date} '{payee}': postings do not balance")
+            }
+            ParseError::UndeclaredAccount(acc) => write!(f, "undeclared account '{acc}'"),
+            ParseError::InvalidAutoXactExpr(e) => {
+                write!(f, "invalid automated transaction expression '{e}'")
+            }
+            ParseError::AutoXactWithoutAmount(e) => write!(
+                f,
+                "automated transaction '{e}': every posting needs an amount"
+            ),
+            ParseError::InvalidPeriod(p) => write!(f, "unknown period '{p}'"),
+            ParseError::EmptyTransaction { date, payee } => {
+                write!(f, "transaction {date} '{payee}': no postings")
+            }
+            ParseError::UnterminatedComment { line } => {
+                write!(f, "line {line}: `comment` without `end comment`")
+            }
+            ParseError::UnmatchedEndApply { line } => {
+                write!(
+                    f,
+                    "line {line}: `end apply account` without `apply account`"
+                )
+            }
+            ParseError::InvalidAccount(name) => write!(f, "invalid account name '{name}'"),
+            ParseError::IOErr(err) => write!(f, "{err}"),
+            ParseError::Deser(msg) => write!(f, "{msg}"),
+            ParseError::InvalidMaxEliding(n) => write!(
+                f,
+                "invalid max eliding {n}: at most {MAX_ELIDING_AMOUNT} posting per balance group can omit its amount"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::Parser(err) => Some(err),
+            ParseError::IOErr(err) => Some(err),
+            _ => None,
+        }
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 pub struct Xact {
     #[serde(deserialize_with = "deserialize_date")]
     date: NaiveDate,
@@ -64,6 +208,8 @@ pub struct Xact {
 pub struct Posting {
     account: String,
     #[serde(default)]
+    virtuality: Virtuality,
+    #[serde(default)]
     state: State,
     #[serde(default, deserialize_with = "deserialize_quantity")]
     quantity: Option<Quantity>,
@@ -71,16 +217,29 @@ pub struct Posting {
     uprice: Option<Quantity>,
     #[serde(default, deserialize_with = "deserialize_lotprice")]
     lot_uprice: Option<LotPrice>,
+    /// the `@@` total, if the price was written that way
+    #[serde(skip)]
+    total_price: Option<Quantity>,
+    /// the `{{}}` total, if the lot price was written that way
+    #[serde(skip)]
+    total_lot_price: Option<Quantity>,
+    /// the quantity as written, if a conversion reduced it
+    #[serde(skip)]
+    written: Option<Quantity>,
     #[serde(default, deserialize_with = "deserialize_opt_date")]
     lot_date: Option<NaiveDate>,
     #[serde(default)]
     lot_note: String,
+    #[serde(default, deserialize_with = "deserialize_quantity")]
+    assertion: Option<Quantity>,
     #[serde(default)]
     comment: String,
     #[serde(default)]
     tags: Vec<Tag>,
     #[serde(default)]
     vtags: HashMap<Tag, String>,
+    #[serde(default)]
+    provenance: Provenance,
 }
 
 impl<'a> Deserialize<'a> for Tag {
@@ -155,6 +314,26 @@ where
 }
 
 impl Posting {
+    /// Writes the amounts in the last unit of their chain of
+    /// conversions, see [`Conversions`]. The prices are then per unit
+    /// of the reduced quantity; the quantity as written is kept so the
+    /// posting prints back as such.
+    fn reduce(&mut self, conversions: &Conversions) {
+        if let Some(q) = self.quantity.filter(|q| !q.q.is_zero()) {
+            let unit = conversions.unit(q.s);
+            if unit.s != q.s && !unit.q.is_zero() {
+                let reprice = |p: Quantity| conversions.reduce(p) / unit.q;
+                self.written = Some(q);
+                self.quantity = Some(unit * q.q);
+                self.uprice = self.uprice.map(reprice);
+                if let Some(lp) = self.lot_uprice.as_mut() {
+                    lp.price = reprice(lp.price);
+                }
+            }
+        }
+        self.assertion = self.assertion.map(|a| conversions.reduce(a));
+    }
+
     fn into_posting(self, date: NaiveDate) -> journal::Posting {
         let quantity = self.quantity.unwrap();
 
@@ -185,70 +364,121 @@ impl Posting {
             date,
             state: self.state,
             acc_name: AccName::from(self.account),
+            virtuality: self.virtuality,
             quantity,
             uprice,
             lot_uprice,
+            total_price: self.total_price,
+            total_lot_price: self.total_lot_price,
+            written: self.written,
             lot_date: self.lot_date,
             lot_note: self.lot_note,
+            assertion: self.assertion,
             comment: self.comment,
             tags: self.tags,
             vtags: self.vtags,
+            provenance: self.provenance,
         }
     }
 }
 
 impl Xact {
-    pub fn into_xact(mut self, id: usize) -> Result<journal::Xact, ParseError> {
+    pub fn into_xact(self, id: usize) -> Result<journal::Xact, ParseError> {
+        self.into_xact_with(id, ParseOptions::default())
+    }
+
+    /// Like [`Xact::into_xact`], allowing at most
+    /// [`ParseOptions::max_eliding`] postings without an amount per
+    /// balance group and dating the transaction as
+    /// [`ParseOptions::effective`] says.
+    fn into_xact_with(
+        mut self,
+        id: usize,
+        opts: ParseOptions,
+    ) -> Result<journal::Xact, ParseError> {
+        if self.postings.is_empty() {
+            return Err(ParseError::EmptyTransaction {
+                date: self.date,
+                payee: self.payee,
+            });
+        }
+
         let nel = self.neliding_amount();
-        if nel > MAX_ELIDING_AMOUNT {
-            return Err(ParseError::ElidingAmount(nel));
+        if nel > opts.max_eliding {
+            return Err(ParseError::ElidingAmount {
+                date: self.date,
+                payee: self.payee,
+                count: nel,
+            });
         }
 
-        let eliding = self.maybe_remove_eliding();
+        let xdate = XactDate {
+            txdate: self.date,
+            efdate: self.efdate,
+            effective: opts.effective,
+        };
+        let date = xdate.date();
+        let eliding = self.remove_eliding();
         let mut postings: Vec<journal::Posting> = self
             .postings
             .into_iter()
-            .map(|p| p.into_posting(self.date))
+            .map(|p| p.into_posting(date))
             .collect();
 
-        let bal: Amount = postings.iter().map(|p| p.book_value()).sum();
-        match eliding {
-            Some(eliding) => {
-                postings.extend(bal.quantities().map(|q| {
-                    let mut p = eliding.clone();
-                    p.quantity = Some(-q);
-                    p.into_posting(self.date)
-                }));
-            }
-            None => {
-                match bal.arity() {
-                    n if n != 0 && n != 2 => {
-                        return Err(ParseError::XactNoBalanced);
-                    }
-                    2 => {
-                        // balance must be in the form nX - mY
-                        let p: Decimal = bal.quantities().map(|qty| qty.q).product();
-                        if p > Decimal::ZERO {
-                            return Err(ParseError::XactNoBalanced);
+        // real and balanced virtual postings balance separately, the
+        // plain virtual ones don't have to balance at all
+        for group in [Virtuality::Real, Virtuality::BalancedVirtual] {
+            let bal: Amount = postings
+                .iter()
+                .filter(|p| p.virtuality == group)
+                .map(|p| p.book_value())
+                .sum();
+            match eliding
+                .iter()
+                .find(|e| balance_group(e.virtuality) == group)
+            {
+                Some(eliding) => {
+                    postings.extend(bal.quantities().map(|q| {
+                        let mut p = eliding.clone();
+                        p.quantity = Some(-q);
+                        let mut p = p.into_posting(date);
+                        p.provenance = Provenance::Inferred;
+                        p
+                    }));
+                }
+                None => {
+                    match bal.arity() {
+                        n if n != 0 && n != 2 => {
+                            return Err(ParseError::XactNoBalanced {
+                                date: self.date,
+                                payee: self.payee,
+                            });
+                        }
+                        2 => {
+                            // balance must be in the form nX - mY
+                            let p: Decimal = bal.quantities().map(|qty| qty.q).product();
+                            if p > Decimal::ZERO {
+                                return Err(ParseError::XactNoBalanced {
+                                    date: self.date,
+                                    payee: self.payee,
+                                });
+                            }
                         }
+                        _ => {}
                     }
-                    _ => {}
                 }
             }
-        }
 
-        if bal.arity() == 2 {
-            Xact::fill_inferred_prices(&mut postings, bal)
+            if bal.arity() == 2 {
+                Xact::fill_inferred_prices(&mut postings, group, bal)
+            }
         }
 
         let xact = journal::Xact {
-            id: id,
+            id,
             state: self.state,
             code: self.code,
-            date: XactDate {
-                txdate: self.date,
-                efdate: self.efdate,
-            },
+            date: xdate,
             payee: self.payee,
             comment: self.comment,
             postings,
@@ -259,27 +489,95 @@ impl Xact {
         Ok(xact)
     }
 
-    /// Removes and returns the first `Posting` from the `postings`
-    /// list where `quantity` is `None`.
-    fn maybe_remove_eliding(&mut self) -> Option<Posting> {
-        if let Some(pos) = self.postings.iter().position(|p| p.quantity.is_none()) {
-            Some(self.postings.remove(pos))
-        } else {
-            None
+    /// Reduces the amounts of every posting, see [`Posting::reduce`].
+    fn reduce(&mut self, conversions: &Conversions) {
+        for p in self.postings.iter_mut() {
+            p.reduce(conversions);
         }
     }
 
-    /// count how many postings with an explicit amount/quantity
+    /// Rewrites the posting accounts whose name, or first segment,
+    /// is an alias. Aliases pointing at other aliases are resolved
+    /// transitively.
+    fn apply_aliases(&mut self, aliases: &HashMap<String, String>) {
+        if aliases.is_empty() {
+            return;
+        }
+        for p in self.postings.iter_mut() {
+            p.account = resolve_alias(&p.account, aliases);
+        }
+    }
+
+    /// Prepends `prefix` (from `apply account`) to every posting
+    /// account.
+    fn apply_account_prefix(&mut self, prefix: &AccName) {
+        if prefix.is_empty() {
+            return;
+        }
+        for p in self.postings.iter_mut() {
+            p.account = prefix
+                .append(&AccName::from(p.account.as_str()))
+                .to_string();
+        }
+    }
+
+    /// Fills in the quantity of the balance assignments, postings
+    /// with only a balance (`Assets:Cash  = $100`): the amount that
+    /// takes the account from its `running` balance, plus the earlier
+    /// postings of this transaction, to the assigned one.
+    fn assign_balances(&mut self, running: &HashMap<(String, Symbol), Decimal>) {
+        let mut own: HashMap<(String, Symbol), Decimal> = HashMap::new();
+        for p in self.postings.iter_mut() {
+            let q = match (p.quantity, p.assertion) {
+                (Some(q), _) => q,
+                (None, Some(target)) => {
+                    let key = (p.account.clone(), target.s);
+                    let current = running.get(&key).copied().unwrap_or_default()
+                        + own.get(&key).copied().unwrap_or_default();
+                    let q = Quantity {
+                        q: target.q - current,
+                        s: target.s,
+                    };
+                    p.quantity = Some(q);
+                    q
+                }
+                (None, None) => continue,
+            };
+            *own.entry((p.account.clone(), q.s)).or_default() += q.q;
+        }
+    }
+
+    /// Removes and returns the postings whose `quantity` is `None`.
+    fn remove_eliding(&mut self) -> Vec<Posting> {
+        let (eliding, rest) = std::mem::take(&mut self.postings)
+            .into_iter()
+            .partition(|p| p.quantity.is_none());
+        self.postings = rest;
+        eliding
+    }
+
+    /// The largest number of postings without an explicit
+    /// amount/quantity in a single balance group
     fn neliding_amount(&self) -> usize {
-        self.postings
-            .iter()
-            .filter(|p| p.quantity.is_none())
-            .count()
+        [Virtuality::Real, Virtuality::BalancedVirtual]
+            .into_iter()
+            .map(|group| {
+                self.postings
+                    .iter()
+                    .filter(|p| p.quantity.is_none() && balance_group(p.virtuality) == group)
+                    .count()
+            })
+            .max()
+            .unwrap_or(0)
     }
 
     /// In a xact with a balance in the form nA - mB, try to guess
     /// which one is the primary commodity of the xact
-    fn guess_primary(ps: &Vec<journal::Posting>, a: Quantity, b: Quantity) -> (Quantity, Quantity) {
+    fn guess_primary(
+        ps: &[&mut journal::Posting],
+        a: Quantity,
+        b: Quantity,
+    ) -> (Quantity, Quantity) {
         let fs = ps[0].quantity.s;
         if a.s == fs {
             return (b, a);
@@ -287,7 +585,7 @@ impl Xact {
             return (a, b);
         }
 
-        for p in ps {
+        for p in ps.iter() {
             if p.uprice.s != p.quantity.s {
                 if p.uprice.s == a.s {
                     return (a, b);
@@ -299,13 +597,17 @@ impl Xact {
         unreachable!()
     }
 
-    /// find postings with secondary commodity having its uprice's
-    /// equal to itself and replace it's in terms of the primary
-    /// commodity
-    fn fill_inferred_prices(postings: &mut Vec<journal::Posting>, bal: Amount) {
+    /// find postings of `group` with secondary commodity having its
+    /// uprice's equal to itself and replace it's in terms of the
+    /// primary commodity
+    fn fill_inferred_prices(postings: &mut [journal::Posting], group: Virtuality, bal: Amount) {
         let mut iter = bal.quantities();
         let a = iter.next().unwrap();
         let b = iter.next().unwrap();
+        let mut postings: Vec<&mut journal::Posting> = postings
+            .iter_mut()
+            .filter(|p| p.virtuality == group)
+            .collect();
         let (pri, sec) = Xact::guess_primary(&postings, a, b);
 
         postings.iter_mut().for_each(|p| {
@@ -326,6 +628,43 @@ impl Xact {
     }
 }
 
+/// The postings a posting balances with: `[balanced virtual]` ones
+/// among themselves, the rest with the real postings. An elided
+/// `(virtual)` posting takes the remainder of the real ones.
+fn balance_group(v: Virtuality) -> Virtuality {
+    match v {
+        Virtuality::BalancedVirtual => Virtuality::BalancedVirtual,
+        Virtuality::Real | Virtuality::Virtual => Virtuality::Real,
+    }
+}
+
+/// Expands `name` using `aliases`, matching either the whole name or
+/// its first segment (`Checking:Sub` with `Checking=Assets:Checking`
+/// gives `Assets:Checking:Sub`). Repeats until no alias applies; the
+/// number of rounds is bounded so a cycle of aliases can't loop
+/// forever.
+fn resolve_alias(name: &str, aliases: &HashMap<String, String>) -> String {
+    let mut name = name.to_string();
+    for _ in 0..aliases.len() {
+        if let Some(account) = aliases.get(&name) {
+            name = account.clone();
+            continue;
+        }
+        let (head, tail) = match name.split_once(':') {
+            Some((head, tail)) => (head, Some(tail)),
+            None => (name.as_str(), None),
+        };
+        let Some(account) = aliases.get(head) else {
+            break;
+        };
+        name = match tail {
+            Some(tail) => format!("{account}:{tail}"),
+            None => account.clone(),
+        };
+    }
+    name
+}
+
 /// Derive tags (`:tag:`) and value-tags (`key: value`) from a comment,
 /// the same way `parse_comment` does for the text path.
 fn tags_from_comment(comment: &str) -> (Vec<Tag>, HashMap<Tag, String>) {
@@ -354,38 +693,242 @@ fn tags_from_comment(comment: &str) -> (Vec<Tag>, HashMap<Tag, String>) {
     (tags, vtags)
 }
 
-pub struct ParsedJounral {
+pub struct ParsedJournal {
     pub xacts: Vec<journal::Xact>,
     pub market_prices: Vec<MarketPrice>,
+    /// for each market price, the number of transactions written
+    /// before it in the file
+    pub market_price_pos: Vec<usize>,
+    pub declared_accounts: HashSet<AccName>,
+    pub auto_xacts: Vec<AutoXact>,
+    pub periodic_xacts: Vec<PeriodicXact>,
+    /// errors of the transactions left out under
+    /// [`ParseOptions::allow_unbalanced`], in date order
+    pub skipped: Vec<ParseError>,
+    /// how the amounts of each commodity are written
+    pub styles: Styles,
+    /// the unit conversions declared with `C` directives
+    pub conversions: Conversions,
+}
+
+/// Tag carried by the postings added by an automated transaction.
+pub const AUTO_XACT_TAG: &str = "generated";
+
+/// An automated transaction (`= EXPR` followed by template postings).
+///
+/// Every posting whose account matches `expr` gets the template
+/// postings added to its transaction. A template amount without a
+/// commodity is a multiplier of the matched posting's quantity; with a
+/// commodity it is added as is.
+pub struct AutoXact {
+    expr: Regex,
+    postings: Vec<Posting>,
+}
+
+impl AutoXact {
+    /// Whether the rule applies to a posting to `acc`.
+    pub fn matches(&self, acc: &AccName) -> bool {
+        self.expr.is_match(acc)
+    }
+
+    /// Builds the postings generated for the matched posting `p`,
+    /// tagged with [`AUTO_XACT_TAG`].
+    pub fn postings_for(&self, p: &journal::Posting) -> Vec<journal::Posting> {
+        self.postings
+            .iter()
+            .map(|tpl| {
+                let mut tpl = tpl.clone();
+                if let Some(q) = tpl.quantity
+                    && q.s == Symbol::new("")
+                {
+                    tpl.quantity = Some(p.quantity * q.q);
+                }
+                let mut posting = tpl.into_posting(p.date);
+                posting.tags.push(Tag::new(AUTO_XACT_TAG));
+                posting.provenance = Provenance::Automated;
+                posting
+            })
+            .collect()
+    }
+}
+
+/// Tag carried by the transactions generated from a periodic
+/// transaction.
+pub const PERIODIC_XACT_TAG: &str = "periodic";
+
+/// A periodic transaction (`~ PERIOD` followed by template postings),
+/// used to generate budget transactions.
+pub struct PeriodicXact {
+    /// Kind and length of the period: `every 2 weeks` is
+    /// `Step::Weeks(2)`.
+    pub period: Step,
+    /// First day of the `from DATE` bound; the periods are counted
+    /// from it.
+    pub begin: Option<NaiveDate>,
+    /// Last day of the `to DATE` bound, inclusive like `--end`.
+    pub end: Option<NaiveDate>,
+    xact: Xact,
+}
+
+impl PeriodicXact {
+    /// Dates of the instances within `[from, to]` and the bounds of
+    /// the period expression, in order.
+    pub fn dates(&self, from: NaiveDate, to: NaiveDate) -> impl Iterator<Item = NaiveDate> {
+        let start = self.begin.unwrap_or(from);
+        let to = self.end.map_or(to, |end| end.min(to));
+        misc::period_boundaries(start, to, self.period).skip_while(move |d| *d < from)
+    }
+
+    /// Materializes the template on `date`. The transaction's payee
+    /// is the period expression and it's tagged with
+    /// [`PERIODIC_XACT_TAG`].
+    pub fn instantiate(&self, date: NaiveDate, id: usize) -> journal::Xact {
+        self.materialize(date, id, true)
+    }
+
+    /// Like [`PeriodicXact::instantiate`], but without the postings
+    /// that balance the template, those written without an amount.
+    /// What is left is what the budget report compares.
+    pub fn instantiate_budget(&self, date: NaiveDate, id: usize) -> journal::Xact {
+        self.materialize(date, id, false)
+    }
+
+    fn materialize(&self, date: NaiveDate, id: usize, balancing: bool) -> journal::Xact {
+        let mut xact = self.xact.clone();
+        xact.date = date;
+        // the template was checked to balance when parsed
+        let mut xact = xact.into_xact(id).unwrap();
+        if !balancing {
+            xact.postings
+                .retain(|p| p.provenance != Provenance::Inferred);
+        }
+        xact.tags.push(Tag::new(PERIODIC_XACT_TAG));
+        for p in &mut xact.postings {
+            p.provenance = Provenance::Periodic;
+        }
+        xact
+    }
 }
 
-pub fn parse_journal(content: &String) -> Result<ParsedJounral, ParseError> {
-    let mut journal = match LedgerParser::parse(Rule::journal, &content) {
+pub fn parse_journal(content: &str) -> Result<ParsedJournal, ParseError> {
+    parse_journal_with_format(content, NumberFormat::Us)
+}
+
+/// Like [`parse_journal`], but amounts are read in `fmt` until a `D`
+/// or `commodity ... format` directive switches it.
+pub fn parse_journal_with_format(
+    content: &str,
+    fmt: NumberFormat,
+) -> Result<ParsedJournal, ParseError> {
+    parse_journal_with_options(content, fmt, ParseOptions::default())
+}
+
+/// Like [`parse_journal_with_format`], checking the transactions as
+/// `opts` says.
+pub fn parse_journal_with_options(
+    content: &str,
+    mut fmt: NumberFormat,
+    opts: ParseOptions,
+) -> Result<ParsedJournal, ParseError> {
+    if opts.max_eliding > MAX_ELIDING_AMOUNT {
+        return Err(ParseError::InvalidMaxEliding(opts.max_eliding));
+    }
+
+    let mut journal = match LedgerParser::parse(Rule::journal, content) {
         Ok(pairs) => pairs,
         Err(err) => return Err(ParseError::Parser(err)),
     };
 
     let mut xacts = Vec::new();
     let mut market_prices = Vec::new();
+    let mut market_price_pos = Vec::new();
+    let mut declared_accounts = HashSet::new();
+    // alias -> account, only applies to transactions after the directive
+    let mut aliases = HashMap::new();
+    // year of the `MM/DD` dates, set by the `Y` directive
+    let mut year = opts.now.year();
+    // open `apply account` directives, innermost last
+    let mut applied_accounts: Vec<AccName> = Vec::new();
+    let mut auto_xacts = Vec::new();
+    let mut periodic_xacts = Vec::new();
+    let mut styles = Styles::default();
+    let mut conversions = Conversions::default();
 
     let mut id = 0;
     let element_list = journal.next().unwrap().into_inner().next().unwrap();
     for p in element_list.into_inner() {
         match p.as_rule() {
             Rule::xact => {
-                let xact = parse_xact(p)?;
-                let xact = xact.into_xact(id);
+                let mut xact = parse_xact(p, fmt, &mut styles, year)?;
+                xact.apply_aliases(&aliases);
+                xact.apply_account_prefix(&account_prefix(&applied_accounts));
+                xacts.push((id, xact));
                 id += 1;
-
-                let Ok(xact) = xact else {
-                    return Err(xact.unwrap_err());
-                };
-
-                xacts.push(xact);
             }
             Rule::market_price => {
-                let mp = parse_market_price(p)?;
+                let mp = parse_market_price(p, fmt, &mut styles)?;
                 market_prices.push(mp);
+                market_price_pos.push(id);
+            }
+            Rule::unterminated_comment_block => {
+                let (line, _) = p.line_col();
+                return Err(ParseError::UnterminatedComment { line });
+            }
+            Rule::periodic_xact => {
+                periodic_xacts.push(parse_periodic_xact(p, fmt, &mut styles)?);
+            }
+            Rule::auto_xact => {
+                auto_xacts.push(parse_auto_xact(p, fmt, &mut styles)?);
+            }
+            Rule::alias_directive => {
+                let mut inner = p.into_inner();
+                let alias = parse_alias_side(inner.next().unwrap())?;
+                let account = parse_alias_side(inner.next().unwrap())?;
+                aliases.insert(alias, account);
+            }
+            Rule::account_directive => {
+                let name = parse_acc_name(p.into_inner().next().unwrap())?;
+                declared_accounts.insert(account_prefix(&applied_accounts).append(&name));
+            }
+            Rule::year_directive => {
+                year = p.into_inner().next().unwrap().as_str().parse().unwrap();
+            }
+            Rule::apply_account_directive => {
+                let name = parse_acc_name(p.into_inner().next().unwrap())?;
+                applied_accounts.push(name);
+            }
+            Rule::end_apply_account_directive => {
+                if applied_accounts.pop().is_none() {
+                    let (line, _) = p.line_col();
+                    return Err(ParseError::UnmatchedEndApply { line });
+                }
+            }
+            Rule::conversion_directive => {
+                let text = p.as_str().trim();
+                let mut inner = p.into_inner();
+                let unit = parse_unit_value(inner.next().unwrap(), fmt, &mut styles)?;
+                let base = parse_unit_value(inner.next().unwrap(), fmt, &mut styles)?;
+                let per_unit = base
+                    .checked_div(unit.q)
+                    .ok_or_else(|| ParseError::InvalidNumber(text.to_owned()))?;
+                conversions.set(unit.s, per_unit);
+            }
+            Rule::default_commodity_directive | Rule::commodity_directive => {
+                if let Some(sample) = directive_sample(p) {
+                    if let Some(f) = sample_number_format(&sample) {
+                        fmt = f;
+                    }
+                    let suffix = symbol_is_suffix(&sample);
+                    let q = parse_unit_value(sample, fmt, &mut styles)?;
+                    styles.set(
+                        q.s,
+                        Style {
+                            places: q.q.scale(),
+                            suffix,
+                            format: fmt,
+                        },
+                    );
+                }
             }
             _ => {
                 continue;
@@ -393,13 +936,239 @@ pub fn parse_journal(content: &String) -> Result<ParsedJounral, ParseError> {
         }
     }
 
-    Ok(ParsedJounral {
+    // conversions apply to the whole file, wherever they are declared
+    if !conversions.is_empty() {
+        for (_, xact) in xacts.iter_mut() {
+            xact.reduce(&conversions);
+        }
+    }
+    let (xacts, skipped) = into_xacts(xacts, opts)?;
+    Ok(ParsedJournal {
         xacts,
+        skipped,
         market_prices,
+        market_price_pos,
+        declared_accounts,
+        auto_xacts,
+        periodic_xacts,
+        styles,
+        conversions,
+    })
+}
+
+/// Turns the parsed transactions, paired with their ids, into journal
+/// ones. They are visited in date order so that balance assignments
+/// (`Assets:Cash  = $100`) see the running balance of their account;
+/// the result is in id order. Along with it come the errors of the
+/// transactions skipped under [`ParseOptions::allow_unbalanced`].
+fn into_xacts(
+    mut xacts: Vec<(usize, Xact)>,
+    opts: ParseOptions,
+) -> Result<(Vec<journal::Xact>, Vec<ParseError>), ParseError> {
+    xacts.sort_by_key(|(id, x)| {
+        let date = XactDate {
+            txdate: x.date,
+            efdate: x.efdate,
+            effective: opts.effective,
+        };
+        (date.date(), *id)
+    });
+
+    // balance per account and commodity
+    let mut running: HashMap<(String, Symbol), Decimal> = HashMap::new();
+    let mut res = Vec::with_capacity(xacts.len());
+    let mut skipped = Vec::new();
+    for (id, mut xact) in xacts {
+        xact.assign_balances(&running);
+        let xact = match xact.into_xact_with(id, opts) {
+            Ok(xact) => xact,
+            Err(err) if opts.allow_unbalanced && err.is_xact_error() => {
+                skipped.push(err);
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        for p in &xact.postings {
+            *running
+                .entry((p.acc_name.to_string(), p.quantity.s))
+                .or_default() += p.quantity.q;
+        }
+        res.push(xact);
+    }
+    res.sort_by_key(|x| x.id);
+    Ok((res, skipped))
+}
+
+/// The prefix set by nested `apply account` directives, outermost
+/// first.
+fn account_prefix(applied: &[AccName]) -> AccName {
+    applied
+        .iter()
+        .fold(AccName::from(""), |prefix, acc| prefix.append(acc))
+}
+
+/// The number format implied by the sample amount of a directive.
+fn sample_number_format(sample: &Pair<Rule>) -> Option<NumberFormat> {
+    let amount = sample
+        .clone()
+        .into_inner()
+        .find(|p| p.as_rule() == Rule::ammount)?;
+    NumberFormat::from_sample(amount.as_str())
+}
+
+/// The sample amount of a `D` directive or of the `format` line of a
+/// `commodity` directive, if it has one.
+fn directive_sample(p: Pair<Rule>) -> Option<Pair<Rule>> {
+    p.into_inner()
+        .flatten()
+        .find(|p| p.as_rule() == Rule::units_value)
+}
+
+fn parse_periodic_xact(
+    p: Pair<Rule>,
+    fmt: NumberFormat,
+    styles: &mut Styles,
+) -> Result<PeriodicXact, ParseError> {
+    let mut period = None;
+    let mut payee = String::new();
+    let mut postings = Vec::new();
+
+    for p in p.into_inner() {
+        match p.as_rule() {
+            Rule::period_expr => {
+                payee = p.as_str().to_string();
+                period = Some(
+                    parse_period(&payee).ok_or_else(|| ParseError::InvalidPeriod(payee.clone()))?,
+                );
+            }
+            Rule::postings => {
+                for p in p.into_inner() {
+                    postings.push(parse_posting(p, fmt, styles)?);
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    let xact = Xact {
+        date: NaiveDate::default(),
+        efdate: None,
+        state: State::None,
+        code: String::new(),
+        payee,
+        comment: String::new(),
+        postings,
+        tags: Vec::new(),
+        vtags: HashMap::new(),
+    };
+    // fail early if the template does not balance
+    xact.clone().into_xact(0)?;
+
+    let (period, begin, end) = period.unwrap();
+    Ok(PeriodicXact {
+        period,
+        begin,
+        end,
+        xact,
+    })
+}
+
+/// Parses the period of a periodic transaction: `Monthly` (any of
+/// daily, weekly, monthly, quarterly, yearly or annually) or `every
+/// [N] UNIT`, followed by optional `from DATE` and `to DATE` bounds.
+fn parse_period(s: &str) -> Option<(Step, Option<NaiveDate>, Option<NaiveDate>)> {
+    let lower = s.to_lowercase();
+    let mut words = lower.split_whitespace().peekable();
+
+    let period = match words.next()? {
+        "daily" => Step::Days(1),
+        "weekly" => Step::Weeks(1),
+        "monthly" => Step::Months(1),
+        "quarterly" => Step::Quarters(1),
+        "yearly" | "annually" => Step::Years(1),
+        "every" => {
+            let n = match words.peek()?.parse::<i32>() {
+                Ok(n) if n > 0 => {
+                    words.next();
+                    n
+                }
+                Ok(_) => return None,
+                Err(_) => 1,
+            };
+            match words.next()?.trim_end_matches('s') {
+                "day" => Step::Days(n),
+                "week" => Step::Weeks(n),
+                "month" => Step::Months(n),
+                "quarter" => Step::Quarters(n),
+                "year" => Step::Years(n),
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+
+    let mut begin = None;
+    let mut end = None;
+    if words.next_if_eq(&"from").is_some() {
+        begin = Some(parse_date_str(words.next()?)?);
+    }
+    if words.next_if_eq(&"to").is_some() {
+        end = Some(parse_date_str(words.next()?)?);
+    }
+    if words.next().is_some() || begin.zip(end).is_some_and(|(b, e)| b > e) {
+        return None;
+    }
+    Some((period, begin, end))
+}
+
+fn parse_auto_xact(
+    p: Pair<Rule>,
+    fmt: NumberFormat,
+    styles: &mut Styles,
+) -> Result<AutoXact, ParseError> {
+    let mut expr = None;
+    let mut postings = Vec::new();
+
+    for p in p.into_inner() {
+        match p.as_rule() {
+            Rule::auto_xact_expr => {
+                // `/regex/` and a bare `regex` are both accepted
+                let e = p.as_str().trim();
+                let e = e
+                    .strip_prefix('/')
+                    .and_then(|e| e.strip_suffix('/'))
+                    .unwrap_or(e);
+                let re =
+                    Regex::new(e).map_err(|_| ParseError::InvalidAutoXactExpr(e.to_string()))?;
+                expr = Some(re);
+            }
+            Rule::postings => {
+                for p in p.into_inner() {
+                    let ps = parse_posting(p, fmt, styles)?;
+                    if ps.quantity.is_none() {
+                        let e = expr.as_ref().map_or("", Regex::as_str);
+                        return Err(ParseError::AutoXactWithoutAmount(e.to_string()));
+                    }
+                    postings.push(ps);
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    Ok(AutoXact {
+        expr: expr.unwrap(),
+        postings,
     })
 }
 
-fn parse_xact(p: Pair<Rule>) -> Result<Xact, ParseError> {
+/// `year` is the year of the short `MM/DD` dates.
+fn parse_xact(
+    p: Pair<Rule>,
+    fmt: NumberFormat,
+    styles: &mut Styles,
+    year: i32,
+) -> Result<Xact, ParseError> {
     let inner = p.into_inner();
 
     let mut date = NaiveDate::default();
@@ -415,7 +1184,7 @@ fn parse_xact(p: Pair<Rule>) -> Result<Xact, ParseError> {
     for p in inner {
         match p.as_rule() {
             Rule::xact_date => {
-                let xd = parse_xact_date(p)?;
+                let xd = parse_xact_date(p, year)?;
                 date = xd.txdate;
                 efdate = xd.efdate;
             }
@@ -432,7 +1201,7 @@ fn parse_xact(p: Pair<Rule>) -> Result<Xact, ParseError> {
             }
             Rule::postings => {
                 for p in p.into_inner() {
-                    let ps = parse_posting(p)?;
+                    let ps = parse_posting(p, fmt, styles)?;
                     postings.push(ps);
                 }
             }
@@ -453,24 +1222,39 @@ fn parse_xact(p: Pair<Rule>) -> Result<Xact, ParseError> {
     })
 }
 
-fn parse_xact_date(p: Pair<Rule>) -> Result<XactDate, ParseError> {
+fn parse_xact_date(p: Pair<Rule>, year: i32) -> Result<XactDate, ParseError> {
     let mut p = p.into_inner();
 
     let date = p.next().unwrap().into_inner().next().unwrap();
-    let txdate = parse_date(date)?;
+    let txdate = parse_date_in_year(date, year)?;
     let efdate = if let Some(op) = p.next() {
-        Some(parse_date(op.into_inner().next().unwrap())?)
+        Some(parse_date_in_year(op.into_inner().next().unwrap(), year)?)
     } else {
         None
     };
 
-    Ok(XactDate { txdate, efdate })
+    Ok(XactDate {
+        txdate,
+        efdate,
+        ..Default::default()
+    })
 }
 
 fn parse_date(p: Pair<Rule>) -> Result<NaiveDate, ParseError> {
+    parse_date_in_year(p, 0)
+}
+
+/// Parses a `date` or a `short_date` rule; the latter, lacking the
+/// year, is taken in `year`.
+fn parse_date_in_year(p: Pair<Rule>, year: i32) -> Result<NaiveDate, ParseError> {
+    let is_short = p.as_rule() == Rule::short_date;
     let mut inner = p.into_inner();
 
-    let y: i32 = inner.next().unwrap().as_str().parse().unwrap();
+    let y: i32 = if is_short {
+        year
+    } else {
+        inner.next().unwrap().as_str().parse().unwrap()
+    };
     let m: u32 = inner.next().unwrap().as_str().parse().unwrap();
     let d: u32 = inner.next().unwrap().as_str().parse().unwrap();
 
@@ -481,6 +1265,59 @@ fn parse_date(p: Pair<Rule>) -> Result<NaiveDate, ParseError> {
     }
 }
 
+/// Parses the whole of `s`, surrounding spaces aside, as a date
+/// written as in the journal (`2024-03-01`, `2024/03/01`).
+pub fn parse_date_str(s: &str) -> Option<NaiveDate> {
+    let s = s.trim();
+    let p = LedgerParser::parse(Rule::date, s).ok()?.next()?;
+    if p.as_str().len() != s.len() {
+        return None;
+    }
+    parse_date(p).ok()
+}
+
+/// Parses the whole of `s`, surrounding spaces aside, as an amount
+/// written as in the journal (`$500.00`, `10 AAPL`).
+pub fn parse_amount_str(s: &str, fmt: NumberFormat) -> Option<Quantity> {
+    let s = s.trim();
+    let p = LedgerParser::parse(Rule::units_value, s).ok()?.next()?;
+    if p.as_str().len() != s.len() {
+        return None;
+    }
+    parse_unit_value(p, fmt, &mut Styles::default()).ok()
+}
+
+/// Splits an `account` rule into the account name and whether it was
+/// written as `(virtual)` or `[balanced virtual]`.
+fn parse_account(p: Pair<Rule>) -> Result<(String, Virtuality), ParseError> {
+    let inner = p.into_inner().next().unwrap();
+    let (name, virtuality) = match inner.as_rule() {
+        Rule::virtual_account => (inner.into_inner().next().unwrap(), Virtuality::Virtual),
+        Rule::balanced_virtual_account => (
+            inner.into_inner().next().unwrap(),
+            Virtuality::BalancedVirtual,
+        ),
+        _ => (inner, Virtuality::Real),
+    };
+    Ok((parse_acc_name(name)?.to_string(), virtuality))
+}
+
+/// The account name in `p`, in canonical form (see
+/// [`AccName::normalized`]).
+fn parse_acc_name(p: Pair<Rule>) -> Result<AccName, ParseError> {
+    AccName::normalized(p.as_str()).ok_or_else(|| ParseError::InvalidAccount(parse_text(p)))
+}
+
+/// Normalizes either side of an `alias` directive as posting accounts
+/// are (see [`AccName::normalized`]), dropping a trailing `:` too, so
+/// that `alias A = B:` names the same account as `alias A = B`.
+fn parse_alias_side(p: Pair<Rule>) -> Result<String, ParseError> {
+    let name = p.as_str().trim().trim_end_matches(AccName::SEP);
+    AccName::normalized(name)
+        .map(|name| name.to_string())
+        .ok_or_else(|| ParseError::InvalidAccount(parse_text(p)))
+}
+
 fn parse_text(p: Pair<Rule>) -> String {
     String::from(p.as_str())
 }
@@ -581,12 +1418,20 @@ fn parse_vtags(line: &str) -> Result<Option<(Tag, String)>, ParseError> {
     Ok(None)
 }
 
-fn parse_posting(p: Pair<Rule>) -> Result<Posting, ParseError> {
+fn parse_posting(
+    p: Pair<Rule>,
+    fmt: NumberFormat,
+    styles: &mut Styles,
+) -> Result<Posting, ParseError> {
+    let text = p.as_str().trim().to_string();
     let mut state = State::None;
     let mut account = String::from("");
+    let mut virtuality = Virtuality::Real;
     let mut quantity: Option<Quantity> = None;
     let mut uprice: Option<Quantity> = None;
+    let mut total_price: Option<Quantity> = None;
     let mut lots = Lots::default();
+    let mut assertion = None;
     let mut comment = String::new();
     let mut tags = Vec::new();
     let mut vtags = HashMap::new();
@@ -598,12 +1443,12 @@ fn parse_posting(p: Pair<Rule>) -> Result<Posting, ParseError> {
                 state = parse_state(p.as_str());
             }
 
-            Rule::account => account = parse_text(p),
+            Rule::account => (account, virtuality) = parse_account(p)?,
             Rule::quantity => {
-                quantity = Some(parse_quantity(p)?);
+                quantity = Some(parse_quantity(p, fmt, styles)?);
             }
             Rule::lots => {
-                lots = parse_lots(p)?;
+                lots = parse_lots(p, fmt, styles)?;
             }
             Rule::price => {
                 let mut inner = p.into_inner();
@@ -614,7 +1459,7 @@ fn parse_posting(p: Pair<Rule>) -> Result<Posting, ParseError> {
                 };
 
                 let tmp = inner.next().unwrap();
-                let price = parse_quantity(tmp)?;
+                let price = parse_quantity(tmp, fmt, styles)?;
 
                 if is_unitary {
                     uprice = Some(price);
@@ -625,7 +1470,12 @@ fn parse_posting(p: Pair<Rule>) -> Result<Posting, ParseError> {
                     panic!("units should be defined at this point");
                 };
 
-                uprice = Some(price / qty.q.abs());
+                uprice = Some(per_unit(price, qty, &text)?);
+                total_price = Some(price);
+            }
+            Rule::balance_assertion => {
+                let value = p.into_inner().next().unwrap();
+                assertion = Some(parse_unit_value(value, fmt, styles)?);
             }
             Rule::comment => {
                 (comment, tags, vtags) = parse_comment(p);
@@ -634,39 +1484,60 @@ fn parse_posting(p: Pair<Rule>) -> Result<Posting, ParseError> {
         }
     }
 
-    let lot_uprice = lots.price.map(|p| {
-        let price_base = lots.price_basis.unwrap();
-        let price_type = lots.price_type.unwrap();
-        match price_base {
-            PriceBasis::PerUnit => LotPrice {
-                price: p,
-                ptype: price_type,
-            },
-            PriceBasis::Total => LotPrice {
-                price: p / quantity.unwrap().q.abs(),
+    let total_lot_price = lots
+        .price
+        .filter(|_| lots.price_basis == Some(PriceBasis::Total));
+    let lot_uprice = lots
+        .price
+        .map(|p| {
+            let price_type = lots.price_type.unwrap();
+            let price = match lots.price_basis.unwrap() {
+                PriceBasis::PerUnit => p,
+                PriceBasis::Total => per_unit(p, &quantity.unwrap(), &text)?,
+            };
+            Ok(LotPrice {
+                price,
                 ptype: price_type,
-            },
-        }
-    });
+            })
+        })
+        .transpose()?;
 
     Ok(Posting {
         state,
         account,
+        virtuality,
         quantity,
         uprice,
         lot_uprice,
+        total_price,
+        total_lot_price,
+        written: None,
         lot_date: lots.date,
+        provenance: Provenance::Explicit,
         lot_note: lots.note,
+        assertion,
         comment,
         vtags,
         tags,
     })
 }
 
-fn parse_quantity(p: Pair<Rule>) -> Result<Quantity, ParseError> {
+/// The unit price of a total price (`@@`, `{{}}`) paid for `qty`;
+/// fails with the posting `text` if `qty` is zero.
+fn per_unit(total: Quantity, qty: &Quantity, text: &str) -> Result<Quantity, ParseError> {
+    total
+        .checked_div(qty.q.abs())
+        .ok_or_else(|| ParseError::InvalidNumber(text.to_string()))
+}
+
+fn parse_quantity(
+    p: Pair<Rule>,
+    fmt: NumberFormat,
+    styles: &mut Styles,
+) -> Result<Quantity, ParseError> {
     let p = p.into_inner().next().unwrap();
     match p.as_rule() {
-        Rule::units_value => parse_unit_value(p),
+        Rule::units_value => parse_unit_value(p, fmt, styles),
         // TODO: when implemented unit_expression an error could be
         // returned
         _ => unreachable!(),
@@ -675,13 +1546,18 @@ fn parse_quantity(p: Pair<Rule>) -> Result<Quantity, ParseError> {
 
 // TODO: this function should return a Result<Quantity, ParserError>
 // amount could be malformed for example 1,1,1 y valid amount
-fn parse_unit_value(p: Pair<Rule>) -> Result<Quantity, ParseError> {
+fn parse_unit_value(
+    p: Pair<Rule>,
+    fmt: NumberFormat,
+    styles: &mut Styles,
+) -> Result<Quantity, ParseError> {
+    let suffix = symbol_is_suffix(&p);
     let mut amount = Decimal::ZERO;
     let mut sym = Symbol::new("");
 
     for p in p.into_inner() {
         match p.as_rule() {
-            Rule::ammount => match parser_number::parse(p.as_str().trim(), NumberFormat::Us) {
+            Rule::ammount => match parser_number::parse(p.as_str().trim(), fmt) {
                 Some(n) => amount = n,
                 None => {
                     return Err(ParseError::InvalidNumber(p.as_str().to_string()));
@@ -696,9 +1572,27 @@ fn parse_unit_value(p: Pair<Rule>) -> Result<Quantity, ParseError> {
         }
     }
 
+    styles.observe(
+        sym,
+        Style {
+            places: amount.scale(),
+            suffix,
+            format: fmt,
+        },
+    );
     Ok(Quantity { q: amount, s: sym })
 }
 
+/// Whether the commodity of a `units_value` is written after the
+/// amount, as in `10 AAPL`.
+fn symbol_is_suffix(p: &Pair<Rule>) -> bool {
+    p.clone()
+        .into_inner()
+        .map(|p| p.as_rule())
+        .collect::<Vec<_>>()
+        == [Rule::ammount, Rule::commodity]
+}
+
 #[derive(Debug, Default)]
 pub struct Lots {
     price: Option<Quantity>,
@@ -709,7 +1603,7 @@ pub struct Lots {
     note: String,
 }
 
-fn parse_lots(p: Pair<Rule>) -> Result<Lots, ParseError> {
+fn parse_lots(p: Pair<Rule>, fmt: NumberFormat, styles: &mut Styles) -> Result<Lots, ParseError> {
     let mut note = String::new();
     let mut price: Option<Quantity> = None;
     let mut price_type: Option<PriceType> = None;
@@ -737,19 +1631,19 @@ fn parse_lots(p: Pair<Rule>) -> Result<Lots, ParseError> {
                         let unit_value = value_type.into_inner().next().unwrap();
                         price_type = Some(PriceType::Static);
                         price_basis = Some(PriceBasis::PerUnit);
-                        price = Some(parse_unit_value(unit_value)?);
+                        price = Some(parse_unit_value(unit_value, fmt, styles)?);
                     }
                     Rule::per_unit_point_value => {
                         let unit_value = value_type.into_inner().next().unwrap();
                         price_type = Some(PriceType::Floating);
                         price_basis = Some(PriceBasis::PerUnit);
-                        price = Some(parse_unit_value(unit_value)?);
+                        price = Some(parse_unit_value(unit_value, fmt, styles)?);
                     }
                     Rule::total_point_value => {
                         let unit_value = value_type.into_inner().next().unwrap();
                         price_type = Some(PriceType::Floating);
                         price_basis = Some(PriceBasis::Total);
-                        price = Some(parse_unit_value(unit_value)?)
+                        price = Some(parse_unit_value(unit_value, fmt, styles)?)
                     }
                     _ => unreachable!(),
                 }
@@ -788,7 +1682,11 @@ fn parse_state(s: &str) -> State {
     }
 }
 
-fn parse_market_price(p: Pair<Rule>) -> Result<MarketPrice, ParseError> {
+fn parse_market_price(
+    p: Pair<Rule>,
+    fmt: NumberFormat,
+    styles: &mut Styles,
+) -> Result<MarketPrice, ParseError> {
     let inner = p.into_inner();
 
     let mut date = None;
@@ -809,7 +1707,7 @@ fn parse_market_price(p: Pair<Rule>) -> Result<MarketPrice, ParseError> {
                 sym = Symbol::new(p.as_str());
             }
             Rule::units_value => {
-                price = Some(parse_unit_value(p)?);
+                price = Some(parse_unit_value(p, fmt, styles)?);
             }
             _ => unreachable!(),
         }
@@ -824,7 +1722,7 @@ fn parse_market_price(p: Pair<Rule>) -> Result<MarketPrice, ParseError> {
 
     Ok(MarketPrice {
         date_time: dt,
-        sym: sym,
+        sym,
         price: price.unwrap(),
     })
 }
@@ -847,12 +1745,17 @@ mod tests {
     Assets:Brokerage                     10 LTM {$30.00} @ $20.00
     Equity:Opening Balances
 ";
-        let mut raw_xact = match LedgerParser::parse(Rule::xact, &xact) {
+        let mut raw_xact = match LedgerParser::parse(Rule::xact, xact) {
             Ok(pairs) => pairs,
             Err(err) => return Err(ParseError::Parser(err)),
         };
 
-        let parsed = parse_xact(raw_xact.next().unwrap())?;
+        let parsed = parse_xact(
+            raw_xact.next().unwrap(),
+            NumberFormat::Us,
+            &mut Styles::default(),
+            2000,
+        )?;
 
         let expected = Xact {
             state: State::Cleared,
@@ -867,70 +1770,100 @@ mod tests {
                 Posting {
                     state: State::None,
                     account: String::from("Assets:Bank:Checking"),
+                    virtuality: Virtuality::Real,
                     quantity: Some(quantity!(1000.00, "$")),
                     uprice: None,
                     lot_uprice: None,
+                    total_price: None,
+                    total_lot_price: None,
+                    written: None,
                     lot_date: None,
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::from(":Tag1: Tag2: Value one"),
                     tags: vec![Tag::new("Tag1")],
                     vtags: [(Tag::new("Tag2"), String::from("Value one"))]
                         .into_iter()
                         .collect(),
+                    provenance: Provenance::Explicit,
                 },
                 Posting {
                     state: State::None,
                     account: String::from("Assets:Brokerage"),
+                    virtuality: Virtuality::Real,
                     quantity: Some(quantity!(50, "LTM")),
                     uprice: Some(quantity!(30.00, "$")),
                     lot_uprice: None,
+                    total_price: None,
+                    total_lot_price: None,
+                    written: None,
                     lot_date: None,
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::new(),
                     tags: Vec::new(),
                     vtags: HashMap::new(),
+                    provenance: Provenance::Explicit,
                 },
                 Posting {
                     state: State::None,
                     account: String::from("Assets:Brokerage"),
+                    virtuality: Virtuality::Real,
                     quantity: Some(quantity!(40, "LTM")),
                     uprice: None,
                     lot_uprice: Some(LotPrice {
                         price: quantity!(30.00, "$"),
                         ptype: PriceType::Floating,
                     }),
+                    total_price: None,
+                    total_lot_price: None,
+                    written: None,
                     lot_date: None,
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::new(),
                     tags: Vec::new(),
                     vtags: HashMap::new(),
+                    provenance: Provenance::Explicit,
                 },
                 Posting {
                     state: State::None,
                     account: String::from("Assets:Brokerage"),
+                    virtuality: Virtuality::Real,
                     quantity: Some(quantity!(10, "LTM")),
                     uprice: Some(quantity!(20.00, "$")),
                     lot_uprice: Some(LotPrice {
                         price: quantity!(30.00, "$"),
                         ptype: PriceType::Floating,
                     }),
+                    total_price: None,
+                    total_lot_price: None,
+                    written: None,
                     lot_date: None,
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::new(),
                     tags: Vec::new(),
                     vtags: HashMap::new(),
+                    provenance: Provenance::Explicit,
                 },
                 Posting {
                     state: State::None,
                     account: String::from("Equity:Opening Balances"),
+                    virtuality: Virtuality::Real,
                     quantity: None,
                     uprice: None,
                     lot_uprice: None,
+                    total_price: None,
+                    total_lot_price: None,
+                    written: None,
                     lot_date: None,
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::new(),
                     tags: Vec::new(),
                     vtags: HashMap::new(),
+                    provenance: Provenance::Explicit,
                 },
             ],
         };
@@ -944,6 +1877,7 @@ mod tests {
             date: XactDate {
                 txdate: NaiveDate::from_ymd_opt(2004, 5, 11).unwrap(),
                 efdate: None,
+                ..Default::default()
             },
             payee: String::from("Checking balance"),
             comment: String::from(":XTag:"),
@@ -954,84 +1888,114 @@ mod tests {
                     date: NaiveDate::from_ymd_opt(2004, 5, 11).unwrap(),
                     state: State::None,
                     acc_name: AccName::from("Assets:Bank:Checking"),
+                    virtuality: Virtuality::Real,
                     quantity: quantity!(1000.00, "$"),
                     uprice: quantity!(1, "$"),
                     lot_uprice: LotPrice {
                         price: quantity!(1, "$"),
                         ptype: PriceType::Floating,
                     },
+                    total_price: None,
+                    total_lot_price: None,
+                    written: None,
                     lot_date: None,
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::from(":Tag1: Tag2: Value one"),
                     tags: vec![Tag::new("Tag1")],
                     vtags: [(Tag::new("Tag2"), String::from("Value one"))]
                         .into_iter()
                         .collect(),
+                    provenance: Provenance::Explicit,
                 },
                 journal::Posting {
                     date: NaiveDate::from_ymd_opt(2004, 5, 11).unwrap(),
                     state: State::None,
                     acc_name: AccName::from("Assets:Brokerage"),
+                    virtuality: Virtuality::Real,
                     quantity: quantity!(50, "LTM"),
                     uprice: quantity!(30.00, "$"),
                     lot_uprice: LotPrice {
                         price: quantity!(30.00, "$"),
                         ptype: PriceType::Floating,
                     },
+                    total_price: None,
+                    total_lot_price: None,
+                    written: None,
                     lot_date: None,
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::new(),
                     tags: Vec::new(),
                     vtags: HashMap::new(),
+                    provenance: Provenance::Explicit,
                 },
                 journal::Posting {
                     date: NaiveDate::from_ymd_opt(2004, 5, 11).unwrap(),
                     state: State::None,
                     acc_name: AccName::from("Assets:Brokerage"),
+                    virtuality: Virtuality::Real,
                     quantity: quantity!(40, "LTM"),
                     uprice: quantity!(30.00, "$"),
                     lot_uprice: LotPrice {
                         price: quantity!(30.00, "$"),
                         ptype: PriceType::Floating,
                     },
+                    total_price: None,
+                    total_lot_price: None,
+                    written: None,
                     lot_date: None,
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::new(),
                     tags: Vec::new(),
                     vtags: HashMap::new(),
+                    provenance: Provenance::Explicit,
                 },
                 journal::Posting {
                     date: NaiveDate::from_ymd_opt(2004, 5, 11).unwrap(),
                     state: State::None,
                     acc_name: AccName::from("Assets:Brokerage"),
+                    virtuality: Virtuality::Real,
                     quantity: quantity!(10, "LTM"),
                     uprice: quantity!(20.00, "$"),
                     lot_uprice: LotPrice {
                         price: quantity!(30.00, "$"),
                         ptype: PriceType::Floating,
                     },
+                    total_price: None,
+                    total_lot_price: None,
+                    written: None,
                     lot_date: None,
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::new(),
                     tags: Vec::new(),
                     vtags: HashMap::new(),
+                    provenance: Provenance::Explicit,
                 },
                 // generate eliding amount
                 journal::Posting {
                     date: NaiveDate::from_ymd_opt(2004, 5, 11).unwrap(),
                     state: State::None,
                     acc_name: AccName::from("Equity:Opening Balances"),
+                    virtuality: Virtuality::Real,
                     quantity: quantity!(-4000.00, "$"),
                     uprice: quantity!(1, "$"),
                     lot_uprice: LotPrice {
                         price: quantity!(1, "$"),
                         ptype: PriceType::Floating,
                     },
+                    total_price: None,
+                    total_lot_price: None,
+                    written: None,
                     lot_date: None,
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::new(),
                     tags: Vec::new(),
                     vtags: HashMap::new(),
+                    provenance: Provenance::Inferred,
                 },
             ],
         };
@@ -1049,12 +2013,17 @@ mod tests {
     ! Assets:Brokerage                     10 LTM [2025/08/29] {$30.00} @ $20.00
     * Assets:Checking
 ";
-        let mut raw_xact = match LedgerParser::parse(Rule::xact, &xact) {
+        let mut raw_xact = match LedgerParser::parse(Rule::xact, xact) {
             Ok(pairs) => pairs,
             Err(err) => return Err(ParseError::Parser(err)),
         };
 
-        let parsed = parse_xact(raw_xact.next().unwrap())?;
+        let parsed = parse_xact(
+            raw_xact.next().unwrap(),
+            NumberFormat::Us,
+            &mut Styles::default(),
+            2000,
+        )?;
 
         let expected = Xact {
             state: State::Cleared,
@@ -1074,29 +2043,41 @@ mod tests {
                 Posting {
                     state: State::Pending,
                     account: String::from("Assets:Brokerage"),
+                    virtuality: Virtuality::Real,
                     quantity: Some(quantity!(10, "LTM")),
                     uprice: Some(quantity!(20.00, "$")),
                     lot_uprice: Some(LotPrice {
                         price: quantity!(30.00, "$"),
                         ptype: PriceType::Floating,
                     }),
+                    total_price: None,
+                    total_lot_price: None,
+                    written: None,
                     lot_date: Some(NaiveDate::from_ymd_opt(2025, 8, 29).unwrap()),
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::new(),
                     tags: Vec::new(),
                     vtags: HashMap::new(),
+                    provenance: Provenance::Explicit,
                 },
                 Posting {
                     state: State::Cleared,
                     account: String::from("Assets:Checking"),
+                    virtuality: Virtuality::Real,
                     quantity: None,
                     uprice: None,
                     lot_uprice: None,
+                    total_price: None,
+                    total_lot_price: None,
+                    written: None,
                     lot_date: None,
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::new(),
                     tags: Vec::new(),
                     vtags: HashMap::new(),
+                    provenance: Provenance::Explicit,
                 },
             ],
         };
@@ -1110,6 +2091,7 @@ mod tests {
             date: XactDate {
                 txdate: NaiveDate::from_ymd_opt(2004, 5, 11).unwrap(),
                 efdate: None,
+                ..Default::default()
             },
             payee: String::from("Checking balance"),
             comment: String::from("TagVal: Suma was great, but ma was blind"),
@@ -1125,34 +2107,46 @@ mod tests {
                     date: NaiveDate::from_ymd_opt(2004, 5, 11).unwrap(),
                     state: State::Pending,
                     acc_name: AccName::from("Assets:Brokerage"),
+                    virtuality: Virtuality::Real,
                     quantity: quantity!(10, "LTM"),
                     uprice: quantity!(20.00, "$"),
                     lot_uprice: LotPrice {
                         price: quantity!(30.00, "$"),
                         ptype: PriceType::Floating,
                     },
+                    total_price: None,
+                    total_lot_price: None,
+                    written: None,
                     lot_date: Some(NaiveDate::from_ymd_opt(2025, 8, 29).unwrap()),
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::new(),
                     tags: Vec::new(),
                     vtags: HashMap::new(),
+                    provenance: Provenance::Explicit,
                 },
                 // generate eliding amount
                 journal::Posting {
                     date: NaiveDate::from_ymd_opt(2004, 5, 11).unwrap(),
                     state: State::Cleared,
                     acc_name: AccName::from("Assets:Checking"),
+                    virtuality: Virtuality::Real,
                     quantity: quantity!(-300, "$"),
                     uprice: quantity!(1, "$"),
                     lot_uprice: LotPrice {
                         price: quantity!(1, "$"),
                         ptype: PriceType::Floating,
                     },
+                    total_price: None,
+                    total_lot_price: None,
+                    written: None,
                     lot_date: None,
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::new(),
                     tags: Vec::new(),
                     vtags: HashMap::new(),
+                    provenance: Provenance::Inferred,
                 },
             ],
         };
@@ -1170,12 +2164,17 @@ mod tests {
     ! Assets:Brokerage                     10 LTM {{$300.00}} [2025/08/29]  @ $20.00
     * Assets:Cash  ; :SuTag:MaTag:
 ";
-        let mut raw_xact = match LedgerParser::parse(Rule::xact, &xact) {
+        let mut raw_xact = match LedgerParser::parse(Rule::xact, xact) {
             Ok(pairs) => pairs,
             Err(err) => return Err(ParseError::Parser(err)),
         };
 
-        let parsed = parse_xact(raw_xact.next().unwrap())?;
+        let parsed = parse_xact(
+            raw_xact.next().unwrap(),
+            NumberFormat::Us,
+            &mut Styles::default(),
+            2000,
+        )?;
 
         let expected = Xact {
             state: State::Cleared,
@@ -1190,29 +2189,41 @@ mod tests {
                 Posting {
                     state: State::Pending,
                     account: String::from("Assets:Brokerage"),
+                    virtuality: Virtuality::Real,
                     quantity: Some(quantity!(10, "LTM")),
                     uprice: Some(quantity!(20.00, "$")),
                     lot_uprice: Some(LotPrice {
                         price: quantity!(30.00, "$"),
                         ptype: PriceType::Floating,
                     }),
+                    total_price: None,
+                    total_lot_price: Some(quantity!(300.00, "$")),
+                    written: None,
                     lot_date: Some(NaiveDate::from_ymd_opt(2025, 8, 29).unwrap()),
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::new(),
                     tags: Vec::new(),
                     vtags: HashMap::new(),
+                    provenance: Provenance::Explicit,
                 },
                 Posting {
                     state: State::Cleared,
                     account: String::from("Assets:Cash"),
+                    virtuality: Virtuality::Real,
                     quantity: None,
                     uprice: None,
                     lot_uprice: None,
+                    total_price: None,
+                    total_lot_price: None,
+                    written: None,
                     lot_date: None,
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::from(":SuTag:MaTag:"),
                     tags: vec![Tag::new("SuTag"), Tag::new("MaTag")],
                     vtags: HashMap::new(),
+                    provenance: Provenance::Explicit,
                 },
             ],
         };
@@ -1226,6 +2237,7 @@ mod tests {
             date: XactDate {
                 txdate: NaiveDate::from_ymd_opt(2004, 5, 11).unwrap(),
                 efdate: None,
+                ..Default::default()
             },
             payee: String::from("Checking balance"),
             comment: String::new(),
@@ -1236,34 +2248,46 @@ mod tests {
                     date: NaiveDate::from_ymd_opt(2004, 5, 11).unwrap(),
                     state: State::Pending,
                     acc_name: AccName::from("Assets:Brokerage"),
+                    virtuality: Virtuality::Real,
                     quantity: quantity!(10, "LTM"),
                     uprice: quantity!(20.00, "$"),
                     lot_uprice: LotPrice {
                         price: quantity!(30.00, "$"),
                         ptype: PriceType::Floating,
                     },
+                    total_price: None,
+                    total_lot_price: Some(quantity!(300.00, "$")),
+                    written: None,
                     lot_date: Some(NaiveDate::from_ymd_opt(2025, 8, 29).unwrap()),
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::new(),
                     tags: Vec::new(),
                     vtags: HashMap::new(),
+                    provenance: Provenance::Explicit,
                 },
                 // generate eliding amount
                 journal::Posting {
                     date: NaiveDate::from_ymd_opt(2004, 5, 11).unwrap(),
                     state: State::Cleared,
                     acc_name: AccName::from("Assets:Cash"),
+                    virtuality: Virtuality::Real,
                     quantity: quantity!(-300, "$"),
                     uprice: quantity!(1, "$"),
                     lot_uprice: LotPrice {
                         price: quantity!(1, "$"),
                         ptype: PriceType::Floating,
                     },
+                    total_price: None,
+                    total_lot_price: None,
+                    written: None,
                     lot_date: None,
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::from(":SuTag:MaTag:"),
                     tags: vec![Tag::new("SuTag"), Tag::new("MaTag")],
                     vtags: HashMap::new(),
+                    provenance: Provenance::Inferred,
                 },
             ],
         };
@@ -1280,12 +2304,17 @@ mod tests {
     ! Assets:Brokerage                     -10 LTM {{$300.00}} [2025/08/29] @@ $200.00
     * Assets:Cash
 ";
-        let mut raw_xact = match LedgerParser::parse(Rule::xact, &xact) {
+        let mut raw_xact = match LedgerParser::parse(Rule::xact, xact) {
             Ok(pairs) => pairs,
             Err(err) => return Err(ParseError::Parser(err)),
         };
 
-        let parsed = parse_xact(raw_xact.next().unwrap())?;
+        let parsed = parse_xact(
+            raw_xact.next().unwrap(),
+            NumberFormat::Us,
+            &mut Styles::default(),
+            2000,
+        )?;
 
         let expected = Xact {
             state: State::Cleared,
@@ -1300,29 +2329,41 @@ mod tests {
                 Posting {
                     state: State::Pending,
                     account: String::from("Assets:Brokerage"),
+                    virtuality: Virtuality::Real,
                     quantity: Some(quantity!(-10, "LTM")),
                     uprice: Some(quantity!(20.00, "$")),
                     lot_uprice: Some(LotPrice {
                         price: quantity!(30.00, "$"),
                         ptype: PriceType::Floating,
                     }),
+                    total_price: Some(quantity!(200.00, "$")),
+                    total_lot_price: Some(quantity!(300.00, "$")),
+                    written: None,
                     lot_date: Some(NaiveDate::from_ymd_opt(2025, 8, 29).unwrap()),
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::new(),
                     tags: Vec::new(),
                     vtags: HashMap::new(),
+                    provenance: Provenance::Explicit,
                 },
                 Posting {
                     state: State::Cleared,
                     account: String::from("Assets:Cash"),
+                    virtuality: Virtuality::Real,
                     quantity: None,
                     uprice: None,
                     lot_uprice: None,
+                    total_price: None,
+                    total_lot_price: None,
+                    written: None,
                     lot_date: None,
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::new(),
                     tags: Vec::new(),
                     vtags: HashMap::new(),
+                    provenance: Provenance::Explicit,
                 },
             ],
         };
@@ -1336,6 +2377,7 @@ mod tests {
             date: XactDate {
                 txdate: NaiveDate::from_ymd_opt(2004, 5, 11).unwrap(),
                 efdate: None,
+                ..Default::default()
             },
             payee: String::from("Checking balance"),
             comment: String::new(),
@@ -1346,34 +2388,46 @@ mod tests {
                     date: NaiveDate::from_ymd_opt(2004, 5, 11).unwrap(),
                     state: State::Pending,
                     acc_name: AccName::from("Assets:Brokerage"),
+                    virtuality: Virtuality::Real,
                     quantity: quantity!(-10, "LTM"),
                     uprice: quantity!(20.00, "$"),
                     lot_uprice: LotPrice {
                         price: quantity!(30.00, "$"),
                         ptype: PriceType::Floating,
                     },
+                    total_price: Some(quantity!(200.00, "$")),
+                    total_lot_price: Some(quantity!(300.00, "$")),
+                    written: None,
                     lot_date: Some(NaiveDate::from_ymd_opt(2025, 8, 29).unwrap()),
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::new(),
                     tags: Vec::new(),
                     vtags: HashMap::new(),
+                    provenance: Provenance::Explicit,
                 },
                 // generate eliding amount
                 journal::Posting {
                     date: NaiveDate::from_ymd_opt(2004, 5, 11).unwrap(),
                     state: State::Cleared,
                     acc_name: AccName::from("Assets:Cash"),
+                    virtuality: Virtuality::Real,
                     quantity: quantity!(300, "$"),
                     uprice: quantity!(1, "$"),
                     lot_uprice: LotPrice {
                         price: quantity!(1, "$"),
                         ptype: PriceType::Floating,
                     },
+                    total_price: None,
+                    total_lot_price: None,
+                    written: None,
                     lot_date: None,
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::new(),
                     tags: Vec::new(),
                     vtags: HashMap::new(),
+                    provenance: Provenance::Inferred,
                 },
             ],
         };
@@ -1390,12 +2444,17 @@ mod tests {
     Assets:Brokerage      1 X
     Assets:Checking      -1 Y
 ";
-        let mut raw_xact = match LedgerParser::parse(Rule::xact, &xact) {
+        let mut raw_xact = match LedgerParser::parse(Rule::xact, xact) {
             Ok(pairs) => pairs,
             Err(err) => return Err(ParseError::Parser(err)),
         };
 
-        let parsed = parse_xact(raw_xact.next().unwrap())?;
+        let parsed = parse_xact(
+            raw_xact.next().unwrap(),
+            NumberFormat::Us,
+            &mut Styles::default(),
+            2000,
+        )?;
 
         let expected = Xact {
             state: State::Cleared,
@@ -1410,26 +2469,38 @@ mod tests {
                 Posting {
                     state: State::None,
                     account: String::from("Assets:Brokerage"),
+                    virtuality: Virtuality::Real,
                     quantity: Some(quantity!(1, "X")),
                     uprice: None,
                     lot_uprice: None,
+                    total_price: None,
+                    total_lot_price: None,
+                    written: None,
                     lot_date: None,
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::new(),
                     tags: Vec::new(),
                     vtags: HashMap::new(),
+                    provenance: Provenance::Explicit,
                 },
                 Posting {
                     state: State::None,
                     account: String::from("Assets:Checking"),
+                    virtuality: Virtuality::Real,
                     quantity: Some(quantity!(-1, "Y")),
                     uprice: None,
                     lot_uprice: None,
+                    total_price: None,
+                    total_lot_price: None,
+                    written: None,
                     lot_date: None,
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::new(),
                     tags: Vec::new(),
                     vtags: HashMap::new(),
+                    provenance: Provenance::Explicit,
                 },
             ],
         };
@@ -1443,6 +2514,7 @@ mod tests {
             date: XactDate {
                 txdate: NaiveDate::from_ymd_opt(2004, 5, 11).unwrap(),
                 efdate: None,
+                ..Default::default()
             },
             payee: String::from("Checking balance"),
             comment: String::new(),
@@ -1453,6 +2525,7 @@ mod tests {
                     date: NaiveDate::from_ymd_opt(2004, 5, 11).unwrap(),
                     state: State::None,
                     acc_name: AccName::from("Assets:Brokerage"),
+                    virtuality: Virtuality::Real,
                     quantity: quantity!(1, "X"),
                     uprice: quantity!(1, "Y"), // Y is the primary commodity
                     lot_uprice: LotPrice {
@@ -1460,27 +2533,38 @@ mod tests {
                         ptype: PriceType::Floating,
                     },
 
+                    total_price: None,
+                    total_lot_price: None,
+                    written: None,
                     lot_date: None,
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::new(),
                     tags: Vec::new(),
                     vtags: HashMap::new(),
+                    provenance: Provenance::Explicit,
                 },
                 journal::Posting {
                     date: NaiveDate::from_ymd_opt(2004, 5, 11).unwrap(),
                     state: State::None,
                     acc_name: AccName::from("Assets:Checking"),
+                    virtuality: Virtuality::Real,
                     quantity: quantity!(-1, "Y"),
                     uprice: quantity!(1, "Y"),
                     lot_uprice: LotPrice {
                         price: quantity!(1, "Y"),
                         ptype: PriceType::Floating,
                     },
+                    total_price: None,
+                    total_lot_price: None,
+                    written: None,
                     lot_date: None,
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::new(),
                     tags: Vec::new(),
                     vtags: HashMap::new(),
+                    provenance: Provenance::Explicit,
                 },
             ],
         };
@@ -1497,14 +2581,19 @@ mod tests {
     Assets:Brokerage      1 X
     Assets:Checking       1 Y
 ";
-        let mut raw_xact = match LedgerParser::parse(Rule::xact, &xact) {
+        let mut raw_xact = match LedgerParser::parse(Rule::xact, xact) {
             Ok(pairs) => pairs,
             Err(err) => return Err(ParseError::Parser(err)),
         };
 
-        let parsed = parse_xact(raw_xact.next().unwrap())?;
+        let parsed = parse_xact(
+            raw_xact.next().unwrap(),
+            NumberFormat::Us,
+            &mut Styles::default(),
+            2000,
+        )?;
         let xact = parsed.into_xact(0);
-        assert!(matches!(xact, Err(ParseError::XactNoBalanced)));
+        assert!(matches!(xact, Err(ParseError::XactNoBalanced { .. })));
         Ok(())
     }
 
@@ -1515,14 +2604,72 @@ mod tests {
     Assets:Brokerage      1 X
     Assets:Checking       1 X
 ";
-        let mut raw_xact = match LedgerParser::parse(Rule::xact, &xact) {
+        let mut raw_xact = match LedgerParser::parse(Rule::xact, xact) {
+            Ok(pairs) => pairs,
+            Err(err) => return Err(ParseError::Parser(err)),
+        };
+
+        let parsed = parse_xact(
+            raw_xact.next().unwrap(),
+            NumberFormat::Us,
+            &mut Styles::default(),
+            2000,
+        )?;
+        let xact = parsed.into_xact(0);
+        assert!(matches!(xact, Err(ParseError::XactNoBalanced { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn test_into_xact_without_postings() {
+        let xact = Xact {
+            date: NaiveDate::from_ymd_opt(2026, 1, 1).unwrap(),
+            efdate: None,
+            state: State::None,
+            code: String::new(),
+            payee: "Nothing".to_string(),
+            comment: String::new(),
+            postings: vec![],
+            tags: vec![],
+            vtags: HashMap::new(),
+        };
+        let res = xact.into_xact(0);
+        assert!(matches!(
+            res,
+            Err(ParseError::EmptyTransaction { date, payee })
+                if date == NaiveDate::from_ymd_opt(2026, 1, 1).unwrap() && payee == "Nothing"
+        ));
+    }
+
+    #[test]
+    fn test_parse_journal_xact_header_without_postings() {
+        let res = parse_journal("2026-01-01 Nothing\n\n2026-01-02 Store\n  A  $1\n  B\n");
+        assert!(matches!(
+            res,
+            Err(ParseError::EmptyTransaction { date, payee })
+                if date == NaiveDate::from_ymd_opt(2026, 1, 1).unwrap() && payee == "Nothing"
+        ));
+    }
+
+    #[test]
+    fn test_parse_xact_single_posting_no_eliding() -> Result<(), ParseError> {
+        let xact = "\
+2026-01-01 * Lonely
+    Assets:Cash      $100.00
+";
+        let mut raw_xact = match LedgerParser::parse(Rule::xact, xact) {
             Ok(pairs) => pairs,
             Err(err) => return Err(ParseError::Parser(err)),
         };
 
-        let parsed = parse_xact(raw_xact.next().unwrap())?;
+        let parsed = parse_xact(
+            raw_xact.next().unwrap(),
+            NumberFormat::Us,
+            &mut Styles::default(),
+            2000,
+        )?;
         let xact = parsed.into_xact(0);
-        assert!(matches!(xact, Err(ParseError::XactNoBalanced)));
+        assert!(matches!(xact, Err(ParseError::XactNoBalanced { .. })));
         Ok(())
     }
 
@@ -1538,7 +2685,7 @@ P 2025/08/09  12:00:00 LTM $ 21.10
 
 P 2025/08/28 LTM  $ 23.69
 ";
-        let parsed = parse_journal(&jf.to_string())?;
+        let parsed = parse_journal(jf)?;
         let expected = journal::Xact {
             id: 0,
             state: State::Cleared,
@@ -1546,6 +2693,7 @@ P 2025/08/28 LTM  $ 23.69
             date: XactDate {
                 txdate: NaiveDate::from_ymd_opt(2004, 5, 11).unwrap(),
                 efdate: None,
+                ..Default::default()
             },
             payee: String::from("Checking balance"),
             comment: String::new(),
@@ -1557,34 +2705,46 @@ P 2025/08/28 LTM  $ 23.69
                     date: NaiveDate::from_ymd_opt(2004, 5, 11).unwrap(),
                     state: State::Pending,
                     acc_name: AccName::from("Assets:Brokerage"),
+                    virtuality: Virtuality::Real,
                     quantity: quantity!(-10, "LTM"),
                     uprice: quantity!(20.00, "$"),
                     lot_uprice: LotPrice {
                         price: quantity!(30.00, "$"),
                         ptype: PriceType::Floating,
                     },
+                    total_price: Some(quantity!(200.00, "$")),
+                    total_lot_price: Some(quantity!(300.00, "$")),
+                    written: None,
                     lot_date: Some(NaiveDate::from_ymd_opt(2025, 8, 29).unwrap()),
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::new(),
                     tags: Vec::new(),
                     vtags: HashMap::new(),
+                    provenance: Provenance::Explicit,
                 },
                 // generate eliding amount
                 journal::Posting {
                     date: NaiveDate::from_ymd_opt(2004, 5, 11).unwrap(),
                     state: State::Cleared,
                     acc_name: AccName::from("Assets:Cash"),
+                    virtuality: Virtuality::Real,
                     quantity: quantity!(300, "$"),
                     uprice: quantity!(1, "$"),
                     lot_uprice: LotPrice {
                         price: quantity!(1, "$"),
                         ptype: PriceType::Floating,
                     },
+                    total_price: None,
+                    total_lot_price: None,
+                    written: None,
                     lot_date: None,
                     lot_note: String::new(),
+                    assertion: None,
                     comment: String::new(),
                     tags: Vec::new(),
                     vtags: HashMap::new(),
+                    provenance: Provenance::Inferred,
                 },
             ],
         };
@@ -1723,7 +2883,140 @@ P 2025/08/28 LTM  $ 23.69
 "
         .to_string();
         let result = parse_journal(&jf);
-        assert!(matches!(result, Err(ParseError::XactNoBalanced)));
+        assert!(matches!(result, Err(ParseError::XactNoBalanced { .. })));
+    }
+
+    #[test]
+    fn test_parse_journal_virtual_postings() {
+        // `(..)` needn't balance, `[..]` balances on its own and takes
+        // its own elided amount
+        let jf = "\
+2026-01-01 * Paycheck
+    Assets:Bank       $100.00
+    Income:Salary
+    (Budget:Food)     $30.00
+    [Budget:Rent]     $60.00
+    [Budget:Unassigned]
+";
+        let parsed = parse_journal(jf).unwrap();
+        let postings: Vec<_> = parsed.xacts[0]
+            .postings
+            .iter()
+            .map(|p| (p.acc_name.to_string(), p.virtuality, p.quantity))
+            .collect();
+        assert_eq!(
+            postings,
+            vec![
+                (
+                    "Assets:Bank".to_string(),
+                    Virtuality::Real,
+                    quantity!(100, "$")
+                ),
+                (
+                    "Budget:Food".to_string(),
+                    Virtuality::Virtual,
+                    quantity!(30, "$")
+                ),
+                (
+                    "Budget:Rent".to_string(),
+                    Virtuality::BalancedVirtual,
+                    quantity!(60, "$")
+                ),
+                (
+                    "Income:Salary".to_string(),
+                    Virtuality::Real,
+                    quantity!(-100, "$")
+                ),
+                (
+                    "Budget:Unassigned".to_string(),
+                    Virtuality::BalancedVirtual,
+                    quantity!(-60, "$")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_journal_virtual_names_follow_account_names() {
+        // words are separated by single spaces, as in a real account
+        let jf = "\
+2026-01-01 * Paycheck
+    Assets:Bank       $100.00
+    Income:Salary
+    (Budget:Eating Out)     $30.00
+";
+        let parsed = parse_journal(jf).unwrap();
+        assert_eq!(
+            &parsed.xacts[0].postings[1].acc_name[..],
+            "Budget:Eating Out"
+        );
+
+        let jf = jf.replace("Eating Out", "Eating  Out");
+        assert!(matches!(parse_journal(&jf), Err(ParseError::Parser(_))));
+    }
+
+    #[test]
+    fn test_parse_journal_alias() {
+        let jf = "\
+2026-01-01 * Before the alias
+    Checking         $10.00
+    Income
+
+alias Bank=Assets:Bank
+alias Checking=Bank:Checking
+
+2026-01-02 * After the alias
+    Checking         $20.00
+    Checking:Sub     $5.00
+    Income
+";
+        let parsed = parse_journal(jf).unwrap();
+        let accounts: Vec<Vec<&str>> = parsed
+            .xacts
+            .iter()
+            .map(|x| x.postings.iter().map(|p| &*p.acc_name).collect())
+            .collect();
+
+        assert_eq!(accounts[0], vec!["Checking", "Income"]);
+        assert_eq!(
+            accounts[1],
+            vec!["Assets:Bank:Checking", "Assets:Bank:Checking:Sub", "Income"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_alias_cycle_terminates() {
+        let aliases = HashMap::from([
+            ("A".to_string(), "B".to_string()),
+            ("B".to_string(), "A".to_string()),
+        ]);
+        assert_eq!(resolve_alias("A:X", &aliases), "A:X");
+        assert_eq!(resolve_alias("Other", &aliases), "Other");
+    }
+
+    #[test]
+    fn test_parse_journal_alias_sides_are_normalized() {
+        let jf = "\
+alias Bank: = Assets: Bank :
+alias Cash = Assets:Cash:
+
+2026-01-01 * Normalized aliases
+    Bank:Checking    $20.00
+    Cash
+";
+        let parsed = parse_journal(jf).unwrap();
+        let accounts: Vec<&str> = parsed.xacts[0]
+            .postings
+            .iter()
+            .map(|p| &*p.acc_name)
+            .collect();
+        assert_eq!(accounts, vec!["Assets:Bank:Checking", "Assets:Cash"]);
+
+        let jf = "alias Bank = Assets::Bank\n";
+        assert!(matches!(
+            parse_journal(jf),
+            Err(ParseError::InvalidAccount(name)) if name == "Assets::Bank"
+        ));
     }
 
     #[test]
@@ -1737,7 +3030,39 @@ P 2025/08/28 LTM  $ 23.69
 "
         .to_string();
         let result = parse_journal(&jf);
-        assert!(matches!(result, Err(ParseError::ElidingAmount(_))));
+        assert!(matches!(
+            result,
+            Err(ParseError::ElidingAmount { count: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_xact_errors_name_the_transaction() {
+        let jf = "\
+2004/05/11 * Checking balance
+    Assets:Brokerage
+    Assets:Checking
+";
+        let Err(err) = parse_journal(jf) else {
+            panic!("expected an error");
+        };
+        assert_eq!(
+            err.to_string(),
+            "transaction 2004-05-11 'Checking balance': too many postings without amounts"
+        );
+
+        let jf = "\
+2004/05/11 * Checking balance
+    Assets:Brokerage      1 X
+    Assets:Checking       1 X
+";
+        let Err(err) = parse_journal(jf) else {
+            panic!("expected an error");
+        };
+        assert_eq!(
+            err.to_string(),
+            "transaction 2004-05-11 'Checking balance': postings do not balance"
+        );
     }
 
     #[test]
@@ -1764,7 +3089,7 @@ P 2025/08/28 LTM  $ 23.69
     Assets:Brokerage     10 LTM (my note) {$30.00} @ $30.00
     Assets:Cash
 ";
-        let parsed = parse_journal(&xact.to_string())?;
+        let parsed = parse_journal(xact)?;
         assert_eq!(parsed.xacts.len(), 1);
         assert_eq!(parsed.xacts[0].postings[0].lot_note, "my note");
         Ok(())
@@ -1778,7 +3103,7 @@ P 2025/08/28 LTM  $ 23.69
     Assets:Brokerage     10 LTM {=$30.00} @ $30.00
     Assets:Cash
 ";
-        let parsed = parse_journal(&xact.to_string())?;
+        let parsed = parse_journal(xact)?;
         assert_eq!(parsed.xacts.len(), 1);
         assert_eq!(
             parsed.xacts[0].postings[0].lot_uprice,
@@ -1807,6 +3132,113 @@ P 2025/08/28 LTM  $ 23.69
         // Just verify no panic; the result is either Ok or Err
     }
 
+    #[test]
+    fn test_parse_quoted_commodity_with_spaces() -> Result<(), ParseError> {
+        let jf = "\
+2026/01/01 * Buy fund
+    Assets:Broker    10 \"Vanguard 500\" @ $30
+    Assets:Cash
+";
+        let parsed = parse_journal(jf)?;
+        let posting = &parsed.xacts[0].postings[0];
+        assert_eq!(posting.quantity, quantity!(10, "Vanguard 500"));
+        assert_eq!(posting.uprice, quantity!(30, "$"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_journal_european_format() -> Result<(), ParseError> {
+        let jf = "\
+2026/01/01 * Test
+    Assets:Cash    1.234,56 EUR
+    Assets:Other
+";
+        let parsed = parse_journal_with_format(jf, NumberFormat::European)?;
+        assert_eq!(
+            parsed.xacts[0].postings[0].quantity,
+            quantity!(1234.56, "EUR")
+        );
+
+        let result = parse_journal_with_format(
+            "\
+2026/01/01 * Test
+    Assets:Cash    1,23,456 EUR
+    Assets:Other
+",
+            NumberFormat::Us,
+        );
+        assert!(matches!(result, Err(ParseError::InvalidNumber(n)) if n == "1,23,456"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_journal_number_format_directives() -> Result<(), ParseError> {
+        let jf = "\
+2026/01/01 * Before
+    Assets:Cash    1,234.56 EUR
+    Assets:Other
+
+D 1.000,00 EUR
+
+2026/01/02 * After D
+    Assets:Cash    1.234,56 EUR
+    Assets:Other
+
+commodity EUR
+    note Euro
+    format 1,000.00 EUR
+
+2026/01/03 * After commodity
+    Assets:Cash    1,234.56 EUR
+    Assets:Other
+";
+        let parsed = parse_journal(jf)?;
+        for xact in &parsed.xacts {
+            assert_eq!(xact.postings[0].quantity, quantity!(1234.56, "EUR"));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_journal_indian_format_directive() -> Result<(), ParseError> {
+        let jf = "\
+commodity INR
+    format INR 1,00,000.00
+
+2026/01/01 * Salary
+    Assets:Bank    INR 12,34,567.50
+    Assets:Cash    INR -500.00
+    Income:Salary
+";
+        let parsed = parse_journal(jf)?;
+        let postings = &parsed.xacts[0].postings;
+        assert_eq!(postings[0].quantity, quantity!(1234567.50, "INR"));
+        assert_eq!(postings[1].quantity, quantity!(-500.00, "INR"));
+        let inr = Symbol::new("INR");
+        assert_eq!(parsed.styles.get(inr).format, NumberFormat::Indian);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_journal_short_dates_take_the_year_of_now() -> Result<(), ParseError> {
+        let jf = "\
+01/05 * Coffee
+    Expenses:Food    $5
+    Assets:Cash
+";
+        let opts = |y| ParseOptions {
+            now: NaiveDate::from_ymd_opt(y, 6, 1).unwrap(),
+            ..Default::default()
+        };
+        let date = |y| -> Result<NaiveDate, ParseError> {
+            let parsed = parse_journal_with_options(jf, NumberFormat::Us, opts(y))?;
+            Ok(parsed.xacts[0].date.date())
+        };
+        assert_eq!(date(2020)?, NaiveDate::from_ymd_opt(2020, 1, 5).unwrap());
+        assert_eq!(date(2024)?, NaiveDate::from_ymd_opt(2024, 1, 5).unwrap());
+        Ok(())
+    }
+
     // --- market price with time in grammar ---
 
     #[test]
@@ -1814,7 +3246,7 @@ P 2025/08/28 LTM  $ 23.69
         let jf = "\
 P 2025/09/13 12:00:00 AAPL $ 150.25
 ";
-        let parsed = parse_journal(&jf.to_string())?;
+        let parsed = parse_journal(jf)?;
         assert_eq!(parsed.market_prices.len(), 1);
         assert_eq!(
             parsed.market_prices[0].date_time,
@@ -1836,7 +3268,7 @@ P 2025/09/13 12:00:00 AAPL $ 150.25
     Income:Salary   $-500
     Assets:Cash      $500
 ";
-        let parsed = parse_journal(&jf.to_string())?;
+        let parsed = parse_journal(jf)?;
         assert_eq!(parsed.xacts.len(), 1);
         assert_eq!(
             parsed.xacts[0].date.txdate,
@@ -1875,7 +3307,7 @@ P 2025/09/13 12:00:00 AAPL $ 150.25
 ",
         ];
         for jf in &variants {
-            let parsed = parse_journal(&jf.to_string())?;
+            let parsed = parse_journal(jf)?;
             assert_eq!(parsed.xacts.len(), 1);
         }
         Ok(())
@@ -1915,7 +3347,7 @@ P 2025/09/13 12:00:00 AAPL $ 150.25
     Assets:Brokerage    10 AAPL {$100} [2025/13/01]
     Assets:Cash        $-1000
 ";
-        let result = parse_journal(&jf.to_string());
+        let result = parse_journal(jf);
         assert!(matches!(result, Err(ParseError::InvalidDate)));
     }
 
@@ -1928,7 +3360,7 @@ P 2025/09/13 12:00:00 AAPL $ 150.25
         let jf = "\
 P 2025/09/13 25:00:00 AAPL $ 150.25
 ";
-        let result = parse_journal(&jf.to_string());
+        let result = parse_journal(jf);
         assert!(matches!(result, Err(ParseError::InvalidDate)));
     }
 
@@ -1968,4 +3400,83 @@ P 2025/09/13 25:00:00 AAPL $ 150.25
         expected.insert(Tag::new("memo"), "latte".to_string());
         assert_eq!(vtags, expected);
     }
+
+    #[test]
+    fn test_total_price_of_zero_quantity() -> Result<(), ParseError> {
+        let parse = |posting: &str| {
+            let xact = format!("2025/01/01 t\n    A    {posting}\n    B\n");
+            let mut raw = LedgerParser::parse(Rule::xact, &xact).map_err(ParseError::Parser)?;
+            parse_xact(
+                raw.next().unwrap(),
+                NumberFormat::Us,
+                &mut Styles::default(),
+                2000,
+            )
+        };
+
+        let xact = parse("0 X @ $5.00")?;
+        assert_eq!(xact.postings[0].uprice, Some(quantity!(5.00, "$")));
+
+        for posting in ["0 X @@ $5.00", "0 X {{$5.00}}"] {
+            match parse(posting) {
+                Err(ParseError::InvalidNumber(text)) => assert_eq!(text, format!("A    {posting}")),
+                _ => panic!("expected InvalidNumber for {posting:?}"),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_quantity_ledger_string_round_trips() {
+        // a fixed linear congruential sequence stands in for random input
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+            seed >> 33
+        };
+
+        // as after `commodity RTF` with `format 1 RTF`: fewer places
+        // than the amounts have
+        let mut styles = Styles::default();
+        styles.set(
+            Symbol::new("RTF"),
+            Style {
+                places: 0,
+                suffix: true,
+                format: NumberFormat::Us,
+            },
+        );
+        // as after `commodity RTI` with `format 1,00,000.00 RTI`
+        styles.set(
+            Symbol::new("RTI"),
+            Style {
+                places: 2,
+                suffix: true,
+                format: NumberFormat::Indian,
+            },
+        );
+
+        let us = NumberFormat::Us;
+        let cases = [
+            ("¤{}", us),
+            ("{} RTQ", us),
+            ("{} \"RT Q\"", us),
+            ("{} RTF", us),
+            ("{} RTI", NumberFormat::Indian),
+        ];
+        for (template, fmt) in cases {
+            for _ in 0..200 {
+                let value = Decimal::new(next() as i64 - (1 << 30), (next() % 9) as u32);
+                let number = fmt.format(value, value.scale() as usize);
+                let written = template.replace("{}", &number);
+                let parsed = parse_amount_str(&written, fmt).unwrap();
+                let text = parsed.to_ledger_string(&styles);
+                assert_eq!(
+                    parse_amount_str(&text, fmt),
+                    Some(parsed),
+                    "{written} was written back as {text}"
+                );
+            }
+        }
+    }
 }
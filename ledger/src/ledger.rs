@@ -1,19 +1,86 @@
 use std::collections::HashMap;
 
 use chrono::NaiveDate;
+use rust_decimal::Decimal;
 
 use crate::account::AccPostingSrc;
-use crate::journal::{AccName, Journal, Posting, Xact};
+use crate::amount::Amount;
+use crate::balance::Valuation;
+use crate::journal::{AccName, Journal, Posting, StateFilter, Virtuality, Xact};
+use crate::limit::Limit;
 use crate::misc::BetweenDate;
+use crate::pricedb::PriceDB;
+use crate::quantity::Quantity;
+use crate::query::Query;
+use crate::symbol::Symbol;
 
 #[derive(Debug)]
 pub struct Ledger<'l> {
-    acc_posting: HashMap<&'l AccName, Vec<&'l Posting>>,
+    acc_posting: HashMap<&'l AccName, Vec<PostingCtx<'l>>>,
+}
+
+/// A posting together with the transaction it belongs to, as seen by
+/// [`Ledger::filter_postings`] predicates.
+#[derive(Debug, Clone, Copy)]
+pub struct PostingCtx<'l> {
+    /// Transaction that owns `posting`.
+    pub xact: &'l Xact,
+    /// The posting itself.
+    pub posting: &'l Posting,
+}
+
+/// The posting-level report filters: clearing state, the `--limit`
+/// amount predicate, `--real`, `--actual` and `--commodity`. A
+/// posting passes when it satisfies all of them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PostingFilter<'a> {
+    pub state: StateFilter,
+    pub limit: Option<&'a Limit>,
+    /// Leave out virtual postings, balanced or not.
+    pub real: bool,
+    /// Leave out generated postings, see [`Posting::is_generated`].
+    pub actual: bool,
+    /// Keep only the postings of this commodity.
+    pub commodity: Option<Symbol>,
+}
+
+impl PostingFilter<'_> {
+    /// Whether the filter keeps nothing out.
+    pub fn is_empty(&self) -> bool {
+        self.state == StateFilter::All
+            && self.limit.is_none()
+            && !self.real
+            && !self.actual
+            && self.commodity.is_none()
+    }
+
+    /// Whether `ctx.posting` passes the filter.
+    pub fn matches(&self, ctx: &PostingCtx) -> bool {
+        self.state.matches(ctx.xact, ctx.posting)
+            && self.limit.is_none_or(|l| l.matches(&ctx.posting.quantity))
+            && (!self.real || ctx.posting.virtuality == Virtuality::Real)
+            && (!self.actual || !ctx.posting.is_generated())
+            && self.commodity.is_none_or(|s| ctx.posting.quantity.s == s)
+    }
+}
+
+/// A balance assertion (`= AMOUNT`) that doesn't hold, as reported by
+/// [`Ledger::verify_assertions`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssertionMismatch<'l> {
+    /// Transaction holding the failed assertion.
+    pub xact: &'l Xact,
+    /// Account whose balance was asserted.
+    pub acc_name: &'l AccName,
+    /// Asserted balance.
+    pub expected: Quantity,
+    /// Actual balance in the asserted commodity after the posting.
+    pub actual: Quantity,
 }
 
 struct AccPosting<'a> {
     acc_name: AccName,
-    postings: &'a Vec<&'a Posting>,
+    postings: &'a Vec<PostingCtx<'a>>,
 }
 
 impl<'a> AccPostingSrc<'a> for AccPosting<'a> {
@@ -21,7 +88,22 @@ impl<'a> AccPostingSrc<'a> for AccPosting<'a> {
         &self.acc_name
     }
 
-    fn postings(&self) -> Box<dyn Iterator<Item = &'a Posting> + 'a> {
+    fn postings(&self) -> Box<dyn Iterator<Item = &'a Posting> + '_> {
+        Box::new(self.postings.iter().map(|ctx| ctx.posting))
+    }
+}
+
+struct MatchedPostings<'a> {
+    acc_name: AccName,
+    postings: Vec<&'a Posting>,
+}
+
+impl<'a> AccPostingSrc<'a> for MatchedPostings<'a> {
+    fn acc_name(&self) -> &AccName {
+        &self.acc_name
+    }
+
+    fn postings(&self) -> Box<dyn Iterator<Item = &'a Posting> + '_> {
         Box::new(self.postings.iter().copied())
     }
 }
@@ -59,7 +141,7 @@ impl<'l> Ledger<'l> {
                     name,
                     postings
                         .iter()
-                        .filter(|&e| between.check(e.date))
+                        .filter(|&e| between.check(e.posting.date))
                         .copied()
                         .collect(),
                 )
@@ -69,6 +151,121 @@ impl<'l> Ledger<'l> {
         Ledger { acc_posting: acc }
     }
 
+    /// Returns a new [`Ledger`] keeping only the postings for which
+    /// `predicate` returns `true`. Accounts left without postings are
+    /// dropped, so reports run on the result never see them.
+    ///
+    /// ```
+    /// use ledger::journal::{Journal, JrnIO};
+    /// use ledger::ledger::Ledger;
+    /// use rust_decimal::Decimal;
+    ///
+    /// let jf = "2026-01-01 test\n  A    $10\n  B   $-10\n";
+    /// let journal = Journal::new(JrnIO::Reader(Box::new(jf.as_bytes()))).unwrap();
+    /// let ledger = Ledger::from_journal(&journal);
+    ///
+    /// let positive = ledger.filter_postings(|ctx| ctx.posting.quantity.q > Decimal::ZERO);
+    /// assert!(positive.get_acc_postings(&"A".into()).is_some());
+    /// assert!(positive.get_acc_postings(&"B".into()).is_none());
+    /// ```
+    pub fn filter_postings(&self, predicate: impl Fn(&PostingCtx) -> bool) -> Self {
+        let acc = self
+            .acc_posting
+            .iter()
+            .filter_map(|(&name, postings)| {
+                let postings: Vec<_> = postings.iter().filter(|&e| predicate(e)).copied().collect();
+                (!postings.is_empty()).then_some((name, postings))
+            })
+            .collect();
+
+        Ledger { acc_posting: acc }
+    }
+
+    /// Checks the balance assertions of every posting. Postings are
+    /// applied in date order, keeping a running balance per account
+    /// and commodity; an assertion holds when the balance in its
+    /// commodity equals the asserted amount right after its posting.
+    /// Mismatches are returned sorted by date.
+    pub fn verify_assertions(&self) -> Vec<AssertionMismatch<'l>> {
+        let mut res = Vec::new();
+        for (&name, postings) in &self.acc_posting {
+            let mut running = HashMap::new();
+            for ctx in postings {
+                let p = ctx.posting;
+                *running.entry(p.quantity.s).or_insert(Decimal::ZERO) += p.quantity.q;
+
+                let Some(expected) = p.assertion else {
+                    continue;
+                };
+                let actual = Quantity {
+                    q: running.get(&expected.s).copied().unwrap_or_default(),
+                    s: expected.s,
+                };
+                if actual.q != expected.q {
+                    res.push(AssertionMismatch {
+                        xact: ctx.xact,
+                        acc_name: name,
+                        expected,
+                        actual,
+                    });
+                }
+            }
+        }
+        res.sort_by_key(|m| (m.xact.date.date(), m.xact.id));
+        res
+    }
+
+    /// The cumulative balance of the postings matching `qry` after each
+    /// transaction that has one, in date order: one point per
+    /// transaction, e.g. to chart net worth.
+    pub fn running_balance(&self, qry: &Query) -> Vec<(NaiveDate, Amount)> {
+        self.running_value(qry, Valuation::Quantity, &PriceDB::new())
+    }
+
+    /// Like [`Ledger::running_balance`], but each point is valued as
+    /// `valuation` says. Under [`Valuation::Market`] the holdings at
+    /// each point are priced as of its date, so the series also moves
+    /// with prices between transactions recorded in the journal.
+    pub fn running_value(
+        &self,
+        qry: &Query,
+        valuation: Valuation,
+        price_db: &PriceDB,
+    ) -> Vec<(NaiveDate, Amount)> {
+        let mut postings: Vec<&PostingCtx> = self
+            .acc_posting
+            .values()
+            .flatten()
+            .filter(|ctx| qry.eval(ctx.xact, ctx.posting))
+            .collect();
+        postings.sort_by_key(|ctx| (ctx.xact.date.date(), ctx.xact.id));
+
+        let mut qty = Amount::new();
+        let mut value = Amount::new();
+        postings
+            .chunk_by(|a, b| a.xact.id == b.xact.id)
+            .map(|chunk| {
+                let date = chunk[0].xact.date.date();
+                for ctx in chunk {
+                    let p = ctx.posting;
+                    qty += p.quantity;
+                    value += match valuation {
+                        Valuation::Quantity | Valuation::Market => p.quantity,
+                        Valuation::Basis => p.book_value(),
+                        Valuation::Historical => match p.lot_date {
+                            Some(at) => p.historical_value(at, price_db),
+                            None => p.book_value(),
+                        },
+                    };
+                }
+                match valuation {
+                    Valuation::Market => (date, price_db.value_or_quantity(date, qty.clone())),
+                    _ => (date, value.clone()),
+                }
+            })
+            .collect()
+    }
+
     /// Returns an immutable reference to a ledger entry of an account
     /// by name.
     pub fn get_acc_postings<'a>(&'a self, name: &AccName) -> Option<impl AccPostingSrc<'a>> {
@@ -86,9 +283,33 @@ impl<'l> Ledger<'l> {
         })
     }
 
+    /// Like [`Ledger::get_all_posting`], but keeping only the postings
+    /// that match `query`. Accounts left without postings are skipped.
+    pub fn get_matching_postings<'a>(
+        &'a self,
+        query: &Query,
+    ) -> impl Iterator<Item = impl AccPostingSrc<'a> + use<'a, 'l>> + use<'a, 'l> {
+        let matched: Vec<_> = self
+            .acc_posting
+            .iter()
+            .filter_map(|(&acc_name, ps)| {
+                let postings: Vec<_> = ps
+                    .iter()
+                    .filter(|ctx| query.eval(ctx.xact, ctx.posting))
+                    .map(|ctx| ctx.posting)
+                    .collect();
+                (!postings.is_empty()).then(|| MatchedPostings {
+                    acc_name: acc_name.clone(),
+                    postings,
+                })
+            })
+            .collect();
+        matched.into_iter()
+    }
+
     /// Returns a mutable reference to a ledger entry of an account
     /// by name.
-    fn get_entry_mut(&mut self, name: &'l AccName) -> &mut Vec<&'l Posting> {
+    fn get_entry_mut(&mut self, name: &'l AccName) -> &mut Vec<PostingCtx<'l>> {
         self.acc_posting.entry(name).or_default()
     }
 
@@ -103,7 +324,7 @@ impl<'l> Ledger<'l> {
             for p in &xact.postings {
                 let acc = self.get_entry_mut(&p.acc_name);
                 // TODO: optimize this: try to determine needed space
-                acc.push(p);
+                acc.push(PostingCtx { xact, posting: p });
             }
         }
         self
@@ -114,9 +335,11 @@ impl<'l> Ledger<'l> {
 mod tests {
     use super::*;
     use crate::account::AccPostingSrc;
+    use crate::amount;
     use crate::journal::JrnIO;
     use crate::util;
     use chrono::NaiveDate;
+    use rust_decimal::dec;
     use std::io::Cursor;
 
     fn d(y: i32, m: u32, day: u32) -> NaiveDate {
@@ -246,6 +469,66 @@ mod tests {
         assert_eq!(postings.len(), 2);
     }
 
+    #[test]
+    fn filter_postings_above_threshold_then_balance() {
+        use crate::balance::{Balance, Valuation};
+        use crate::holdings::Holdings;
+        use crate::ntypes::Valuable;
+        use crate::pricedb::PriceDB;
+        use crate::query::Query;
+
+        let input = "\
+2026-01-01 small
+  Expenses:Food    $5
+  Assets:Cash
+
+2026-01-02 big
+  Expenses:Food    $50
+  Assets:Cash
+
+2026-01-03 other
+  Expenses:Rent    $2
+  Assets:Cash
+";
+        let journal = make_journal(input);
+        let ledger = Ledger::from_journal(&journal);
+
+        let filtered = ledger.filter_postings(|ctx| ctx.posting.quantity.q > dec!(10));
+
+        // Rent only had a posting below the threshold, so it is gone.
+        let rent = AccName::from("Expenses:Rent");
+        assert!(filtered.get_acc_postings(&rent).is_none());
+
+        let price_db = PriceDB::from_journal(&journal);
+        let bal = Balance::from_ledger(&filtered, &Query::All);
+        assert!(bal.account(&AccName::from("Assets:Cash")).is_none());
+
+        let total: Holdings = bal.balance(&price_db);
+        assert_eq!(total.valued_in(Valuation::Quantity), amount!(50, "$"));
+    }
+
+    #[test]
+    fn filter_postings_exposes_the_owning_xact() {
+        let input = "\
+2026-01-01 keep
+  A    $10
+  B   $-10
+
+2026-01-02 drop
+  A    $20
+  B   $-20
+";
+        let journal = make_journal(input);
+        let ledger = Ledger::from_journal(&journal);
+
+        let filtered = ledger.filter_postings(|ctx| ctx.xact.payee == "keep");
+        let acc_a = AccName::from("A");
+        let ps = filtered.get_acc_postings(&acc_a).unwrap();
+        let postings: Vec<_> = ps.postings().collect();
+        assert_eq!(postings.len(), 1);
+        assert_eq!(postings[0].date, d(2026, 1, 1));
+    }
+
     #[test]
     fn fill_from_xacts_multiple_transactions_same_account() {
         let input = "\
@@ -266,4 +549,103 @@ mod tests {
         // Both postings to account A should be present
         assert_eq!(postings.len(), 2);
     }
+
+    #[test]
+    fn verify_assertions_reports_mismatches() {
+        let input = "\
+2026-01-01 opening
+  Assets:Checking    $1,050.00
+  Equity:Opening
+
+2026-01-02 groceries
+  Assets:Checking    $-50.00 = $1,000.00
+  Expenses:Food
+
+2026-01-03 rent
+  Assets:Checking    $-500.00 = $400.00
+  Expenses:Rent
+";
+        let journal = make_journal(input);
+        let ledger = Ledger::from_journal(&journal);
+
+        let mismatches = ledger.verify_assertions();
+        assert_eq!(mismatches.len(), 1);
+
+        let m = &mismatches[0];
+        assert_eq!(m.xact.payee, "rent");
+        assert_eq!(m.acc_name, &AccName::from("Assets:Checking"));
+        assert_eq!(m.expected.q, Decimal::new(400, 0));
+        assert_eq!(m.actual.q, Decimal::new(500, 0));
+    }
+
+    #[test]
+    fn running_balance_after_each_touching_xact() {
+        let input = "\
+2026-01-01 salary
+  Assets:Cash       $100
+  Income:Salary
+
+2026-01-05 coffee
+  Expenses:Food     $5
+  Assets:Cash
+
+2026-01-10 rent
+  Expenses:Rent     $50
+  Assets:Bank:Checking
+
+2026-01-15 deposit
+  Assets:Bank:Checking    $60
+  Assets:Cash
+";
+        let journal = make_journal(input);
+        let ledger = Ledger::from_journal(&journal);
+
+        let series = ledger.running_balance(&Query::parse(["^Assets"]).unwrap());
+        assert_eq!(
+            series,
+            vec![
+                (d(2026, 1, 1), amount!(100, "$")),
+                (d(2026, 1, 5), amount!(95, "$")),
+                (d(2026, 1, 10), amount!(45, "$")),
+                (d(2026, 1, 15), amount!(45, "$")),
+            ]
+        );
+
+        let food = ledger.running_balance(&Query::parse(["Food"]).unwrap());
+        assert_eq!(food, vec![(d(2026, 1, 5), amount!(5, "$"))]);
+    }
+
+    #[test]
+    fn running_value_at_market_prices() {
+        let input = "\
+2026-01-01 buy
+  Assets:Broker     10 AAPL @ $10.00
+  Assets:Cash
+
+P 2026-01-20 AAPL $12.00
+
+2026-02-01 buy more
+  Assets:Broker     10 AAPL @ $15.00
+  Assets:Cash
+";
+        let (journal, price_db) = util::read_journal_and_price_db(
+            JrnIO::Reader(Box::new(Cursor::new(input.as_bytes().to_vec()))),
+            None,
+        )
+        .unwrap();
+        let ledger = Ledger::from_journal(&journal);
+        let broker = Query::parse(["Broker"]).unwrap();
+
+        let market = ledger.running_value(&broker, Valuation::Market, &price_db);
+        assert_eq!(
+            market,
+            vec![
+                (d(2026, 1, 1), amount!(100, "$")),
+                (d(2026, 2, 1), amount!(300, "$")),
+            ]
+        );
+
+        let basis = ledger.running_value(&broker, Valuation::Basis, &price_db);
+        assert_eq!(basis[1], (d(2026, 2, 1), amount!(250, "$")));
+    }
 }
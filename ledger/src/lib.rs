@@ -3,11 +3,17 @@ pub mod account_view;
 pub mod amount;
 pub mod balance;
 pub mod balance_view;
+pub mod budget;
+pub mod check;
+pub mod equity;
+pub mod gains;
 pub mod holdings;
 pub mod info;
 pub mod iter;
 pub mod journal;
 pub mod ledger;
+pub mod limit;
+pub mod lots;
 pub mod macros;
 pub mod misc;
 pub mod ntypes;
@@ -15,6 +21,7 @@ pub mod parser_number;
 pub mod pricedb;
 pub mod printing;
 pub mod quantity;
+pub mod query;
 pub mod register;
 pub mod symbol;
 pub mod tags;
@@ -0,0 +1,144 @@
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use rust_decimal::Decimal;
+
+use crate::quantity::Quantity;
+use crate::symbol::Symbol;
+
+/// An amount predicate, as given to `--limit`.
+///
+/// The grammar is deliberately tiny:
+///
+/// ```text
+/// limit := "amount" op number [commodity]
+/// op    := ">" | "<" | ">=" | "<=" | "=="
+/// ```
+///
+/// `number` is a plain decimal literal (`100`, `-2.5`). Spaces are
+/// optional (`amount>=100$`). When a commodity is given, quantities in
+/// any other commodity never match.
+///
+/// ```
+/// use ledger::limit::Limit;
+/// use ledger::quantity;
+/// use rust_decimal::dec;
+///
+/// let limit: Limit = "amount >= 100 $".parse().unwrap();
+/// assert!(limit.matches(&quantity!(100, "$")));
+/// assert!(!limit.matches(&quantity!(99, "$")));
+/// assert!(!limit.matches(&quantity!(500, "EUR")));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limit {
+    op: Op,
+    value: Decimal,
+    symbol: Option<Symbol>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Gt,
+    Lt,
+    Ge,
+    Le,
+    Eq,
+}
+
+impl Limit {
+    /// Whether `q` satisfies the predicate.
+    pub fn matches(&self, q: &Quantity) -> bool {
+        if self.symbol.is_some_and(|s| s != q.s) {
+            return false;
+        }
+        let ord = q.q.cmp(&self.value);
+        match self.op {
+            Op::Gt => ord == Ordering::Greater,
+            Op::Lt => ord == Ordering::Less,
+            Op::Ge => ord != Ordering::Less,
+            Op::Le => ord != Ordering::Greater,
+            Op::Eq => ord == Ordering::Equal,
+        }
+    }
+}
+
+impl FromStr for Limit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rest = s
+            .trim()
+            .strip_prefix("amount")
+            .ok_or_else(|| format!("expected `amount <op> <number>`, got '{s}'"))?
+            .trim_start();
+
+        // two-char operators first so `>=` isn't read as `>`
+        let (op, rest) = [
+            (">=", Op::Ge),
+            ("<=", Op::Le),
+            ("==", Op::Eq),
+            (">", Op::Gt),
+            ("<", Op::Lt),
+        ]
+        .into_iter()
+        .find_map(|(tok, op)| rest.strip_prefix(tok).map(|r| (op, r.trim())))
+        .ok_or_else(|| format!("expected one of >, <, >=, <=, == in '{s}'"))?;
+
+        let end = rest
+            .find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | '-' | '+')))
+            .unwrap_or(rest.len());
+        let (number, commodity) = rest.split_at(end);
+        let value =
+            Decimal::from_str(number).map_err(|_| format!("invalid number '{number}' in '{s}'"))?;
+        let commodity = commodity.trim();
+
+        Ok(Limit {
+            op,
+            value,
+            symbol: (!commodity.is_empty()).then(|| Symbol::new(commodity)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::quantity;
+
+    fn limit(s: &str) -> Limit {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn operators_compare_against_the_literal() {
+        let q = quantity!(100, "$");
+        assert!(!limit("amount > 100").matches(&q));
+        assert!(limit("amount >= 100").matches(&q));
+        assert!(limit("amount == 100").matches(&q));
+        assert!(limit("amount <= 100").matches(&q));
+        assert!(!limit("amount < 100").matches(&q));
+        assert!(limit("amount<100.5").matches(&q));
+        assert!(limit("amount > -1").matches(&q));
+    }
+
+    #[test]
+    fn commodity_scopes_the_predicate() {
+        let l = limit("amount > 10 EUR");
+        assert!(l.matches(&quantity!(20, "EUR")));
+        assert!(!l.matches(&quantity!(20, "$")));
+
+        let l = limit("amount>=100$");
+        assert!(l.matches(&quantity!(100, "$")));
+        assert!(!l.matches(&quantity!(100, "EUR")));
+    }
+
+    #[test]
+    fn invalid_expressions_are_rejected() {
+        assert!("price > 10".parse::<Limit>().is_err());
+        assert!("amount ~ 10".parse::<Limit>().is_err());
+        assert!("amount > ten".parse::<Limit>().is_err());
+        assert!("amount >".parse::<Limit>().is_err());
+    }
+}
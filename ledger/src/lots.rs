@@ -0,0 +1,230 @@
+use chrono::NaiveDate;
+
+use crate::{
+    journal::{AccName, Posting, Xact},
+    misc,
+    pricedb::PriceDB,
+    quantity::Quantity,
+    query::Query,
+};
+
+/// An open lot: the units of a commodity held in an account that were
+/// acquired at the same cost.
+///
+/// Postings are grouped into lots by account, commodity, lot price and
+/// lot date. A sale that names the lot price (e.g. `-5 AAPL {$100} @
+/// $120`) reduces that lot; any other sale closes the open lots of the
+/// account first in, first out, keeping to its lot date if it has one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lot {
+    /// Account holding the lot.
+    pub acc_name: AccName,
+    /// Units held.
+    pub qty: Quantity,
+    /// Unit cost basis (`Posting::lot_uprice`).
+    pub cost: Quantity,
+    /// Lot date, when the postings carry one (`[2026-01-01]`).
+    pub date: Option<NaiveDate>,
+    /// Unit market price as of the report date, or `None` when the
+    /// commodity has no price in the cost commodity.
+    pub market: Option<Quantity>,
+}
+
+impl Lot {
+    /// Unrealized gain of the lot, in the cost commodity: `(market -
+    /// cost) * qty`, rounded to `dp` places. `None` when the lot has
+    /// no market price.
+    pub fn gain(&self, dp: u32) -> Option<Quantity> {
+        let market = self.market?;
+        let q = (market.q - self.cost.q) * self.qty.q;
+        Some(Quantity {
+            q: q.round_dp(dp),
+            s: self.cost.s,
+        })
+    }
+}
+
+/// Collects the open lots of the postings matching `query`, valued at
+/// the market prices in effect on `at`.
+///
+/// Postings without a cost in another commodity (e.g. plain `$100`)
+/// are not lots and are skipped, as are lots whose units add up to
+/// zero. Postings are applied in date order; a sale for more than the
+/// account holds leaves the rest as a negative lot at the sale price.
+/// Lots are sorted by account name.
+pub fn lots<'a>(
+    xacts: impl Iterator<Item = &'a Xact>,
+    query: &Query,
+    at: NaiveDate,
+    price_db: &PriceDB,
+) -> Vec<Lot> {
+    let mut postings: Vec<&Posting> = xacts
+        .flat_map(|x| x.postings.iter().map(move |p| (x, p)))
+        .filter(|(x, p)| p.lot_uprice.price.s != p.quantity.s && p.date <= at && query.eval(x, p))
+        .map(|(_, p)| p)
+        .collect();
+    // stable: postings of the same day keep their file order
+    postings.sort_by_key(|p| p.date);
+
+    // in acquisition order, for first in, first out
+    let mut open: Vec<(NaiveDate, Lot)> = Vec::new();
+    for p in postings {
+        let mut qty = p.quantity;
+        if qty.q.is_sign_negative() && !p.names_lot_price() {
+            let mut left = -qty.q;
+            let held = open.iter_mut().map(|(_, l)| l).filter(|l| {
+                l.acc_name == p.acc_name
+                    && l.qty.s == qty.s
+                    && l.qty.q.is_sign_positive()
+                    && p.lot_date.is_none_or(|d| l.date == Some(d))
+            });
+            for lot in held {
+                let take = left.min(lot.qty.q);
+                lot.qty.q -= take;
+                left -= take;
+            }
+            if left.is_zero() {
+                continue;
+            }
+            qty.q = -left;
+        }
+
+        let cost = p.lot_uprice.price;
+        let same = |l: &Lot| {
+            l.acc_name == p.acc_name && l.qty.s == qty.s && l.cost == cost && l.date == p.lot_date
+        };
+        match open.iter_mut().find(|(_, l)| same(l)) {
+            Some((_, lot)) => lot.qty.q += qty.q,
+            None => {
+                let acquired = p.lot_date.unwrap_or(p.date);
+                let lot = Lot {
+                    acc_name: p.acc_name.clone(),
+                    qty,
+                    cost,
+                    date: p.lot_date,
+                    market: None,
+                };
+                let i = open.partition_point(|(d, _)| *d <= acquired);
+                open.insert(i, (acquired, lot));
+            }
+        }
+    }
+
+    let mut lots: Vec<Lot> = open
+        .into_iter()
+        .map(|(_, lot)| lot)
+        .filter(|lot| !lot.qty.q.is_zero())
+        .map(|lot| Lot {
+            market: price_db
+                .uprice_as_of(lot.qty.s, misc::to_datetime(at))
+                .filter(|m| m.s == lot.cost.s),
+            ..lot
+        })
+        .collect();
+    lots.sort_by(|a, b| {
+        (&a.acc_name, a.qty.s, a.cost.s, a.cost.q, a.date).cmp(&(
+            &b.acc_name,
+            b.qty.s,
+            b.cost.s,
+            b.cost.q,
+            b.date,
+        ))
+    });
+    lots
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::journal::JrnIO;
+    use crate::quantity;
+    use crate::util;
+
+    fn d(y: i32, m: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, day).unwrap()
+    }
+
+    fn make_lots(input: &str, at: NaiveDate) -> Vec<Lot> {
+        let bytes = input.to_owned().into_bytes();
+        let (journal, price_db) =
+            util::read_journal_and_price_db(JrnIO::Reader(Box::new(Cursor::new(bytes))), None)
+                .unwrap();
+        lots(journal.xacts(), &Query::All, at, &price_db)
+    }
+
+    #[test]
+    fn lots_are_split_by_cost_and_valued_at_market() {
+        let input = "\
+2026-01-01 buy
+  Assets:Broker    10 AAPL @ $100
+  Assets:Cash
+
+2026-02-01 buy
+  Assets:Broker    5 AAPL @ $120
+  Assets:Cash
+
+P 2026-03-01 AAPL $150
+";
+        let lots = make_lots(input, d(2026, 3, 1));
+        assert_eq!(lots.len(), 2);
+
+        let gains: Vec<_> = lots
+            .iter()
+            .map(|l| l.gain(misc::DEFAULT_VALUATION_PRECISION).unwrap())
+            .collect();
+        assert!(gains.contains(&quantity!(500, "$")));
+        assert!(gains.contains(&quantity!(150, "$")));
+    }
+
+    #[test]
+    fn sale_at_lot_cost_closes_the_lot() {
+        let input = "\
+2026-01-01 buy
+  Assets:Broker    5 AAPL @ $100
+  Assets:Cash
+
+2026-02-01 sell
+  Assets:Broker    -5 AAPL {$100} @ $120
+  Assets:Cash      $600
+  Income:Gains     $-100
+";
+        assert!(make_lots(input, d(2026, 3, 1)).is_empty());
+    }
+
+    #[test]
+    fn plain_sale_closes_the_oldest_lots_first() {
+        let input = "\
+2026-01-01 buy
+  Assets:Broker    10 AAPL @ $100
+  Assets:Cash
+
+2026-02-01 buy
+  Assets:Broker    5 AAPL @ $120
+  Assets:Cash
+
+2026-03-01 sell
+  Assets:Broker    -12 AAPL @ $130
+  Assets:Cash
+";
+        let lots = make_lots(input, d(2026, 4, 1));
+        assert_eq!(lots.len(), 1);
+        assert_eq!(lots[0].qty, quantity!(3, "AAPL"));
+        assert_eq!(lots[0].cost, quantity!(120, "$"));
+    }
+
+    #[test]
+    fn lot_without_market_price_has_no_gain() {
+        let lot = Lot {
+            acc_name: AccName::from("Assets:Broker"),
+            qty: quantity!(10, "XYZ"),
+            cost: quantity!(5, "$"),
+            date: None,
+            market: None,
+        };
+        assert_eq!(lot.gain(misc::DEFAULT_VALUATION_PRECISION), None);
+    }
+}
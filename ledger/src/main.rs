@@ -1,3 +1,4 @@
+use std::collections::BTreeSet;
 use std::fs::File;
 use std::io::{self, BufRead, BufReader};
 
@@ -8,17 +9,47 @@ use regex::Regex;
 
 use ledger::{
     balance::{Balance, Valuation},
+    balance_view::AccountOrder,
+    budget, check, equity,
+    gains::{self, LotStrategy},
     holdings::Holdings,
     info,
     iter::take_headtail,
-    journal::{self, Journal, JournalError, JrnIO, Xact},
-    ledger::Ledger,
+    journal::{self, Journal, JournalError, JrnIO, ParseOptions, StateFilter, Xact},
+    ledger::{Ledger, PostingFilter},
+    limit::Limit,
+    lots,
     misc::{self, Step},
-    printing, register, util,
+    parser_number::NumberFormat,
+    pricedb::PriceDB,
+    printing::{self, ColorChoice},
+    query::Query,
+    register,
+    symbol::Symbol,
+    util,
 };
 
 fn main() {
     let cli = Cli::parse();
+    let now = cli.now.unwrap_or_else(misc::today);
+    let parse_opts = ParseOptions {
+        effective: cli.effective,
+        now,
+        ..Default::default()
+    };
+    let checks = PostReadChecks {
+        warn_zero: cli.warn_zero,
+        dupes: cli.check_dupes,
+        assertions: cli.check_assertions,
+    };
+    let print_opts = printing::Options {
+        color: cli.color,
+        parens: cli.parens,
+        date_format: cli.date_format.clone(),
+        lisp_pretty: cli.lisp_pretty,
+        valuation_precision: cli.valuation_precision,
+        ..Default::default()
+    };
 
     match cli.command {
         Commands::Balance(args) => {
@@ -26,22 +57,91 @@ fn main() {
                 eprintln!("error: {msg}");
                 std::process::exit(2);
             }
+            let query = report_query(&args.report_query);
             let jrnio = path_or_stdin(cli.journal_path);
-            let price_db = open_price_db(&args.price_db_path);
-            match util::read_journal_and_price_db(jrnio, price_db) {
-                Ok((journal, price_db)) => {
+            let price_db = open_price_db(&args.prices.price_db_path);
+            match util::read_journal(jrnio, price_db, cli.strict, cli.number_format, parse_opts) {
+                Ok((journal, mut price_db)) => {
+                    checks.run(&journal);
+                    let opts = report_options(&print_opts, &journal);
+                    price_db.set_price_expiry(args.prices.price_exp);
+                    price_db.set_valuation_precision(cli.valuation_precision);
+                    if args.lots {
+                        let at = args.period.at.first().copied().unwrap_or(now);
+                        let xacts = filtered_xacts(&journal, &args.filter, &query);
+                        let lots = lots::lots(xacts, &query, at, &price_db);
+                        if let Err(err) = printing::lots(io::stdout(), &lots, cli.fmt.into(), &opts)
+                        {
+                            eprintln!("fail printing the report: {err}");
+                            std::process::exit(1);
+                        };
+                        return;
+                    }
+
                     let vtype = args.valuation.get();
-                    let ledger = Ledger::from_xacts(filtered_xacts(&journal, &args.filter, &[]));
+                    let ledger = Ledger::from_xacts(filtered_xacts(&journal, &args.filter, &query));
+                    let postings = PostingFilter {
+                        state: args.state.get(),
+                        limit: args.amount.limit.as_ref(),
+                        real: args.real,
+                        actual: args.actual,
+                        commodity: args.amount.commodity.as_deref().map(Symbol::new),
+                    };
+                    let ledger = if postings.is_empty() {
+                        ledger
+                    } else {
+                        ledger.filter_postings(|ctx| postings.matches(ctx))
+                    };
 
-                    let bal = Balance::from_ledger(&ledger, &args.report_query);
-                    let mut bal =
-                        bal.to_balance_view_at_dates::<Holdings>(&price_db, args.period.at_dates());
+                    let bal = Balance::from_ledger(&ledger, &query);
+                    let change = args.period.change || args.period.since.is_some();
+                    let dates: Box<dyn Iterator<Item = NaiveDate>> = if change {
+                        // the day before `since`/`begin` is the opening
+                        // point the first column is compared against
+                        let opening = args
+                            .period
+                            .since
+                            .or_else(|| change_begin(&args.filter, &journal))
+                            .and_then(|d| d.pred_opt());
+                        Box::new(opening.into_iter().chain(args.period.at_dates(now)))
+                    } else {
+                        args.period.at_dates(now)
+                    };
+                    let mut bal = bal.to_balance_view_at_dates::<Holdings>(&price_db, dates);
+
+                    if let Some(target) = &args.exchange {
+                        let target = Symbol::new(target);
+                        let mut flat = bal.to_flat();
+                        if let Err(missing) = flat.exchange(target, vtype, &price_db) {
+                            exit_no_exchange_price(&missing);
+                        }
+                        bal = flat.to_hier();
+                    }
+
+                    if change {
+                        let mut flat = bal.to_flat();
+                        flat.to_changes();
+                        bal = flat.to_hier();
+                    }
+
+                    if let Some(re) = &args.display.flip_sign {
+                        let mut flat = bal.to_flat();
+                        flat.flip_sign(re);
+                        bal = flat.to_hier();
+                    }
+
+                    if args.display.invert {
+                        let mut flat = bal.to_flat();
+                        flat.invert();
+                        bal = flat.to_hier();
+                    }
 
                     if !args.display.empty {
                         bal.remove_zero_accounts();
                     };
 
                     bal = bal.limit_accounts_depth(args.display.depth());
+                    bal.set_order(args.display.sort.order(vtype));
 
                     let total_mode = match (args.display.no_total, args.display.only_total) {
                         (true, _) => printing::TotalMode::NoTotal,
@@ -55,9 +155,13 @@ fn main() {
                             &bal.to_flat(),
                             total_mode,
                             args.annotate.map(|p| p.into()),
-                            args.display.date_header,
+                            printing::BalanceLayout {
+                                date_header: args.display.date_header,
+                                percent: args.display.percent,
+                            },
                             vtype,
                             cli.fmt.into(),
+                            &opts,
                         )
                     } else {
                         printing::bal(
@@ -65,9 +169,13 @@ fn main() {
                             &bal.to_compact(),
                             total_mode,
                             args.annotate.map(|p| p.into()),
-                            args.display.date_header,
+                            printing::BalanceLayout {
+                                date_header: args.display.date_header,
+                                percent: args.display.percent,
+                            },
                             vtype,
                             cli.fmt.into(),
+                            &opts,
                         )
                     };
 
@@ -75,74 +183,135 @@ fn main() {
                         eprintln!("fail printing the report: {err}");
                         std::process::exit(1);
                     };
+                    warn_missing_prices(&price_db);
 
                     if args.warn_future && args.period.at.is_empty() {
-                        let today = misc::today();
-                        let has_future = journal.xacts().any(|x| x.date.txdate > today);
+                        let has_future = journal.xacts().any(|x| x.date.date() > now);
                         if has_future {
                             eprintln!("warning: there are transactions dated after today");
                         }
                     }
                 }
                 Err(err) => {
-                    eprintln!("fail reading journal or price db: {err:?}");
+                    eprintln!("fail reading journal or price db: {err}");
                     std::process::exit(1);
                 }
             }
         }
         Commands::Register(args) => {
+            let query = report_query(&args.report_query);
             let jrnio = path_or_stdin(cli.journal_path);
-            let price_db = open_price_db(&args.price_db_path);
-            match util::read_journal_and_price_db(jrnio, price_db) {
-                Ok((journal, price_db)) => {
+            let price_db = open_price_db(&args.prices.price_db_path);
+            match util::read_journal(jrnio, price_db, cli.strict, cli.number_format, parse_opts) {
+                Ok((journal, mut price_db)) => {
+                    checks.run(&journal);
+                    let opts = report_options(&print_opts, &journal);
+                    price_db.set_price_expiry(args.prices.price_exp);
+                    price_db.set_valuation_precision(cli.valuation_precision);
                     let vtype = args.valuation.get();
-                    let xacts = filtered_xacts(&journal, &args.filter, &args.report_query);
-                    let query = if args.display.related {
-                        &[]
-                    } else {
-                        args.report_query.as_slice()
+                    let xacts = filtered_xacts(&journal, &args.filter, &query);
+                    let filter = register::RowFilter {
+                        terms: &query,
+                        related: args.display.related,
+                        postings: PostingFilter {
+                            state: args.state.get(),
+                            limit: args.amount.limit.as_ref(),
+                            real: args.real,
+                            actual: args.actual,
+                            commodity: args.amount.commodity.as_deref().map(Symbol::new),
+                        },
                     };
                     let reg = register::register(
                         xacts,
-                        query,
+                        filter,
                         args.filter.end,
+                        now,
                         vtype,
                         args.display.depth(),
+                        args.display.sort_within_day.into(),
                         &price_db,
                     );
 
-                    let reg = take_headtail(reg, args.display.head, args.display.tail);
-                    let reg = if args.display.reverse {
+                    let reg: Box<dyn Iterator<Item = _>> = match &args.exchange {
+                        Some(target) => {
+                            match register::exchange(reg, Symbol::new(target), &price_db) {
+                                Ok(groups) => Box::new(groups.into_iter()),
+                                Err(missing) => exit_no_exchange_price(&missing),
+                            }
+                        }
+                        None => Box::new(reg),
+                    };
+                    let reg: Box<dyn Iterator<Item = _>> = if args.display.collapse {
+                        Box::new(register::collapse(reg))
+                    } else {
+                        Box::new(reg)
+                    };
+                    let reg: Box<dyn Iterator<Item = _>> = match args.period.step() {
+                        Some(step) => Box::new(register::by_period(reg, step)),
+                        None => reg,
+                    };
+                    let reg: Box<dyn Iterator<Item = _>> = match args.display.sort {
+                        RegisterSort::Date => reg,
+                        RegisterSort::Amount => Box::new(register::sort_by_amount(reg, true)),
+                        RegisterSort::AmountAsc => Box::new(register::sort_by_amount(reg, false)),
+                    };
+                    let reg: Box<dyn Iterator<Item = _>> = if args.display.average {
+                        Box::new(register::with_average(reg, price_db.valuation_precision()))
+                    } else {
+                        reg
+                    };
+                    let reg: Box<dyn Iterator<Item = _>> = if args.display.invert {
+                        Box::new(register::invert(reg))
+                    } else {
+                        reg
+                    };
+                    // running totals are computed forward in time, the
+                    // rows are reversed afterwards
+                    let reg: Box<dyn Iterator<Item = _>> = if args.display.reverse {
                         let mut v: Vec<_> = reg.collect();
                         v.reverse();
                         Box::new(v.into_iter())
                     } else {
                         reg
                     };
-                    if let Err(err) = printing::reg(io::stdout(), reg, cli.fmt.into()) {
+                    let reg = take_headtail(reg, args.display.head, args.display.tail);
+                    if let Err(err) = printing::reg(
+                        io::stdout(),
+                        reg,
+                        cli.fmt.into(),
+                        printing::RegisterLayout {
+                            by_xact: args.display.by_xact,
+                            reverse: args.display.reverse,
+                        },
+                        &opts,
+                    ) {
                         eprintln!("fail printing the report: {err}");
                         std::process::exit(1);
                     };
+                    warn_missing_prices(&price_db);
                 }
                 Err(err) => {
-                    eprintln!("fail reading journal or price db: {err:?}");
+                    eprintln!("fail reading journal or price db: {err}");
                     std::process::exit(1);
                 }
             }
         }
         Commands::Print(args) => {
+            let query = report_query(&args.report_query);
             let jrnio = path_or_stdin(cli.journal_path);
-            match util::read_journal_and_price_db(jrnio, None) {
+            match util::read_journal(jrnio, None, cli.strict, cli.number_format, parse_opts) {
                 Ok((journal, _)) => {
-                    let it = filtered_xacts(&journal, &args.filter, &args.report_query);
+                    checks.run(&journal);
+                    let opts = report_options(&print_opts, &journal);
+                    let it = filtered_xacts(&journal, &args.filter, &query);
                     let it = take_headtail(it, args.display.head, args.display.tail);
-                    if let Err(err) = printing::prnt(io::stdout(), it, cli.fmt.into()) {
+                    if let Err(err) = printing::prnt(io::stdout(), it, cli.fmt.into(), &opts) {
                         eprintln!("fail printing the report: {err}");
                         std::process::exit(1);
                     };
                 }
                 Err(err) => {
-                    eprintln!("fail reading journal or price db: {err:?}");
+                    eprintln!("fail reading journal or price db: {err}");
                     std::process::exit(1);
                 }
             }
@@ -156,7 +325,7 @@ fn main() {
             let xacts = match addx_decode(cli.fmt.into(), &input) {
                 Ok(xacts) => xacts,
                 Err(err) => {
-                    eprintln!("error: {err:?}");
+                    eprintln!("error: {err}");
                     std::process::exit(1);
                 }
             };
@@ -164,35 +333,228 @@ fn main() {
                 return;
             }
 
-            let Some(path) = cli.journal_path else {
-                eprintln!("error: addx requires -f/--file (the journal file to append to)");
+            let [path] = cli.journal_path.as_slice() else {
+                eprintln!(
+                    "error: addx requires a single -f/--file (the journal file to append to)"
+                );
                 std::process::exit(2);
             };
-            let mut journal = match Journal::new(JrnIO::Path(path)) {
+            let mut journal = match Journal::new(JrnIO::Path(path.clone())) {
                 Ok(j) => j,
                 Err(err) => {
-                    eprintln!("error opening journal: {err:?}");
+                    eprintln!("error opening journal: {err}");
                     std::process::exit(1);
                 }
             };
             if let Err(err) = journal.xact_append(xacts) {
-                eprintln!("error writing to journal: {err:?}");
+                eprintln!("error writing to journal: {err}");
                 std::process::exit(1);
             }
         }
         Commands::Info(args) => {
+            let query = report_query(&args.report_query);
             let jrnio = path_or_stdin(cli.journal_path);
-            match util::read_journal_and_price_db(jrnio, None) {
+            match util::read_journal(jrnio, None, cli.strict, cli.number_format, parse_opts) {
                 Ok((journal, _price_db)) => {
-                    let xacts = filtered_xacts(&journal, &args.filter, &args.report_query);
+                    checks.run(&journal);
+                    let opts = report_options(&print_opts, &journal);
+                    let xacts = filtered_xacts(&journal, &args.filter, &query);
                     let report = info::scan(xacts);
-                    if let Err(err) = printing::info(io::stdout(), &report, cli.fmt.into()) {
+                    if let Err(err) = printing::info(io::stdout(), &report, cli.fmt.into(), &opts) {
+                        eprintln!("fail printing the report: {err}");
+                        std::process::exit(1);
+                    };
+                }
+                Err(err) => {
+                    eprintln!("fail reading journal or price db: {err}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Accounts(args) => {
+            let query = report_query(&args.report_query);
+            let jrnio = path_or_stdin(cli.journal_path);
+            match util::read_journal(jrnio, None, cli.strict, cli.number_format, parse_opts) {
+                Ok((journal, _)) => {
+                    checks.run(&journal);
+                    let opts = report_options(&print_opts, &journal);
+                    let mut names = BTreeSet::new();
+                    if !args.declared {
+                        names.extend(journal.used_accounts().into_iter().cloned());
+                    }
+                    if !args.used {
+                        names.extend(journal.declared_accounts().iter().cloned());
+                    }
+                    let names: BTreeSet<_> = names
+                        .into_iter()
+                        .filter(|n| query.eval_account(n))
+                        .map(|n| n.truncate(args.depth))
+                        .collect();
+                    let names: Vec<_> = names.into_iter().collect();
+                    if let Err(err) =
+                        printing::accounts(io::stdout(), &names, cli.fmt.into(), &opts)
+                    {
+                        eprintln!("fail printing the report: {err}");
+                        std::process::exit(1);
+                    };
+                }
+                Err(err) => {
+                    eprintln!("fail reading journal or price db: {err}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Commodities(args) => {
+            let jrnio = path_or_stdin(cli.journal_path);
+            match util::read_journal(jrnio, None, cli.strict, cli.number_format, parse_opts) {
+                Ok((journal, _)) => {
+                    checks.run(&journal);
+                    let opts = report_options(&print_opts, &journal);
+                    let rows = info::commodities(journal.xacts(), journal.market_prices());
+                    if let Err(err) = printing::commodities(
+                        io::stdout(),
+                        &rows,
+                        args.count,
+                        cli.fmt.into(),
+                        &opts,
+                    ) {
+                        eprintln!("fail printing the report: {err}");
+                        std::process::exit(1);
+                    };
+                }
+                Err(err) => {
+                    eprintln!("fail reading journal or price db: {err}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Payees(args) => {
+            let query = report_query(&args.query);
+            let jrnio = path_or_stdin(cli.journal_path);
+            match util::read_journal(jrnio, None, cli.strict, cli.number_format, parse_opts) {
+                Ok((journal, _)) => {
+                    checks.run(&journal);
+                    let opts = report_options(&print_opts, &journal);
+                    let rows = info::payees(journal.xacts(), &query);
+                    if let Err(err) =
+                        printing::payees(io::stdout(), &rows, args.count, cli.fmt.into(), &opts)
+                    {
+                        eprintln!("fail printing the report: {err}");
+                        std::process::exit(1);
+                    };
+                }
+                Err(err) => {
+                    eprintln!("fail reading journal or price db: {err}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Equity(args) => {
+            let query = report_query(&args.report_query);
+            let jrnio = path_or_stdin(cli.journal_path);
+            let price_db = open_price_db(&args.prices.price_db_path);
+            match util::read_journal(jrnio, price_db, cli.strict, cli.number_format, parse_opts) {
+                Ok((journal, mut price_db)) => {
+                    checks.run(&journal);
+                    let opts = report_options(&print_opts, &journal);
+                    price_db.set_price_expiry(args.prices.price_exp);
+                    price_db.set_valuation_precision(cli.valuation_precision);
+                    let date = args.at.unwrap_or(now);
+                    let ledger =
+                        Ledger::from_xacts(filtered_xacts(&journal, &args.filter, &Query::All));
+                    let bal = Balance::from_ledger(&ledger, &query)
+                        .to_balance_view_at_dates::<Holdings>(&price_db, std::iter::once(date))
+                        .to_flat();
+                    let xact = equity::opening_xact(&bal, date);
+                    if let Err(err) =
+                        printing::prnt(io::stdout(), std::iter::once(&xact), cli.fmt.into(), &opts)
+                    {
+                        eprintln!("fail printing the report: {err}");
+                        std::process::exit(1);
+                    };
+                }
+                Err(err) => {
+                    eprintln!("fail reading journal or price db: {err}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Gains(args) => {
+            let query = report_query(&args.query);
+            let jrnio = path_or_stdin(cli.journal_path);
+            match util::read_journal(jrnio, None, cli.strict, cli.number_format, parse_opts) {
+                Ok((journal, _)) => {
+                    checks.run(&journal);
+                    let opts = report_options(&print_opts, &journal);
+                    let realized = gains::realized(
+                        journal.xacts(),
+                        &query,
+                        args.begin,
+                        args.end,
+                        args.lots_method,
+                    );
+                    let realized = match realized {
+                        Ok(r) => r,
+                        Err(err) => {
+                            eprintln!("error: {}", err.message(journal.styles()));
+                            std::process::exit(1);
+                        }
+                    };
+                    if let Err(err) =
+                        printing::gains(io::stdout(), &realized, cli.fmt.into(), &opts)
+                    {
                         eprintln!("fail printing the report: {err}");
                         std::process::exit(1);
                     };
                 }
                 Err(err) => {
-                    eprintln!("fail reading journal or price db: {err:?}");
+                    eprintln!("fail reading journal or price db: {err}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Budget(args) => {
+            let jrnio = path_or_stdin(cli.journal_path);
+            match util::read_journal(jrnio, None, cli.strict, cli.number_format, parse_opts) {
+                Ok((journal, _)) => {
+                    checks.run(&journal);
+                    let opts = report_options(&print_opts, &journal);
+                    let step = args.period.step().unwrap_or(Step::Months(1));
+                    let rows = budget::budget(&journal, step, args.begin, args.end);
+                    if let Err(err) = printing::budget(io::stdout(), &rows, cli.fmt.into(), &opts) {
+                        eprintln!("fail printing the report: {err}");
+                        std::process::exit(1);
+                    };
+                }
+                Err(err) => {
+                    eprintln!("fail reading journal or price db: {err}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Check(args) => {
+            let jrnio = path_or_stdin(cli.journal_path);
+            match util::read_journal(jrnio, None, cli.strict, cli.number_format, parse_opts) {
+                Ok((journal, _)) => {
+                    // the assertions are always verified below, along
+                    // with the other checks
+                    PostReadChecks {
+                        assertions: false,
+                        ..checks
+                    }
+                    .run(&journal);
+                    let today = args.future.then_some(now);
+                    let violations = check::check(journal.xacts(), today);
+                    for v in &violations {
+                        println!("{v}");
+                    }
+                    let failed_assertions = report_assertions(&journal);
+                    if !violations.is_empty() || failed_assertions {
+                        std::process::exit(1);
+                    }
+                }
+                Err(err) => {
+                    eprintln!("fail reading journal or price db: {err}");
                     std::process::exit(1);
                 }
             }
@@ -206,10 +568,11 @@ fn main() {
     };
 }
 
-fn path_or_stdin(path: Option<String>) -> JrnIO {
-    match path {
-        Some(p) => JrnIO::Path(p),
-        None => JrnIO::Reader(Box::new(io::stdin())),
+fn path_or_stdin(mut paths: Vec<String>) -> JrnIO {
+    match paths.len() {
+        0 => JrnIO::Reader(Box::new(io::stdin())),
+        1 => JrnIO::Path(paths.remove(0)),
+        _ => JrnIO::Paths(paths),
     }
 }
 
@@ -223,6 +586,100 @@ fn open_price_db(path: &Option<String>) -> Option<Box<dyn BufRead>> {
     })
 }
 
+/// Prints a stderr warning for each posting with a zero amount.
+fn warn_zero_postings(journal: &Journal) {
+    for (x, p) in journal.zero_postings() {
+        eprintln!(
+            "warning: zero amount posting on {} to account {}",
+            x.date.txdate, p.acc_name
+        );
+    }
+}
+
+/// Prints a stderr warning for each group of duplicated transactions,
+/// with the ids of the copies.
+fn warn_duplicate_xacts(journal: &Journal) {
+    for group in journal.find_duplicates() {
+        let ids: Vec<String> = group.iter().map(|x| x.id.to_string()).collect();
+        eprintln!(
+            "warning: duplicate transactions on {} ({}): ids {}",
+            group[0].date.txdate,
+            group[0].payee,
+            ids.join(", ")
+        );
+    }
+}
+
+/// Reports the commodities that were shown at their quantity because
+/// no price was known for them.
+fn warn_missing_prices(price_db: &PriceDB) {
+    for (s, date) in price_db.missing_prices() {
+        eprintln!("warning: no price for {s} on or before {date}, reported at its quantity");
+    }
+}
+
+/// Aborts a `-X` report listing the pairs without a rate.
+fn exit_no_exchange_price(missing: &[(Symbol, Symbol)]) -> ! {
+    let pairs: Vec<String> = missing
+        .iter()
+        .map(|(from, to)| format!("{from} -> {to}"))
+        .collect();
+    eprintln!("error: no price to exchange {}", pairs.join(", "));
+    std::process::exit(1);
+}
+
+/// The report options of the command line, writing the amounts as
+/// they are written in `journal`.
+fn report_options(base: &printing::Options, journal: &Journal) -> printing::Options {
+    printing::Options {
+        styles: journal.styles().clone(),
+        ..base.clone()
+    }
+}
+
+/// The checks asked for on the command line (`--warn-zero`,
+/// `--check-dupes`, `--check-assertions`), run on the journal of every
+/// command right after it's read.
+#[derive(Clone, Copy)]
+struct PostReadChecks {
+    warn_zero: bool,
+    dupes: bool,
+    assertions: bool,
+}
+
+impl PostReadChecks {
+    /// Runs the checks on `journal`, aborting with exit code 1 if a
+    /// balance assertion doesn't hold.
+    fn run(self, journal: &Journal) {
+        if self.warn_zero {
+            warn_zero_postings(journal);
+        }
+        if self.dupes {
+            warn_duplicate_xacts(journal);
+        }
+        if self.assertions && report_assertions(journal) {
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Reports the balance assertions (`= AMOUNT`) that don't hold, and
+/// whether there is any.
+fn report_assertions(journal: &Journal) -> bool {
+    let mismatches = Ledger::from_journal(journal).verify_assertions();
+    for m in &mismatches {
+        eprintln!(
+            "error: balance assertion failed on {} ({}) for account {}: expected {}, got {}",
+            m.xact.date.txdate,
+            m.xact.payee,
+            m.acc_name,
+            m.expected.display(journal.styles()),
+            m.actual.display(journal.styles())
+        );
+    }
+    !mismatches.is_empty()
+}
+
 /// Decode the transactions read from stdin according to the input
 /// encoding selected by `--fmt`.
 fn addx_decode(fmt: printing::Fmt, input: &str) -> Result<Vec<Xact>, JournalError> {
@@ -230,6 +687,12 @@ fn addx_decode(fmt: printing::Fmt, input: &str) -> Result<Vec<Xact>, JournalErro
         printing::Fmt::Tty => journal::parse_xacts_ledger(input),
         printing::Fmt::Json => journal::parse_xacts_json(input),
         printing::Fmt::Lisp => journal::parse_xacts_lisp(input),
+        printing::Fmt::Html | printing::Fmt::Csv | printing::Fmt::JsonLines => {
+            Err(JournalError::Io(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "html, csv and jsonl cannot be used as input format",
+            )))
+        }
     }
 }
 
@@ -238,15 +701,23 @@ fn addx_decode(fmt: printing::Fmt, input: &str) -> Result<Vec<Xact>, JournalErro
 enum Fmt {
     Tty,
     Json,
+    /// JSON Lines, register only
+    #[value(name = "jsonl")]
+    JsonLines,
     Lisp,
+    Html,
+    Csv,
 }
 
 impl From<Fmt> for printing::Fmt {
     fn from(arg: Fmt) -> Self {
         match arg {
             Fmt::Json => printing::Fmt::Json,
+            Fmt::JsonLines => printing::Fmt::JsonLines,
             Fmt::Tty => printing::Fmt::Tty,
             Fmt::Lisp => printing::Fmt::Lisp,
+            Fmt::Html => printing::Fmt::Html,
+            Fmt::Csv => printing::Fmt::Csv,
         }
     }
 }
@@ -262,20 +733,122 @@ impl From<Fmt> for printing::Fmt {
 )]
 struct Cli {
     /// The ledger file. If omitted, the journal is read from standard
-    /// input.
+    /// input. Repeat it to read several files as a single journal,
+    /// e.g. `-f a.ledger -f b.ledger`; each file is parsed on its own.
     #[arg(short = 'f', long = "file", global = true, help_heading = "Input")]
-    journal_path: Option<String>,
+    journal_path: Vec<String>,
 
     /// Format used for input and output. It controls how reports are
     /// rendered (e.g. `balance`, `register`) and how commands that
     /// consume structured data parse their input (e.g. `addx`); more
     /// subcommands will honor it as they are added. The values are
     /// `tty` for human-readable tables, `json` for machine-readable
-    /// JSON, and `lisp` for S-expressions with the same shape as
-    /// `json` (useful for Lisp/Emacs consumers).
+    /// JSON, `lisp` for S-expressions with the same shape as `json`
+    /// (useful for Lisp/Emacs consumers), and `csv` for comma-separated
+    /// rows (`register` and `balance`).
     #[arg(long = "fmt", global = true, default_value_t = Fmt::Tty, value_enum, help_heading = "Display")]
     fmt: Fmt,
 
+    /// Decimal places kept on computed valuations (price × quantity)
+    /// under `-B`, `-V` and `-H`. Stored prices keep full precision.
+    #[arg(
+        long = "valuation-precision",
+        value_name = "PLACES",
+        global = true,
+        default_value_t = misc::DEFAULT_VALUATION_PRECISION,
+        help_heading = "Display"
+    )]
+    valuation_precision: u32,
+
+    /// When to color the `tty` reports: negative amounts in red and
+    /// sub-account names dimmed. `auto` colors only when stdout is a
+    /// terminal.
+    #[arg(long = "color", global = true, default_value_t = ColorChoice::Auto, value_enum, help_heading = "Display")]
+    color: ColorChoice,
+
+    /// Write negative amounts in parentheses, accounting style
+    /// (`($ 100.00)` instead of `$ -100.00`), in the `tty` reports.
+    /// JSON and Lisp output keep the minus sign.
+    #[arg(long = "parens", global = true, action = SetTrue, help_heading = "Display")]
+    parens: bool,
+
+    /// Indent the `--fmt lisp` output, breaking the lists that don't
+    /// fit in 80 columns one element per line. By default the output
+    /// is a single line.
+    #[arg(long = "lisp-pretty", global = true, action = SetTrue, help_heading = "Display")]
+    lisp_pretty: bool,
+
+    /// strftime format of the dates in the `tty` and `html` reports,
+    /// e.g. `%Y-%m-%d` for ISO dates.
+    #[arg(
+        long = "date-format",
+        value_name = "FORMAT",
+        global = true,
+        default_value = printing::DEFAULT_DATE_FORMAT,
+        value_parser = parse_date_format,
+        help_heading = "Display"
+    )]
+    date_format: String,
+
+    /// Date transactions by their effective date
+    /// (`2025/01/01=2025/01/05`) when they have one: for the
+    /// `--begin`/`--end` filters, the periods and the register order.
+    #[arg(long = "effective", global = true, action = SetTrue, help_heading = "Input")]
+    effective: bool,
+
+    /// Take DATE as today: the default `--at` of the reports, the end
+    /// of open periods and the reference for future transactions.
+    /// Without it the current system date (UTC) is used.
+    #[arg(
+        long = "now",
+        alias = "today",
+        value_name = "DATE",
+        global = true,
+        value_parser = parse_cli_date,
+        help_heading = "Input"
+    )]
+    now: Option<NaiveDate>,
+
+    /// Emit a stderr warning for every posting with a zero amount
+    /// (e.g. `$0`), which is usually a placeholder or a typo.
+    #[arg(long = "warn-zero", global = true, action = SetTrue, help_heading = "Input")]
+    warn_zero: bool,
+
+    /// Emit a stderr warning for every group of transactions with the
+    /// same date, payee and postings, as left by importing the same
+    /// statement twice.
+    #[arg(long = "check-dupes", global = true, action = SetTrue, help_heading = "Input")]
+    check_dupes: bool,
+
+    /// Fail when a posting uses an account that was not declared with
+    /// an `account` directive, to catch typos like `Assets:Bnak`.
+    #[arg(long = "strict", global = true, action = SetTrue, help_heading = "Input")]
+    strict: bool,
+
+    /// Verify the balance assertions (`= AMOUNT`) before the report
+    /// and fail with exit code 1 when one doesn't hold. `check`
+    /// always verifies them.
+    #[arg(
+        long = "check-assertions",
+        global = true,
+        action = SetTrue,
+        help_heading = "Input"
+    )]
+    check_assertions: bool,
+
+    /// How amounts in the journal are written: `us` for `1,234.56`,
+    /// `eu` for `1.234,56`, `indian` for `12,34,567.89` and `plain` for
+    /// `1234.56`. A `D` or `commodity ... format` directive in the
+    /// journal switches it from that point on.
+    #[arg(
+        long = "number-format",
+        global = true,
+        default_value = "us",
+        value_enum,
+        help_heading = "Input"
+    )]
+    number_format: NumberFormat,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -300,6 +873,39 @@ pub enum Commands {
     #[command(alias = "pr")]
     Print(PrintArgs),
 
+    /// List the account names in the journal, one per line, sorted.
+    #[command(alias = "acc")]
+    Accounts(AccountsArgs),
+
+    /// List the commodities used by postings and price records, one
+    /// per line, sorted.
+    Commodities(CommoditiesArgs),
+
+    /// List the payees of the transactions, one per line, sorted.
+    Payees(PayeesArgs),
+
+    /// Print a transaction that opens every matching account at its
+    /// current balance, offset against `Equity:Opening Balances`.
+    /// Handy to start a new journal file.
+    Equity(EquityArgs),
+
+    /// Report realized gains: each sale matched against the open lots
+    /// it closes, with the lot cost, sale price and gain.
+    Gains(GainsArgs),
+
+    /// Compare the periodic transactions (`~ Monthly`) with the actual
+    /// postings: budgeted, actual and remaining amount per account and
+    /// period.
+    Budget(BudgetArgs),
+
+    /// Check the invariants that span transactions: no duplicated
+    /// transaction codes, no effective date before its transaction
+    /// date, every balance assertion holding and, with `--future`,
+    /// nothing dated after today. Lists the violations, the failed
+    /// assertions reported as `--check-assertions` does, and exits
+    /// with 1 if there is any.
+    Check(CheckArgs),
+
     /// Append transaction(s) read from stdin to the journal file.
     ///
     /// `-f/--file` is required unless `--check` is given. The global
@@ -322,6 +928,114 @@ pub struct AddxArgs {
     pub check: bool,
 }
 
+#[derive(Args)]
+pub struct EquityArgs {
+    /// Only open the accounts of the postings matching this report
+    /// query. Same syntax as in `balance`.
+    report_query: Vec<String>,
+
+    #[command(flatten)]
+    prices: PriceFlags,
+
+    #[command(flatten)]
+    filter: FilterFlags,
+
+    /// Date of the transaction, and of the balances it opens with.
+    /// Defaults to today.
+    #[arg(long = "at", value_parser = parse_cli_date)]
+    at: Option<NaiveDate>,
+}
+
+#[derive(Args)]
+pub struct GainsArgs {
+    /// Only consider the lots of the postings matching this report
+    /// query. Same syntax as in `balance`.
+    pub query: Vec<String>,
+
+    /// Only report sales from that date forward. Earlier buys are
+    /// still matched against.
+    #[arg(short = 'b', long = "begin", value_parser = parse_cli_date, help_heading = "Filter")]
+    pub begin: Option<NaiveDate>,
+
+    /// Ignore transactions after that date.
+    #[arg(short = 'e', long = "end", value_parser = parse_cli_date, help_heading = "Filter")]
+    pub end: Option<NaiveDate>,
+
+    /// Cost-basis method used to match sales against open lots.
+    #[arg(long = "lots-method", value_enum, default_value_t = LotStrategy::Fifo)]
+    pub lots_method: LotStrategy,
+}
+
+#[derive(Args)]
+pub struct BudgetArgs {
+    /// Report from the period containing that date. Defaults to the
+    /// first transaction.
+    #[arg(short = 'b', long = "begin", value_parser = parse_cli_date, help_heading = "Filter")]
+    pub begin: Option<NaiveDate>,
+
+    /// Report up to that date. Defaults to the last transaction.
+    #[arg(short = 'e', long = "end", value_parser = parse_cli_date, help_heading = "Filter")]
+    pub end: Option<NaiveDate>,
+
+    // monthly when no period is given
+    #[command(flatten)]
+    period: RegisterPeriodFlags,
+}
+
+#[derive(Args)]
+pub struct AccountsArgs {
+    /// Only list accounts whose name matches one of these regular
+    /// expressions. Same syntax as in `balance`; the terms other than
+    /// account patterns (`@payee`, `%tag`, `expr`) don't apply to an
+    /// account name and are ignored.
+    pub report_query: Vec<String>,
+
+    /// Only list accounts referenced by a posting.
+    #[arg(long = "used", conflicts_with = "declared", help_heading = "Filter")]
+    pub used: bool,
+
+    /// Only list accounts declared with an `account` directive.
+    #[arg(long = "declared", help_heading = "Filter")]
+    pub declared: bool,
+
+    /// Truncate account names to the top N levels. 0 means no limit
+    /// (the default).
+    #[arg(
+        long = "depth",
+        value_name = "DEPTH",
+        default_value_t = 0,
+        help_heading = "Display"
+    )]
+    pub depth: usize,
+}
+
+#[derive(Args)]
+pub struct CommoditiesArgs {
+    /// Show how many postings use each commodity, in their amount or
+    /// their price.
+    #[arg(long = "count", help_heading = "Display")]
+    pub count: bool,
+}
+
+#[derive(Args)]
+pub struct PayeesArgs {
+    /// Only list the payees of the transactions with a posting matching
+    /// this report query. Same syntax as in `balance`, so `@(?i)^sal`
+    /// lists the payees starting with `sal`.
+    pub query: Vec<String>,
+
+    /// Show how many transactions each payee appears in.
+    #[arg(long = "count", help_heading = "Display")]
+    pub count: bool,
+}
+
+#[derive(Args)]
+pub struct CheckArgs {
+    /// Also report transactions dated after today.
+    #[arg(long = "future")]
+    pub future: bool,
+}
+
 #[derive(Args)]
 pub struct SchemaArgs {
     /// Report whose schema to print. Omit to list available schemas.
@@ -331,9 +1045,8 @@ pub struct SchemaArgs {
 #[derive(Args)]
 pub struct InfoArgs {
     /// Restrict the report to transactions with at least one posting
-    /// whose account name matches one of these regular expressions.
-    /// Same syntax as in `balance`.
-    pub report_query: Vec<Regex>,
+    /// matching this report query. Same syntax as in `balance`.
+    pub report_query: Vec<String>,
 
     #[command(flatten)]
     filter: FilterFlags,
@@ -368,6 +1081,72 @@ struct ValuationFlags {
     quantity: Option<bool>,
 }
 
+/// Clearing-state filter flags shared by `balance` and `register`.
+/// They are mutually exclusive; omitting all of them includes every
+/// posting. A posting without a state of its own (`*` or `!` before
+/// the account) takes the state of its transaction.
+#[derive(Args)]
+#[group(id = "state", required = false, multiple = false)]
+struct StateFlags {
+    /// Only include cleared (`*`) postings.
+    #[arg(short = 'C', long = "cleared", help_heading = "Filter")]
+    cleared: bool,
+
+    /// Only include pending (`!`) postings.
+    #[arg(long = "pending", help_heading = "Filter")]
+    pending: bool,
+
+    /// Only include postings that are not cleared: pending ones and
+    /// those without a state.
+    #[arg(short = 'U', long = "uncleared", help_heading = "Filter")]
+    uncleared: bool,
+}
+
+impl StateFlags {
+    fn get(&self) -> StateFilter {
+        match (self.cleared, self.pending, self.uncleared) {
+            (true, _, _) => StateFilter::Cleared,
+            (_, true, _) => StateFilter::Pending,
+            (_, _, true) => StateFilter::Uncleared,
+            _ => StateFilter::All,
+        }
+    }
+}
+
+/// Where market prices come from and how long they last, shared by
+/// the reports that value holdings.
+#[derive(Args)]
+struct PriceFlags {
+    /// Path to the price database file.
+    #[arg(long = "price-db", help_heading = "Input")]
+    price_db_path: Option<String>,
+
+    /// Ignore the market prices recorded more than DAYS days before
+    /// the valuation date. A commodity whose latest price is older is
+    /// valued as if it had none: at the price it was bought at, or
+    /// else at its quantity.
+    #[arg(long = "price-exp", value_name = "DAYS", help_heading = "Valuation")]
+    price_exp: Option<u32>,
+}
+
+/// Flags that filter postings by their amount, shared by `balance`
+/// and `register`.
+#[derive(Args)]
+struct AmountFilterFlags {
+    /// Only include postings whose amount satisfies this predicate,
+    /// e.g. `'amount > 100'` or `amount<=-50EUR`. Operators are
+    /// `>`, `<`, `>=`, `<=` and `==`; a trailing commodity restricts
+    /// the predicate to amounts in that commodity.
+    #[arg(long = "limit", help_heading = "Filter")]
+    limit: Option<Limit>,
+
+    /// Only include the postings in this commodity, e.g. `--commodity
+    /// $` to follow the cash and leave the shares out. A commodity
+    /// never used makes an empty report.
+    #[arg(long = "commodity", value_name = "COMMODITY", help_heading = "Filter")]
+    commodity: Option<String>,
+}
+
 /// Parse a CLI date accepting `-`, `/`, or `.` as the separator,
 /// matching the journal grammar's `date_sep` rule. Mixed separators
 /// (e.g. `2026-01/15`) are rejected.
@@ -383,6 +1162,16 @@ fn parse_cli_date(s: &str) -> Result<NaiveDate, String> {
     ))
 }
 
+fn parse_date_format(s: &str) -> Result<String, String> {
+    if printing::is_valid_date_format(s) {
+        Ok(s.to_string())
+    } else {
+        Err(format!(
+            "invalid date format `{s}`: expected a strftime format such as `%Y-%m-%d`"
+        ))
+    }
+}
+
 /// Flags that filter which transactions are considered in the report.
 #[derive(Args)]
 struct FilterFlags {
@@ -402,20 +1191,42 @@ struct FilterFlags {
     id: Option<usize>,
 }
 
+/// Start of the window used by `balance --change`: `--begin` if
+/// given, otherwise the date of the first transaction in the journal.
+fn change_begin(filter: &FilterFlags, journal: &Journal) -> Option<NaiveDate> {
+    filter
+        .begin
+        .or_else(|| journal.date_range().map(|(first, _)| first))
+}
+
 /// Yields the transactions selected by the filter. When `--id` is set
 /// it short-circuits to that single transaction; otherwise it applies
-/// `--begin`/`--end` and the report query.
+/// `--begin`/`--end` and keeps the transactions with a posting
+/// matching `query`.
 fn filtered_xacts<'a>(
     journal: &'a Journal,
     filter: &'a FilterFlags,
-    query: &'a [Regex],
+    query: &'a Query,
 ) -> Box<dyn Iterator<Item = &'a Xact> + 'a> {
     match filter.id {
         Some(target) => Box::new(journal.filter(move |x| x.id == target).take(1)),
-        None => Box::new(journal.xact_filter_by(query, filter.begin, filter.end)),
+        None => Box::new(
+            journal
+                .xact_filter_by_date(filter.begin, filter.end)
+                .filter(move |x| query.matches_xact(x)),
+        ),
     }
 }
 
+/// Parses the report query tokens of a command, exiting on an
+/// invalid one.
+fn report_query(tokens: &[String]) -> Query {
+    Query::parse(tokens).unwrap_or_else(|err| {
+        eprintln!("error: {err}");
+        std::process::exit(2);
+    })
+}
+
 #[derive(Debug, Clone, Copy, ValueEnum)]
 pub enum Period {
     Daily,
@@ -474,6 +1285,17 @@ struct BalancePeriodFlags {
     #[arg(short = 'W', long = "weekly", help_heading = "Period")]
     weekly: bool,
 
+    /// With `--weekly`, move `--at` back to the start of its week so
+    /// the dates line up with calendar weeks. Without it, the dates
+    /// step by 7 days from `--at`.
+    #[arg(
+        long = "week-start",
+        value_enum,
+        value_name = "DAY",
+        help_heading = "Period"
+    )]
+    week_start: Option<WeekStart>,
+
     /// Use monthly intervals for `--periods`.
     #[arg(short = 'M', long = "monthly", help_heading = "Period")]
     monthly: bool,
@@ -502,6 +1324,20 @@ struct BalancePeriodFlags {
         help_heading = "Period"
     )]
     periods: i32,
+
+    /// Show, for each date, the change since the previous date instead
+    /// of the accumulated balance. The first date is compared against
+    /// the opening balance at `--begin`, which defaults to the date of
+    /// the first transaction.
+    #[arg(long = "change", alias = "cashflow", help_heading = "Period")]
+    change: bool,
+
+    /// Show the change in each balance since this date instead of the
+    /// accumulated balance; implies `--change`. Unlike `--begin` it
+    /// does not filter transactions, the balance on the day before
+    /// is used as the opening point.
+    #[arg(long = "since", value_parser = parse_cli_date, help_heading = "Period")]
+    since: Option<NaiveDate>,
 }
 
 /// Balance flags that shape how the report is rendered.
@@ -536,6 +1372,7 @@ struct BalanceDisplayFlags {
     /// Show only the summary total, suppressing all account lines.
     #[arg(
         long = "only-total",
+        alias = "total-only",
         conflicts_with = "no_total",
         help_heading = "Display"
     )]
@@ -546,6 +1383,71 @@ struct BalanceDisplayFlags {
     /// one header is emitted per date.
     #[arg(long = "date-header", help_heading = "Display")]
     date_header: bool,
+
+    /// Show balances of accounts matching REGEX with the opposite
+    /// sign, so liabilities, equity and income read as positive
+    /// numbers. Without a value, REGEX defaults to
+    /// `^(Liabilities|Equity|Income)`. Totals use the flipped values.
+    #[arg(
+        long = "flip-sign-for-liabilities",
+        value_name = "REGEX",
+        num_args = 0..=1,
+        default_missing_value = "^(Liabilities|Equity|Income)",
+        help_heading = "Display"
+    )]
+    flip_sign: Option<Regex>,
+
+    /// Show every balance, totals included, with the opposite sign.
+    /// Applied after valuation.
+    #[arg(short = 'i', long = "invert", help_heading = "Display")]
+    invert: bool,
+
+    /// Follow each amount with its share of the parent account's
+    /// balance, e.g. `$ 30.0 (25%)`; root accounts are relative to
+    /// the grand total. The share is `N/A` when either balance holds
+    /// more than one commodity, or they hold different ones.
+    /// Applies to the tty format.
+    #[arg(short = '%', long = "percent", help_heading = "Display")]
+    percent: bool,
+
+    /// Order sibling accounts by `name` (the default), by balance
+    /// magnitude largest first (`amount`), or smallest first
+    /// (`-amount`). Multi-commodity balances are compared by the sum of
+    /// their absolute quantities under the active valuation, so combine
+    /// it with `-X` to order them by value.
+    #[arg(
+        long = "sort",
+        value_enum,
+        value_name = "KEY",
+        default_value = "name",
+        allow_hyphen_values = true,
+        help_heading = "Display"
+    )]
+    sort: BalanceSort,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum BalanceSort {
+    Name,
+    Amount,
+    #[value(name = "-amount")]
+    AmountAsc,
+}
+
+impl BalanceSort {
+    fn order(self, valuation: Valuation) -> AccountOrder {
+        match self {
+            BalanceSort::Name => AccountOrder::Name,
+            BalanceSort::Amount => AccountOrder::Amount {
+                valuation,
+                descending: true,
+            },
+            BalanceSort::AmountAsc => AccountOrder::Amount {
+                valuation,
+                descending: false,
+            },
+        }
+    }
 }
 
 impl BalanceDisplayFlags {
@@ -567,19 +1469,51 @@ pub struct BalanceArgs {
     /// One or more space-separated regular expressions. Only postings
     /// whose account name matches any of them are included. Patterns
     /// use Rust `regex` syntax, match anywhere in the name
-    /// (case-sensitive; use `(?i)` for case-insensitive).
-    report_query: Vec<Regex>,
+    /// (case-sensitive; use `(?i)` for case-insensitive). A pattern
+    /// prefixed with `@` (e.g. `@Amazon`) matches the payee instead;
+    /// transactions must match every payee pattern. `%tag` keeps only
+    /// postings carrying that tag (directly or on their transaction),
+    /// and `%key=value` those whose value tag matches the value regex.
+    /// `expr EXPR` keeps the postings whose amount satisfies EXPR,
+    /// written as for `--limit`.
+    report_query: Vec<String>,
 
-    /// Path to the price database file.
-    #[arg(long = "price-db", help_heading = "Input")]
-    price_db_path: Option<String>,
+    #[command(flatten)]
+    prices: PriceFlags,
 
     #[command(flatten)]
     filter: FilterFlags,
 
+    #[command(flatten)]
+    state: StateFlags,
+
+    #[command(flatten)]
+    amount: AmountFilterFlags,
+
+    /// Leave out virtual postings, `(account)` and `[account]`.
+    #[arg(short = 'R', long = "real", help_heading = "Filter")]
+    real: bool,
+
+    /// Leave out the postings added by automated transactions
+    /// (`= EXPR`), and the `<Revalued>` register rows.
+    #[arg(short = 'L', long = "actual", help_heading = "Filter")]
+    actual: bool,
+
     #[command(flatten)]
     valuation: ValuationFlags,
 
+    /// Convert every balance into this commodity, e.g. `-X $`. Amounts
+    /// are first valued as selected by `-V`/`-H`/`-B`, then exchanged
+    /// at the prices in effect on each report date, chaining prices
+    /// through other commodities when there is no direct one.
+    #[arg(
+        short = 'X',
+        long = "exchange",
+        value_name = "COMMODITY",
+        help_heading = "Valuation"
+    )]
+    exchange: Option<String>,
+
     #[command(flatten)]
     period: BalancePeriodFlags,
 
@@ -601,6 +1535,13 @@ pub struct BalanceArgs {
     )]
     annotate: Option<Prices>,
 
+    /// Show one row per lot instead of account balances: quantity,
+    /// unit cost, market price as of `--at` and unrealized gain.
+    /// Requires `--market`. Lots whose commodity has no price show
+    /// the cost only.
+    #[arg(long = "lots", requires = "market", help_heading = "Display")]
+    lots: bool,
+
     /// Whether to emit a stderr warning when the journal has
     /// transactions dated after today. The check only runs when
     /// `--at` is not given.
@@ -625,10 +1566,9 @@ struct PrintDisplayFlags {
 #[derive(Args)]
 pub struct PrintArgs {
     /// Restrict the report to transactions with at least one posting
-    /// whose account name matches one of these regular expressions.
-    /// Same syntax as in `balance`, but the entire transaction is
-    /// included when one posting matches.
-    pub report_query: Vec<Regex>,
+    /// matching this report query. Same syntax as in `balance`, but
+    /// the entire transaction is included when one posting matches.
+    pub report_query: Vec<String>,
 
     #[command(flatten)]
     filter: FilterFlags,
@@ -637,16 +1577,63 @@ pub struct PrintArgs {
     display: PrintDisplayFlags,
 }
 
+/// Register flags that collapse postings into per-period subtotals.
+/// At most one may be given; without any, the register lists every
+/// posting.
+#[derive(Args)]
+#[group(id = "register_period", required = false, multiple = false)]
+struct RegisterPeriodFlags {
+    /// One subtotal row per account and day.
+    #[arg(short = 'D', long = "daily", help_heading = "Period")]
+    daily: bool,
+
+    /// One subtotal row per account and week (starting on Monday).
+    #[arg(short = 'W', long = "weekly", help_heading = "Period")]
+    weekly: bool,
+
+    /// One subtotal row per account and month.
+    #[arg(short = 'M', long = "monthly", help_heading = "Period")]
+    monthly: bool,
+
+    /// One subtotal row per account and quarter.
+    #[arg(short = 'Q', long = "quarterly", help_heading = "Period")]
+    quarterly: bool,
+
+    /// One subtotal row per account and year.
+    #[arg(short = 'Y', long = "yearly", help_heading = "Period")]
+    yearly: bool,
+}
+
+impl RegisterPeriodFlags {
+    /// The period to group by, if any.
+    fn step(&self) -> Option<Step> {
+        match (
+            self.daily,
+            self.weekly,
+            self.monthly,
+            self.quarterly,
+            self.yearly,
+        ) {
+            (true, ..) => Some(Step::Days(1)),
+            (_, true, ..) => Some(Step::Weeks(1)),
+            (_, _, true, ..) => Some(Step::Months(1)),
+            (.., true, _) => Some(Step::Quarters(1)),
+            (.., true) => Some(Step::Years(1)),
+            _ => None,
+        }
+    }
+}
+
 /// Register flags that shape how the report is rendered.
 #[derive(Args)]
 struct RegisterDisplayFlags {
-    /// Show only the first N postings (oldest first). Can be combined
-    /// with `--tail`.
+    /// Show only the first N postings (the oldest, or the most recent
+    /// with `--reverse`). Can be combined with `--tail`.
     #[arg(long = "head", alias = "first", help_heading = "Display")]
     head: Option<usize>,
 
-    /// Show only the last N postings (most recent). Can be combined
-    /// with `--head`.
+    /// Show only the last N postings (the most recent, or the oldest
+    /// with `--reverse`). Can be combined with `--head`.
     #[arg(long = "tail", alias = "last", help_heading = "Display")]
     tail: Option<usize>,
 
@@ -655,21 +1642,114 @@ struct RegisterDisplayFlags {
     #[arg(long = "depth", value_name = "DEPTH", help_heading = "Display")]
     acc_depth: Option<usize>,
 
-    /// Print postings from newest to oldest.
-    #[arg(long = "reverse", action = SetTrue, help_heading = "Display")]
+    /// Print postings from newest to oldest. Running totals are still
+    /// accumulated oldest first. `--head`/`--tail` apply to the
+    /// reversed order.
+    #[arg(
+        long = "reverse",
+        alias = "invert-dates",
+        action = SetTrue,
+        help_heading = "Display"
+    )]
     reverse: bool,
 
-    /// When a query is given, show all postings of each matching
-    /// transaction, not just the ones that match.
-    #[arg(long = "related", action = SetTrue, help_heading = "Display")]
+    /// When a query is given, show the other postings of each
+    /// matching transaction (the counterparts) instead of the ones
+    /// that match.
+    #[arg(short = 'r', long = "related", action = SetTrue, help_heading = "Display")]
     related: bool,
+
+    /// Show one row per transaction with the net of its postings,
+    /// named after their common parent account (or `<Total>`).
+    #[arg(short = 'n', long = "collapse", action = SetTrue, help_heading = "Display")]
+    collapse: bool,
+
+    /// Show every amount, running totals included, with the opposite
+    /// sign. Applied after valuation.
+    #[arg(short = 'i', long = "invert", action = SetTrue, help_heading = "Display")]
+    invert: bool,
+
+    /// Add a column with the running average of the amounts, per
+    /// commodity.
+    #[arg(short = 'A', long = "average", action = SetTrue, help_heading = "Display")]
+    average: bool,
+
+    /// In the `json`, `jsonl` and `lisp` formats, write one object per
+    /// transaction with its date, payee and tags, and its rows nested
+    /// under `postings`.
+    #[arg(long = "by-xact", action = SetTrue, help_heading = "Display")]
+    by_xact: bool,
+
+    /// Secondary order for postings that share the same date. Days
+    /// stay chronological; the running total follows the new order.
+    #[arg(
+        long = "sort-within-day",
+        value_enum,
+        value_name = "KEY",
+        default_value = "none",
+        help_heading = "Display"
+    )]
+    sort_within_day: SortWithinDay,
+
+    /// Order the rows by `date` (the default), by amount magnitude
+    /// largest first (`amount`), or smallest first (`-amount`). The
+    /// running total follows the new order.
+    #[arg(
+        long = "sort",
+        value_enum,
+        value_name = "KEY",
+        default_value = "date",
+        allow_hyphen_values = true,
+        help_heading = "Display"
+    )]
+    sort: RegisterSort,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum RegisterSort {
+    Date,
+    Amount,
+    #[value(name = "-amount")]
+    AmountAsc,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum WeekStart {
+    Mon,
+    Sun,
+}
+
+impl From<WeekStart> for chrono::Weekday {
+    fn from(w: WeekStart) -> Self {
+        match w {
+            WeekStart::Mon => chrono::Weekday::Mon,
+            WeekStart::Sun => chrono::Weekday::Sun,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum SortWithinDay {
+    Amount,
+    Account,
+    None,
+}
+
+impl From<SortWithinDay> for register::SortWithinDay {
+    fn from(arg: SortWithinDay) -> Self {
+        match arg {
+            SortWithinDay::Amount => register::SortWithinDay::Amount,
+            SortWithinDay::Account => register::SortWithinDay::Account,
+            SortWithinDay::None => register::SortWithinDay::None,
+        }
+    }
 }
 
 impl RegisterDisplayFlags {
     /// Effective depth for `register::register`, where 0 means one row
     /// per posting (no collapsing).
     fn depth(&self) -> usize {
-        if let Some(d) = self.acc_depth { d } else { 0 }
+        self.acc_depth.unwrap_or_default()
     }
 }
 
@@ -677,19 +1757,50 @@ impl RegisterDisplayFlags {
 pub struct RegisterArgs {
     /// One or more regular expressions. Only postings whose account
     /// name matches at least one pattern are shown.  Same regex
-    /// syntax as in `balance`.
-    pub report_query: Vec<Regex>,
+    /// syntax as in `balance`, including `@payee`, `%tag` and `expr`
+    /// tokens.
+    pub report_query: Vec<String>,
 
-    /// Path to the price database file.
-    #[arg(long = "price-db", help_heading = "Input")]
-    price_db_path: Option<String>,
+    #[command(flatten)]
+    prices: PriceFlags,
 
     #[command(flatten)]
     filter: FilterFlags,
 
+    #[command(flatten)]
+    state: StateFlags,
+
+    #[command(flatten)]
+    amount: AmountFilterFlags,
+
+    /// Leave out virtual postings, `(account)` and `[account]`.
+    #[arg(short = 'R', long = "real", help_heading = "Filter")]
+    real: bool,
+
+    /// Leave out the postings added by automated transactions
+    /// (`= EXPR`), and the `<Revalued>` register rows.
+    #[arg(short = 'L', long = "actual", help_heading = "Filter")]
+    actual: bool,
+
     #[command(flatten)]
     valuation: ValuationFlags,
 
+    /// Convert every amount and running total into this commodity,
+    /// e.g. `-X $`. Amounts are first valued as selected by
+    /// `-V`/`-H`/`-B`, then exchanged at the prices in effect on each
+    /// transaction's date, chaining prices through other commodities
+    /// when there is no direct one.
+    #[arg(
+        short = 'X',
+        long = "exchange",
+        value_name = "COMMODITY",
+        help_heading = "Valuation"
+    )]
+    exchange: Option<String>,
+
+    #[command(flatten)]
+    period: RegisterPeriodFlags,
+
     #[command(flatten)]
     display: RegisterDisplayFlags,
 }
@@ -721,11 +1832,17 @@ impl BalancePeriodFlags {
         }
     }
 
-    pub fn at_dates(&self) -> Box<dyn Iterator<Item = NaiveDate>> {
+    /// The dates the balance is reported at: the `--at` dates, or
+    /// else a series of periods starting at the one `--at` gives or
+    /// at `now`.
+    pub fn at_dates(&self, now: NaiveDate) -> Box<dyn Iterator<Item = NaiveDate>> {
         if self.at.len() > 1 {
             return Box::new(self.at.clone().into_iter());
         }
-        let base = self.at.first().copied().unwrap_or_else(misc::today);
+        let mut base = self.at.first().copied().unwrap_or(now);
+        if let (Period::Weekly, Some(first)) = (self.get_period(), self.week_start) {
+            base = misc::week_start(base, first.into());
+        }
         let step = match self.get_period() {
             Period::Daily => Step::Days(self.periods),
             Period::Weekly => Step::Weeks(self.periods),
@@ -757,6 +1874,35 @@ impl BalancePeriodFlags {
 mod tests {
     use super::*;
 
+    #[test]
+    fn change_begin_defaults_to_first_xact_date() {
+        let jf = "\
+2026-02-10 second
+  A          $2
+  B
+
+2026-01-05 first
+  A          $1
+  B
+";
+        let journal = Journal::new(JrnIO::Reader(Box::new(jf.as_bytes()))).unwrap();
+        let mut filter = FilterFlags {
+            begin: None,
+            end: None,
+            id: None,
+        };
+        assert_eq!(
+            change_begin(&filter, &journal),
+            NaiveDate::from_ymd_opt(2026, 1, 5)
+        );
+
+        filter.begin = NaiveDate::from_ymd_opt(2026, 2, 1);
+        assert_eq!(
+            change_begin(&filter, &journal),
+            NaiveDate::from_ymd_opt(2026, 2, 1)
+        );
+    }
+
     #[test]
     fn parse_cli_date_dash() {
         assert_eq!(
@@ -792,6 +1938,13 @@ mod tests {
         assert!(parse_cli_date("2026-13-01").is_err());
     }
 
+    #[test]
+    fn parse_date_format_rejects_unknown_specifiers() {
+        assert!(parse_date_format("%Y-%m-%d").is_ok());
+        assert!(parse_date_format("%Q").is_err());
+        assert!(parse_date_format("%Y %H:%M").is_err());
+    }
+
     #[test]
     fn parse_cli_date_garbage_rejected() {
         assert!(parse_cli_date("not-a-date").is_err());
@@ -1,16 +1,20 @@
-use chrono::{Duration, Months, NaiveDate, NaiveDateTime, Utc};
-use std::sync::OnceLock;
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime, Utc, Weekday};
 
 /// Converts a `NaiveDate` to a `NaiveDateTime` at midnight (00:00:00).
 pub fn to_datetime(date: NaiveDate) -> NaiveDateTime {
     date.and_hms_opt(0, 0, 0).unwrap()
 }
 
-static TODAY: OnceLock<NaiveDate> = OnceLock::new();
+/// The current UTC date. Reports take the date they treat as today
+/// from their options (`--now`), defaulting to this one.
 pub fn today() -> NaiveDate {
-    *TODAY.get_or_init(|| Utc::now().date_naive())
+    Utc::now().date_naive()
 }
 
+/// Decimal places kept by default on computed valuations, see
+/// [`crate::pricedb::PriceDB::set_valuation_precision`].
+pub const DEFAULT_VALUATION_PRECISION: u32 = 8;
+
 /// A date range checker.
 #[derive(Debug)]
 pub enum BetweenDate {
@@ -74,12 +78,18 @@ pub enum Step {
 /// or years.
 /// - Always includes the initial date
 /// - The sign indicates the direction
+/// - Month based steps clamp to the last day of shorter months, and
+///   stay on month ends when `start` is one (`2024-01-31`,
+///   `2024-02-29`, `2024-03-31`)
 pub fn iter_dates(start: NaiveDate, step: Step) -> impl Iterator<Item = NaiveDate> {
     let mut curr = start;
     let mut remaining = match step {
         Step::Days(n) | Step::Weeks(n) | Step::Months(n) | Step::Quarters(n) | Step::Years(n) => n,
     };
     let mut finished = false;
+    let mut taken = 0;
+    // a series started on a month end stays on month ends
+    let month_end = last_day_of_month(start) == start;
 
     std::iter::from_fn(move || {
         if remaining == 0 {
@@ -97,27 +107,89 @@ pub fn iter_dates(start: NaiveDate, step: Step) -> impl Iterator<Item = NaiveDat
 
         let months: u32 = match step {
             Step::Days(_) => {
-                curr = curr + Duration::days(s as i64);
+                curr += Duration::days(s as i64);
                 return Some(res);
             }
             Step::Weeks(_) => {
-                curr = curr + Duration::days(7 * s as i64);
+                curr += Duration::days(7 * s as i64);
                 return Some(res);
             }
             Step::Months(_) => 1,
             Step::Quarters(_) => 3,
             Step::Years(_) => 12,
         };
+        // offset from `start` rather than from the previous date, so a
+        // day clamped in a short month (Jan 31 -> Feb 29) isn't carried
+        // over to the next ones
+        taken += 1;
+        let offset = Months::new(months * taken);
         curr = if s > 0 {
-            curr.checked_add_months(Months::new(months)).unwrap()
+            start.checked_add_months(offset).unwrap()
         } else {
-            curr.checked_sub_months(Months::new(months)).unwrap()
+            start.checked_sub_months(offset).unwrap()
         };
+        if month_end {
+            curr = last_day_of_month(curr);
+        }
 
         Some(res)
     })
 }
 
+/// Returns the last day of the month of `d`.
+fn last_day_of_month(d: NaiveDate) -> NaiveDate {
+    let first = d.with_day(1).unwrap();
+    first
+        .checked_add_months(Months::new(1))
+        .unwrap()
+        .pred_opt()
+        .unwrap()
+}
+
+/// Returns the first day of the period of kind `step` containing `d`:
+/// the date itself for days, the Monday for weeks, and the first day
+/// of the month, quarter or year otherwise. The count in `step` is
+/// ignored.
+pub fn period_start(d: NaiveDate, step: Step) -> NaiveDate {
+    match step {
+        Step::Days(_) => d,
+        Step::Weeks(_) => d - Duration::days(d.weekday().num_days_from_monday() as i64),
+        Step::Months(_) => d.with_day(1).unwrap(),
+        Step::Quarters(_) => {
+            let month = (d.month0() / 3) * 3 + 1;
+            NaiveDate::from_ymd_opt(d.year(), month, 1).unwrap()
+        }
+        Step::Years(_) => NaiveDate::from_ymd_opt(d.year(), 1, 1).unwrap(),
+    }
+}
+
+/// Returns the last `first` weekday on or before `d`: the start of
+/// the week containing `d` when weeks start on `first`.
+pub fn week_start(d: NaiveDate, first: Weekday) -> NaiveDate {
+    d - Duration::days(d.weekday().days_since(first) as i64)
+}
+
+/// Period boundaries within `[from, to]`, in order, every `n` periods
+/// of the kind of `step` (`Step::Weeks(2)` keeps every other week)
+/// counted from the period containing `from`.
+pub fn period_boundaries(
+    from: NaiveDate,
+    to: NaiveDate,
+    step: Step,
+) -> impl Iterator<Item = NaiveDate> {
+    let (step, n) = match step {
+        Step::Days(n) => (Step::Days(i32::MAX), n),
+        Step::Weeks(n) => (Step::Weeks(i32::MAX), n),
+        Step::Months(n) => (Step::Months(i32::MAX), n),
+        Step::Quarters(n) => (Step::Quarters(i32::MAX), n),
+        Step::Years(n) => (Step::Years(i32::MAX), n),
+    };
+    iter_dates(period_start(from, step), step)
+        .step_by(n.max(1) as usize)
+        .skip_while(move |d| *d < from)
+        .take_while(move |d| *d <= to)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -127,6 +199,41 @@ mod tests {
         NaiveDate::from_ymd_opt(y, m, day).unwrap()
     }
 
+    #[test]
+    fn period_start_aligns_to_period() {
+        let date = d(2025, 5, 15); // a Thursday
+        assert_eq!(period_start(date, Step::Days(1)), date);
+        assert_eq!(period_start(date, Step::Weeks(1)), d(2025, 5, 12));
+        assert_eq!(period_start(date, Step::Months(1)), d(2025, 5, 1));
+        assert_eq!(period_start(date, Step::Quarters(1)), d(2025, 4, 1));
+        assert_eq!(period_start(date, Step::Years(1)), d(2025, 1, 1));
+    }
+
+    #[test]
+    fn week_start_snaps_back_to_first_weekday() {
+        let thu = d(2025, 5, 15);
+        assert_eq!(week_start(thu, Weekday::Mon), d(2025, 5, 12));
+        assert_eq!(week_start(thu, Weekday::Sun), d(2025, 5, 11));
+        assert_eq!(week_start(d(2025, 5, 11), Weekday::Sun), d(2025, 5, 11));
+    }
+
+    #[test]
+    fn period_boundaries_within_range() {
+        let dates: Vec<_> =
+            period_boundaries(d(2025, 1, 15), d(2025, 4, 1), Step::Months(1)).collect();
+        assert_eq!(dates, vec![d(2025, 2, 1), d(2025, 3, 1), d(2025, 4, 1)]);
+    }
+
+    #[test]
+    fn period_boundaries_skip_periods_by_the_count() {
+        let dates: Vec<_> =
+            period_boundaries(d(2025, 1, 1), d(2025, 7, 1), Step::Months(2)).collect();
+        assert_eq!(
+            dates,
+            vec![d(2025, 1, 1), d(2025, 3, 1), d(2025, 5, 1), d(2025, 7, 1)]
+        );
+    }
+
     #[test]
     fn check_from_to_inside() {
         let bd = BetweenDate::new(Some(d(2025, 1, 1)), Some(d(2025, 12, 31)));
@@ -321,6 +428,30 @@ mod tests {
         assert_eq!(dates, vec![d(2025, 12, 31)]);
     }
 
+    #[test]
+    fn iter_dates_months_clamp_to_month_end() {
+        let dates: Vec<_> = iter_dates(d(2024, 1, 31), Step::Months(3)).collect();
+        assert_eq!(
+            dates,
+            vec![
+                d(2024, 1, 31),
+                d(2024, 2, 29),
+                d(2024, 3, 31),
+                d(2024, 4, 30)
+            ]
+        );
+        let dates: Vec<_> = iter_dates(d(2023, 1, 31), Step::Months(1)).collect();
+        assert_eq!(dates, vec![d(2023, 1, 31), d(2023, 2, 28)]);
+        let dates: Vec<_> = iter_dates(d(2024, 3, 31), Step::Months(-2)).collect();
+        assert_eq!(dates, vec![d(2024, 3, 31), d(2024, 2, 29), d(2024, 1, 31)]);
+    }
+
+    #[test]
+    fn iter_dates_months_keep_day_after_short_month() {
+        let dates: Vec<_> = iter_dates(d(2024, 1, 30), Step::Months(2)).collect();
+        assert_eq!(dates, vec![d(2024, 1, 30), d(2024, 2, 29), d(2024, 3, 30)]);
+    }
+
     #[test]
     fn iter_dates_years_leap_day_clamps() {
         let dates: Vec<_> = iter_dates(d(2024, 2, 29), Step::Years(1)).collect();
@@ -96,6 +96,7 @@ mod tests {
 
     use super::*;
     use crate::holdings::{AvgPosition, Holdings};
+    use crate::misc;
 
     /// Helper: builds a AvgPosition with all unit prices denominated in "$".
     fn lot(
@@ -119,6 +120,7 @@ mod tests {
             m_uprice: uprice(market),
             h_uprice: uprice(historical),
             b_uprice: uprice(basis),
+            precision: misc::DEFAULT_VALUATION_PRECISION,
         }
     }
 
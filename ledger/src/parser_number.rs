@@ -1,5 +1,5 @@
 use regex::Regex;
-use rust_decimal::Decimal;
+use rust_decimal::{Decimal, RoundingStrategy};
 use std::{collections::HashMap, sync::OnceLock};
 
 pub fn parse(input: &str, f: NumberFormat) -> Option<Decimal> {
@@ -20,14 +20,87 @@ pub fn parse(input: &str, f: NumberFormat) -> Option<Decimal> {
     Decimal::from_str_exact(&cleaned_input).ok()
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum)]
 pub enum NumberFormat {
-    Us,       // 1,234,567.89
+    Us, // 1,234,567.89
+    #[value(name = "eu")]
     European, // 1.234.567,89
-    French,   // 1 234 567,89
-    Swiss,    // 1'234'567.89
-    Indian,   // 12,34,567.89
-    Plain,    // 1234567.89
+    // the journal grammar does not accept spaces or `'` inside amounts
+    #[value(skip)]
+    French, // 1 234 567,89
+    #[value(skip)]
+    Swiss, // 1'234'567.89
+    Indian, // 12,34,567.89
+    Plain,  // 1234567.89
+}
+
+impl NumberFormat {
+    /// Writes `value`, rounded half away from zero to `places`
+    /// decimals, with the separators of this format.
+    pub fn format(&self, value: Decimal, places: usize) -> String {
+        let value =
+            value.round_dp_with_strategy(places as u32, RoundingStrategy::MidpointAwayFromZero);
+        let formatted = format!("{:.prec$}", value, prec = places);
+        let (int, frac) = formatted.split_once('.').unwrap_or((&formatted, ""));
+        let (sign, digits) = match int.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", int),
+        };
+
+        let (group, mark) = match self {
+            NumberFormat::Us | NumberFormat::Indian => (Some(','), '.'),
+            NumberFormat::European => (Some('.'), ','),
+            NumberFormat::French => (Some(' '), ','),
+            NumberFormat::Swiss => (Some('\''), '.'),
+            NumberFormat::Plain => (None, '.'),
+        };
+
+        let mut out = String::from(sign);
+        for (i, c) in digits.chars().enumerate() {
+            let left = digits.len() - i;
+            let boundary = match self {
+                // the last group has three digits, the others two
+                NumberFormat::Indian => left == 3 || (left > 3 && (left - 3).is_multiple_of(2)),
+                _ => left.is_multiple_of(3),
+            };
+            if let (Some(g), true) = (group, i > 0 && boundary) {
+                out.push(g);
+            }
+            out.push(c);
+        }
+        if !frac.is_empty() {
+            out.push(mark);
+            out.push_str(frac);
+        }
+        out
+    }
+
+    /// Guesses the format of a sample amount such as the one in
+    /// `D 1.000,00 EUR`. When both `.` and `,` appear the last one is
+    /// the decimal mark. A lone `,` followed by three digits, or a
+    /// repeated `,`, is taken as grouping (`1,000`); a repeated `.` is
+    /// European grouping (`1.000.000`), and two-digit groups before
+    /// the last one are Indian grouping (`1,00,000.00`). Returns
+    /// `None` for samples without separators, which say nothing about
+    /// the format.
+    pub fn from_sample(sample: &str) -> Option<NumberFormat> {
+        let last = sample.rfind(['.', ','])?;
+        if is_format(sample, NumberFormat::Indian) && !is_format(sample, NumberFormat::Us) {
+            return Some(NumberFormat::Indian);
+        }
+        let commas = sample.matches(',').count();
+        let dots = sample.matches('.').count();
+
+        let f = match (commas, dots) {
+            (0, 1) => NumberFormat::Us,
+            (0, _) => NumberFormat::European,
+            (1, 0) if sample.len() - last - 1 != 3 => NumberFormat::European,
+            (_, 0) => NumberFormat::Us,
+            _ if sample.as_bytes()[last] == b',' => NumberFormat::European,
+            _ => NumberFormat::Us,
+        };
+        Some(f)
+    }
 }
 
 fn is_format(input: &str, f: NumberFormat) -> bool {
@@ -66,7 +139,7 @@ fn patterns() -> &'static HashMap<NumberFormat, Regex> {
 
         m.insert(
             NumberFormat::Indian,
-            Regex::new(r"^[+-]?(?:0|[1-9]\d{0,2})(?:,\d{2})*,\d{3}(?:\.\d+)?$").unwrap(),
+            Regex::new(r"^[+-]?(?:\d{1,3}|\d{1,2}(?:,\d{2})*,\d{3})(?:\.\d+)?$").unwrap(),
         );
 
         m
@@ -83,6 +156,29 @@ mod tests {
         Decimal::from_str(s).unwrap()
     }
 
+    #[test]
+    fn format_uses_the_separators_of_the_format() {
+        let v = d("-1234567.891");
+        assert_eq!(NumberFormat::Us.format(v, 2), "-1,234,567.89");
+        assert_eq!(NumberFormat::European.format(v, 2), "-1.234.567,89");
+        assert_eq!(NumberFormat::Indian.format(v, 2), "-12,34,567.89");
+        assert_eq!(NumberFormat::Plain.format(v, 0), "-1234568");
+        assert_eq!(NumberFormat::Us.format(d("0.125"), 2), "0.13");
+    }
+
+    #[test]
+    fn formatted_numbers_parse_back() {
+        let v = d("-123456789.25");
+        for f in [
+            NumberFormat::Us,
+            NumberFormat::European,
+            NumberFormat::Indian,
+            NumberFormat::Plain,
+        ] {
+            assert_eq!(parse(&f.format(v, 2), f), Some(v), "{f:?}");
+        }
+    }
+
     #[test]
     fn parse_plain_numbers() {
         assert_eq!(parse("0", NumberFormat::Plain), Some(d("0")));
@@ -155,10 +251,46 @@ mod tests {
             parse("-12,34,56,789.00", NumberFormat::Indian),
             Some(d("-123456789.00"))
         );
+        assert_eq!(parse("100.50", NumberFormat::Indian), Some(d("100.50")));
         assert_eq!(parse("-12,34.00", NumberFormat::Indian), None);
+        assert_eq!(parse("123,45,678", NumberFormat::Indian), None);
         assert_eq!(parse("-12,34,32.00", NumberFormat::Indian), None);
     }
 
+    #[test]
+    fn format_from_sample() {
+        assert_eq!(NumberFormat::from_sample("1000"), None);
+        assert_eq!(
+            NumberFormat::from_sample("1.000,00"),
+            Some(NumberFormat::European)
+        );
+        assert_eq!(
+            NumberFormat::from_sample("1000,00"),
+            Some(NumberFormat::European)
+        );
+        assert_eq!(
+            NumberFormat::from_sample("1.000.000"),
+            Some(NumberFormat::European)
+        );
+        assert_eq!(
+            NumberFormat::from_sample("1,000.00"),
+            Some(NumberFormat::Us)
+        );
+        assert_eq!(NumberFormat::from_sample("1,000"), Some(NumberFormat::Us));
+        assert_eq!(
+            NumberFormat::from_sample("1,00,000.00"),
+            Some(NumberFormat::Indian)
+        );
+        assert_eq!(NumberFormat::from_sample("-10.5"), Some(NumberFormat::Us));
+    }
+
+    #[test]
+    fn misplaced_grouping_is_rejected() {
+        assert_eq!(parse("1,23,456", NumberFormat::Us), None);
+        assert_eq!(parse("1.23.456", NumberFormat::European), None);
+        assert_eq!(parse("1.234,56", NumberFormat::Us), None);
+    }
+
     #[test]
     fn parse_with_leading_and_trailing_zeros() {
         assert_eq!(parse("001,234.500", NumberFormat::Us), Some(d("1234.500")));
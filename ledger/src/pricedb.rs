@@ -1,12 +1,14 @@
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
     io,
+    sync::Mutex,
 };
 
 use crate::{
     amount::Amount, journal::Journal, misc, ntypes::Quantities, quantity::Quantity, symbol::Symbol,
 };
-use chrono::{NaiveDate, NaiveDateTime};
+use chrono::{NaiveDate, NaiveDateTime, TimeDelta};
+use rust_decimal::Decimal;
 
 pub use parser::ParseError;
 
@@ -36,6 +38,29 @@ pub struct MarketPrice {
 #[derive(Default)]
 pub struct PriceDB {
     data: HashMap<Symbol, BTreeMap<NaiveDateTime, Quantity>>,
+    /// Unit rates keyed by `(from, to)` commodity pair, so that a rate
+    /// can be looked up in either direction.
+    pairs: HashMap<(Symbol, Symbol), BTreeMap<NaiveDateTime, Decimal>>,
+    /// The commodities each one has a pair with, in either direction.
+    /// Ordered, so that ties between equally short paths in
+    /// [`PriceDB::convert`] resolve the same way on every run.
+    neighbours: BTreeMap<Symbol, BTreeSet<Symbol>>,
+    /// Rates found by [`PriceDB::convert`], keyed by query date and
+    /// pair. `None` records that no path exists.
+    paths: Mutex<HashMap<(NaiveDateTime, Symbol, Symbol), Option<Decimal>>>,
+    /// Prices found by [`PriceDB::uprice_as_of`], keyed by commodity
+    /// and query date. Valuing the same holdings at several dates asks
+    /// for the same prices over and over.
+    uprices: Mutex<HashMap<(Symbol, NaiveDateTime), Option<Quantity>>>,
+    /// Commodities that had to be reported at their quantity for lack
+    /// of a price, with the earliest date asked for.
+    missing: Mutex<BTreeMap<Symbol, NaiveDate>>,
+    /// How old a price can be and still be used, see
+    /// [`PriceDB::set_price_expiry`].
+    expiry: Option<TimeDelta>,
+    /// Decimal places kept on valuations, see
+    /// [`PriceDB::set_valuation_precision`].
+    precision: Option<u32>,
 }
 
 impl PriceDB {
@@ -44,24 +69,35 @@ impl PriceDB {
         PriceDB::default()
     }
 
-    /// Constructs a `PriceDB` from a `Journal`
+    /// Constructs a `PriceDB` from a `Journal`, with the unit prices
+    /// of its postings and its `P` records.
+    ///
+    /// Prices are applied by timestamp. When a commodity has several
+    /// prices at the same timestamp, the one written last in the file
+    /// wins, whether it comes from a posting or a `P` record.
     pub fn from_journal(journal: &Journal) -> PriceDB {
-        let mut db = PriceDB::new();
-        journal
+        // file order: a `P` record written after `n` transactions goes
+        // between the transactions with ids `n - 1` and `n`
+        let mut prices: Vec<_> = journal
             .xacts()
             .flat_map(|x| {
-                x.postings
-                    .iter()
-                    .map(|p| (p.quantity.s, misc::to_datetime(x.date.txdate), p.uprice))
+                x.postings.iter().map(|p| {
+                    let at = misc::to_datetime(x.date.txdate);
+                    (at, 2 * x.id + 1, p.quantity.s, p.uprice)
+                })
             })
             .chain(
                 journal
-                    .market_prices()
-                    .map(|mp| (mp.sym, mp.date_time, mp.price)),
+                    .market_prices_with_pos()
+                    .map(|(pos, mp)| (mp.date_time, 2 * pos, mp.sym, mp.price)),
             )
-            .for_each(|(s, at, price)| {
-                db.upsert_price(s, at, price);
-            });
+            .collect();
+        prices.sort_by_key(|(at, order, ..)| (*at, *order));
+
+        let mut db = PriceDB::new();
+        for (at, _, s, price) in prices {
+            db.upsert_price(s, at, price);
+        }
         db
     }
 
@@ -69,6 +105,56 @@ impl PriceDB {
     /// specific date.
     pub fn upsert_price(&mut self, s: Symbol, at: NaiveDateTime, price: Quantity) {
         self.data.entry(s).or_default().insert(at, price);
+        if s != price.s {
+            self.pairs
+                .entry((s, price.s))
+                .or_default()
+                .insert(at, price.q);
+            self.neighbours.entry(s).or_default().insert(price.s);
+            self.neighbours.entry(price.s).or_default().insert(s);
+        }
+        self.paths.get_mut().unwrap().clear();
+        self.uprices.get_mut().unwrap().clear();
+    }
+
+    /// Makes the lookups as of a date ignore the prices recorded more
+    /// than `days` days before it, as `--price-exp` does. `None`
+    /// lets any earlier price be used.
+    ///
+    /// Since a commodity's latest price before the date is the
+    /// freshest one it has, an expired price leaves it without a
+    /// price: [`PriceDB::uprice_as_of`] and [`PriceDB::rate_as_of`]
+    /// answer `None`, and the valuations fall back as they do for a
+    /// commodity never priced. Market values ([`PriceDB::uprice_or`])
+    /// then use the posting's own price, its cost basis, and failing
+    /// that report the quantity, listed in
+    /// [`PriceDB::missing_prices`].
+    ///
+    /// [`PriceDB::latest_price`] doesn't depend on a date and ignores
+    /// the window.
+    pub fn set_price_expiry(&mut self, days: Option<u32>) {
+        self.expiry = days.map(|d| TimeDelta::days(d.into()));
+        self.paths.get_mut().unwrap().clear();
+        self.uprices.get_mut().unwrap().clear();
+    }
+
+    /// Sets the number of decimal places kept on computed valuations
+    /// (price × quantity), as `--valuation-precision` does. Stored
+    /// prices and quantities keep full precision.
+    pub fn set_valuation_precision(&mut self, dp: u32) {
+        self.precision = Some(dp);
+    }
+
+    /// Decimal places kept on computed valuations,
+    /// [`misc::DEFAULT_VALUATION_PRECISION`] unless set with
+    /// [`PriceDB::set_valuation_precision`].
+    pub fn valuation_precision(&self) -> u32 {
+        self.precision.unwrap_or(misc::DEFAULT_VALUATION_PRECISION)
+    }
+
+    /// Whether a price recorded on `on` can still be used on `at`.
+    fn is_fresh(&self, on: NaiveDateTime, at: NaiveDateTime) -> bool {
+        self.expiry.is_none_or(|exp| at - on <= exp)
     }
 
     /// Retrieves the most recent price of a symbol. All symbols
@@ -82,21 +168,203 @@ impl PriceDB {
     }
 
     /// Returns the most recent unit price recorded for `s` on or
-    /// before `at`, or `None` if no such price exists.
+    /// before `at`, or `None` if no such price exists or it has
+    /// expired (see [`PriceDB::set_price_expiry`]).
+    ///
+    /// A commodity only priced the other way round, e.g. BTC after
+    /// `P $ 0.00125 BTC`, is priced in that commodity through
+    /// [`PriceDB::uprice_in`], provided the latter isn't itself valued
+    /// in it by then.
     pub fn uprice_as_of(&self, s: Symbol, at: NaiveDateTime) -> Option<Quantity> {
+        let key = (s, at);
+        if let Some(&price) = self.uprices.lock().unwrap().get(&key) {
+            return price;
+        }
+        let price = self.lookup_uprice(s, at);
+        self.uprices.lock().unwrap().insert(key, price);
+        price
+    }
+
+    /// [`PriceDB::uprice_as_of`] without the cache.
+    fn lookup_uprice(&self, s: Symbol, at: NaiveDateTime) -> Option<Quantity> {
+        let direct = self.direct_uprice(s, at);
+        match direct {
+            // a price in `s` itself is only the posting's own amount
+            Some(price) if price.s != s => Some(price),
+            _ => self
+                .inverse_target(s, at)
+                .and_then(|t| self.uprice_in(s, t, at))
+                .or(direct),
+        }
+    }
+
+    /// The latest price recorded for `s` itself on or before `at`,
+    /// unless expired.
+    fn direct_uprice(&self, s: Symbol, at: NaiveDateTime) -> Option<Quantity> {
         self.data
             .get(&s)
-            .and_then(|prices| prices.range(..=at).next_back().map(|(_, &price)| price))
+            .and_then(|prices| prices.range(..=at).next_back())
+            .filter(|&(&on, _)| self.is_fresh(on, at))
+            .map(|(_, &price)| price)
+    }
+
+    /// The commodity with the freshest price expressed in `s` as of
+    /// `at`, ties going to the first one by name. A commodity that is
+    /// itself valued in `s`, as AAPL is after `P AAPL $120`, doesn't
+    /// count: `$` would otherwise be valued back in AAPL.
+    fn inverse_target(&self, s: Symbol, at: NaiveDateTime) -> Option<Symbol> {
+        self.neighbours
+            .get(&s)
+            .into_iter()
+            .flatten()
+            .filter(|&&from| self.direct_uprice(from, at).is_none_or(|p| p.s != s))
+            .filter_map(|&from| {
+                let rates = self.pairs.get(&(from, s))?;
+                let (&on, _) = rates.range(..=at).next_back()?;
+                self.is_fresh(on, at).then_some((on, from))
+            })
+            .min_by_key(|&(on, from)| (std::cmp::Reverse(on), from))
+            .map(|(_, from)| from)
+    }
+
+    /// Like [`PriceDB::uprice_as_of`] but never fails: without a
+    /// price on or before `at`, `fallback` (usually the posting's own
+    /// `uprice`) is returned. A fallback in `s` itself means `s` ends
+    /// up reported at its quantity, which is recorded in
+    /// [`PriceDB::missing_prices`].
+    pub fn uprice_or(&self, s: Symbol, at: NaiveDateTime, fallback: Quantity) -> Quantity {
+        self.uprice_as_of(s, at).unwrap_or_else(|| {
+            if fallback.s == s {
+                self.record_missing(s, at.date());
+            }
+            fallback
+        })
+    }
+
+    /// Like [`PriceDB::value_as_of`] but a commodity without a price
+    /// keeps its quantity, and is recorded in
+    /// [`PriceDB::missing_prices`].
+    pub fn value_or_quantity(&self, at: NaiveDate, q: impl Quantities) -> Amount {
+        let at = misc::to_datetime(at);
+        let value = q.quantities().fold(Amount::new(), |amt, q| {
+            let unit = Quantity {
+                q: Decimal::ONE,
+                s: q.s,
+            };
+            amt + self.uprice_or(q.s, at, unit) * q.q
+        });
+        value.round_dp(self.valuation_precision())
+    }
+
+    /// The commodities that were valued at their quantity because no
+    /// price was known, each with the earliest date that lacked one.
+    pub fn missing_prices(&self) -> Vec<(Symbol, NaiveDate)> {
+        self.missing
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(&s, &d)| (s, d))
+            .collect()
+    }
+
+    fn record_missing(&self, s: Symbol, at: NaiveDate) {
+        self.missing
+            .lock()
+            .unwrap()
+            .entry(s)
+            .and_modify(|d| *d = (*d).min(at))
+            .or_insert(at);
+    }
+
+    /// Returns how many units of `to` one unit of `from` was worth on
+    /// or before `at`, ignoring expired rates (see
+    /// [`PriceDB::set_price_expiry`]).
+    ///
+    /// A direct `from -> to` price is preferred. Failing that, a
+    /// `to -> from` price is inverted, so `P $ 0.00125 BTC` also
+    /// answers the BTC to $ rate. A zero rate cannot be inverted and
+    /// yields `None`.
+    pub fn rate_as_of(&self, from: Symbol, to: Symbol, at: NaiveDateTime) -> Option<Decimal> {
+        if from == to {
+            return Some(Decimal::ONE);
+        }
+        let latest = |key: &(Symbol, Symbol)| {
+            self.pairs
+                .get(key)
+                .and_then(|rates| rates.range(..=at).next_back())
+                .filter(|&(&on, _)| self.is_fresh(on, at))
+                .map(|(_, &r)| r)
+        };
+        latest(&(from, to)).or_else(|| {
+            latest(&(to, from))
+                .filter(|r| !r.is_zero())
+                .and_then(|r| Decimal::ONE.checked_div(r))
+        })
+    }
+
+    /// Like [`PriceDB::uprice_as_of`] but expressed in `target`,
+    /// inverting the reverse price when no direct one exists and
+    /// chaining prices through [`PriceDB::convert`] as a last resort.
+    pub fn uprice_in(&self, s: Symbol, target: Symbol, at: NaiveDateTime) -> Option<Quantity> {
+        let unit = Quantity { q: Decimal::ONE, s };
+        self.convert(unit, target, at)
+    }
+
+    /// Converts `q` into `to` using the prices in effect on `at`.
+    ///
+    /// When no direct (or inverted) rate exists, the rate is composed
+    /// along the shortest chain of known pairs, e.g. `AAPL -> EUR ->
+    /// $`. Returns `None` when `to` is unreachable from `q.s`.
+    pub fn convert(&self, q: Quantity, to: Symbol, at: NaiveDateTime) -> Option<Quantity> {
+        let key = (at, q.s, to);
+        let cached = self.paths.lock().unwrap().get(&key).copied();
+        let rate = match cached {
+            Some(rate) => rate,
+            None => {
+                let rate = self.find_rate(q.s, to, at);
+                self.paths.lock().unwrap().insert(key, rate);
+                rate
+            }
+        };
+        rate.map(|r| Quantity { q: q.q * r, s: to })
+    }
+
+    /// Breadth-first search over the pair graph, each edge taken in
+    /// whichever direction [`PriceDB::rate_as_of`] can answer.
+    fn find_rate(&self, from: Symbol, to: Symbol, at: NaiveDateTime) -> Option<Decimal> {
+        let mut seen = HashSet::from([from]);
+        let mut queue = VecDeque::from([(from, Decimal::ONE)]);
+        while let Some((s, rate)) = queue.pop_front() {
+            if s == to {
+                return Some(rate);
+            }
+            for &next in self.neighbours.get(&s).into_iter().flatten() {
+                if seen.contains(&next) {
+                    continue;
+                }
+                let Some(r) = self.rate_as_of(s, next, at) else {
+                    continue;
+                };
+                let Some(rate) = rate.checked_mul(r) else {
+                    continue;
+                };
+                seen.insert(next);
+                queue.push_back((next, rate));
+            }
+        }
+        None
     }
 
     /// Returns the total value of `q` at the prices in effect on
     /// `at`, or `None` if any commodity has no price recorded on or
-    /// before that date.
+    /// before that date. The result is rounded to
+    /// [`PriceDB::valuation_precision`] places.
     pub fn value_as_of(&self, at: NaiveDate, q: impl Quantities) -> Option<Amount> {
-        q.quantities().try_fold(Amount::new(), |amt, q| {
+        let value = q.quantities().try_fold(Amount::new(), |amt, q| {
             let uprice = self.uprice_as_of(q.s, misc::to_datetime(at))?;
             Some(amt + uprice * q.q)
-        })
+        })?;
+        Some(value.round_dp(self.valuation_precision()))
     }
 }
 
@@ -104,31 +372,44 @@ impl PriceDB {
 ///
 /// Variants:
 /// - `Price(MarketPrice)`: Successfully parsed market price.
-/// - `ParseError(ParseError)`: Failed to parse a line.
+/// - `ParseError { line, err }`: Failed to parse the (1-based) `line`.
 /// - `IoError(io::Error)`: Failed to read a line from the file.
 pub enum ReadItem {
     Price(MarketPrice),
-    ParseError(ParseError),
+    ParseError { line: usize, err: ParseError },
     IoError(io::Error),
 }
 
 /// Reads a price database from a buffered reader and returns a lazy iterator of `ReadItem`s.
 ///
-/// Each line is parsed into a `MarketPrice`:
+/// Blank lines and comment lines (starting with `;`, `#`, `%`, `|`
+/// or `*`) are skipped, every other line is parsed into a
+/// `MarketPrice`:
 /// - `ReadItem::Price` for successful parse,
-/// - `ReadItem::ParseError` if parsing fails,
+/// - `ReadItem::ParseError` with the line number if parsing fails,
 /// - `ReadItem::IoError` if reading the line fails.
 ///
 /// # Arguments
 /// * `reader` - Any type implementing `BufRead` (e.g. `BufReader<File>`, `&[u8]`).
 pub fn read_price_db(reader: impl io::BufRead) -> impl Iterator<Item = ReadItem> {
-    reader.lines().map(|line| match line {
-        Ok(line) => match parser::parse_market_price_line(&line) {
-            Ok(price) => ReadItem::Price(price),
-            Err(err) => ReadItem::ParseError(err),
-        },
-        Err(err) => ReadItem::IoError(err),
-    })
+    reader
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| match line {
+            Ok(line) if is_blank_or_comment(&line) => None,
+            Ok(line) => Some(match parser::parse_market_price_line(&line) {
+                Ok(price) => ReadItem::Price(price),
+                Err(err) => ReadItem::ParseError { line: i + 1, err },
+            }),
+            Err(err) => Some(ReadItem::IoError(err)),
+        })
+}
+
+fn is_blank_or_comment(line: &str) -> bool {
+    line.trim_start()
+        .chars()
+        .next()
+        .is_none_or(|c| matches!(c, ';' | '#' | '%' | '|' | '*'))
 }
 
 mod parser {
@@ -158,6 +439,26 @@ mod parser {
         InvalidTime,
     }
 
+    impl std::fmt::Display for ParseError {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            let msg = match self {
+                ParseError::ExpectedP => "expected 'P'",
+                ParseError::ExpectedDate => "expected a date",
+                ParseError::ExpectedTimeOrSymbol => "expected a time or a commodity",
+                ParseError::UnexpectedEndOfInput => "unexpected end of line",
+                ParseError::ExpectedSymbol => "expected a commodity",
+                ParseError::EndQuoteOfSymbolNotFound => "unterminated quoted commodity",
+                ParseError::ExpectedPrice => "expected a price",
+                ParseError::ExpectedNum => "expected a number",
+                ParseError::InvalidDate => "invalid date",
+                ParseError::InvalidTime => "invalid time",
+            };
+            f.write_str(msg)
+        }
+    }
+
+    impl std::error::Error for ParseError {}
+
     pub type ParseResult<T> = Result<T, ParseError>;
 
     /// Parses a market price str into a `MarketPrice` structure.
@@ -856,6 +1157,243 @@ mod tests {
         assert_eq!(db.uprice_as_of(s2, at1), Some(quantity!(1.0, "$")));
     }
 
+    #[test]
+    fn test_uprice_as_of_matches_linear_scan() {
+        let s = Symbol::new("LINEAR-SCAN");
+        let usd = Symbol::new("$");
+        let start = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+        // inserted out of order, every other day
+        let mut prices: Vec<(NaiveDateTime, Quantity)> = (0..10_000)
+            .map(|i| {
+                let at = misc::to_datetime(start + chrono::Duration::days(2 * i));
+                let price = Quantity {
+                    q: Decimal::from(i),
+                    s: usd,
+                };
+                (at, price)
+            })
+            .collect();
+        prices.reverse();
+
+        let mut db = PriceDB::new();
+        for (at, price) in &prices {
+            db.upsert_price(s, *at, *price);
+        }
+
+        for day in (-1..20_001).step_by(7) {
+            let at = misc::to_datetime(start + chrono::Duration::days(day));
+            let linear = prices
+                .iter()
+                .filter(|(d, _)| *d <= at)
+                .max_by_key(|(d, _)| *d)
+                .map(|(_, p)| *p);
+            assert_eq!(db.uprice_as_of(s, at), linear, "at {at}");
+        }
+        assert_eq!(db.latest_price(s).q, Decimal::from(9_999));
+    }
+
+    #[test]
+    fn test_cached_uprice_agrees_with_uncached() {
+        let s = Symbol::new("CACHED");
+        let usd = Symbol::new("$");
+        let start = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let day = |n| misc::to_datetime(start + chrono::Duration::days(n));
+
+        let mut db = PriceDB::new();
+        for i in 0..100 {
+            let price = Quantity {
+                q: Decimal::from(i),
+                s: usd,
+            };
+            db.upsert_price(s, day(3 * i), price);
+        }
+
+        for _ in 0..2 {
+            for n in -1..310 {
+                assert_eq!(db.uprice_as_of(s, day(n)), db.lookup_uprice(s, day(n)));
+            }
+        }
+
+        // a new price invalidates the cached answers
+        db.upsert_price(s, day(1), quantity!(1000, "$"));
+        assert_eq!(db.uprice_as_of(s, day(2)), Some(quantity!(1000, "$")));
+        assert_eq!(db.uprice_as_of(s, day(2)), db.lookup_uprice(s, day(2)));
+    }
+
+    #[test]
+    fn test_rate_inverts_reverse_price() {
+        let mut db = PriceDB::new();
+        let usd = Symbol::new("$");
+        let btc = Symbol::new("BTC");
+        let at = misc::to_datetime(NaiveDate::from_ymd_opt(2025, 1, 1).unwrap());
+
+        db.upsert_price(usd, at, quantity!(0.00125, "BTC"));
+
+        assert_eq!(db.rate_as_of(usd, btc, at), Some(dec!(0.00125)));
+        assert_eq!(db.uprice_in(btc, usd, at), Some(quantity!(800, "$")));
+        assert_eq!(db.rate_as_of(btc, btc, at), Some(dec!(1)));
+        let before = misc::to_datetime(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap());
+        assert_eq!(db.rate_as_of(btc, usd, before), None);
+
+        // market valuation inverts it once $ is no longer valued in
+        // BTC, as after a plain $ posting
+        assert_eq!(db.uprice_as_of(btc, at), None);
+        let later = misc::to_datetime(NaiveDate::from_ymd_opt(2025, 1, 2).unwrap());
+        db.upsert_price(usd, later, quantity!(1, "$"));
+        assert_eq!(db.uprice_as_of(btc, later), Some(quantity!(800, "$")));
+        assert_eq!(db.uprice_as_of(usd, later), Some(quantity!(1, "$")));
+    }
+
+    #[test]
+    fn test_rate_zero_price_is_not_inverted() {
+        let mut db = PriceDB::new();
+        let at = misc::to_datetime(NaiveDate::from_ymd_opt(2025, 1, 1).unwrap());
+
+        db.upsert_price(Symbol::new("X"), at, quantity!(0, "Y"));
+
+        assert_eq!(
+            db.rate_as_of(Symbol::new("X"), Symbol::new("Y"), at),
+            Some(dec!(0))
+        );
+        assert_eq!(db.rate_as_of(Symbol::new("Y"), Symbol::new("X"), at), None);
+    }
+
+    #[test]
+    fn test_value_as_of_rounds_to_the_db_precision() {
+        let mut db = PriceDB::new();
+        let day = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let thirds = Quantity {
+            q: dec!(10) / dec!(3),
+            s: Symbol::new("$"),
+        };
+        db.upsert_price(Symbol::new("ROUNDED"), misc::to_datetime(day), thirds);
+        let held = quantity!(1, "ROUNDED");
+
+        assert_eq!(db.valuation_precision(), misc::DEFAULT_VALUATION_PRECISION);
+        let value = db.value_as_of(day, held).unwrap();
+        assert_eq!(value.to_quantity(), Some(quantity!(3.33333333, "$")));
+
+        db.set_valuation_precision(2);
+        let value = db.value_as_of(day, held).unwrap();
+        assert_eq!(value.to_quantity(), Some(quantity!(3.33, "$")));
+    }
+
+    #[test]
+    fn test_convert_composes_rates() {
+        let mut db = PriceDB::new();
+        let at = misc::to_datetime(NaiveDate::from_ymd_opt(2025, 1, 1).unwrap());
+        let (aapl, eur, usd, gbp) = (
+            Symbol::new("AAPL"),
+            Symbol::new("EUR"),
+            Symbol::new("$"),
+            Symbol::new("GBP"),
+        );
+
+        db.upsert_price(aapl, at, quantity!(100, "EUR"));
+        db.upsert_price(usd, at, quantity!(0.8, "EUR"));
+
+        assert_eq!(
+            db.convert(quantity!(2, "AAPL"), usd, at),
+            Some(quantity!(250, "$"))
+        );
+        assert_eq!(
+            db.convert(quantity!(5, "EUR"), eur, at),
+            Some(quantity!(5, "EUR"))
+        );
+        assert_eq!(db.convert(quantity!(1, "AAPL"), gbp, at), None);
+        assert_eq!(db.uprice_in(aapl, usd, at), Some(quantity!(125, "$")));
+        let before = misc::to_datetime(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap());
+        assert_eq!(db.convert(quantity!(1, "AAPL"), usd, before), None);
+
+        // a later price invalidates what was resolved before
+        db.upsert_price(gbp, at, quantity!(1.25, "$"));
+        assert_eq!(
+            db.convert(quantity!(1, "AAPL"), gbp, at),
+            Some(quantity!(100, "GBP"))
+        );
+    }
+
+    #[test]
+    fn test_uprice_or_falls_back_and_records_missing() {
+        let mut db = PriceDB::new();
+        let aapl = Symbol::new("AAPL");
+        let d1 = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let d2 = NaiveDate::from_ymd_opt(2025, 6, 1).unwrap();
+        db.upsert_price(aapl, misc::to_datetime(d2), quantity!(150, "$"));
+
+        let at = misc::to_datetime(d1);
+        assert_eq!(
+            db.uprice_or(aapl, at, quantity!(100, "$")),
+            quantity!(100, "$")
+        );
+        assert!(db.missing_prices().is_empty());
+
+        assert_eq!(
+            db.value_or_quantity(d1, quantity!(3, "AAPL")),
+            quantity!(3, "AAPL").to_amount()
+        );
+        assert_eq!(db.missing_prices(), vec![(aapl, d1)]);
+    }
+
+    #[test]
+    fn test_expired_prices_are_ignored() {
+        let mut db = PriceDB::new();
+        let aapl = Symbol::new("AAPL");
+        let eur = Symbol::new("EUR");
+        let d = |m, d| misc::to_datetime(NaiveDate::from_ymd_opt(2025, m, d).unwrap());
+        db.upsert_price(aapl, d(1, 1), quantity!(150, "$"));
+        db.upsert_price(eur, d(1, 1), quantity!(1.1, "$"));
+        db.set_price_expiry(Some(30));
+
+        assert_eq!(db.uprice_as_of(aapl, d(1, 31)), Some(quantity!(150, "$")));
+        assert_eq!(db.uprice_as_of(aapl, d(2, 1)), None);
+        assert_eq!(db.rate_as_of(eur, Symbol::new("$"), d(2, 1)), None);
+        assert_eq!(
+            db.uprice_or(aapl, d(2, 1), quantity!(100, "$")),
+            quantity!(100, "$")
+        );
+
+        // a newer price is fresh again
+        db.upsert_price(aapl, d(2, 15), quantity!(160, "$"));
+        assert_eq!(db.uprice_as_of(aapl, d(3, 1)), Some(quantity!(160, "$")));
+
+        db.set_price_expiry(None);
+        assert_eq!(db.uprice_as_of(eur, d(12, 31)), Some(quantity!(1.1, "$")));
+    }
+
+    #[test]
+    fn test_from_journal_last_price_in_file_wins() {
+        let jf = "\
+P 2025/01/10 DUP $ 1.00
+
+2025/01/10 buy
+    Assets:Broker    1 DUP @ $2.00
+    Assets:Cash
+
+P 2025/01/10 DUP $ 3.00
+P 2025/01/10 DUP $ 4.00
+
+P 2025/01/11 DUQ $ 5.00
+
+2025/01/11 buy
+    Assets:Broker    1 DUQ @ $6.00
+    Assets:Cash
+";
+        let journal = crate::journal::Journal::parse(jf).unwrap();
+        let db = PriceDB::from_journal(&journal);
+
+        let d1 = misc::to_datetime(NaiveDate::from_ymd_opt(2025, 1, 10).unwrap());
+        let d2 = misc::to_datetime(NaiveDate::from_ymd_opt(2025, 1, 11).unwrap());
+        assert_eq!(
+            db.uprice_as_of(Symbol::new("DUP"), d1),
+            Some(quantity!(4.00, "$"))
+        );
+        assert_eq!(
+            db.uprice_as_of(Symbol::new("DUQ"), d2),
+            Some(quantity!(6.00, "$"))
+        );
+    }
+
     #[test]
     fn test_from_journal() {
         let jf = "\
@@ -903,7 +1441,22 @@ P 2025/08/28 LTM  $ 23.69
         let items: Vec<_> = read_price_db(input.as_ref()).collect();
         assert_eq!(items.len(), 2);
         assert!(matches!(items[0], ReadItem::Price(_)));
-        assert!(matches!(items[1], ReadItem::ParseError(_)));
+        assert!(matches!(items[1], ReadItem::ParseError { line: 2, .. }));
+    }
+
+    #[test]
+    fn test_read_price_db_skips_blank_and_comment_lines() {
+        let input = b"; prices\n\nP 2025/01/01 GOOG $ 100.00\n  \n# more\nP 2025/01/0x GOOG $ 1\n";
+        let items: Vec<_> = read_price_db(input.as_ref()).collect();
+        assert_eq!(items.len(), 2);
+        assert!(matches!(items[0], ReadItem::Price(_)));
+        assert!(matches!(
+            items[1],
+            ReadItem::ParseError {
+                line: 6,
+                err: ParseError::InvalidDate | ParseError::ExpectedDate
+            }
+        ));
     }
 
     #[test]
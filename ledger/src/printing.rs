@@ -1,20 +1,221 @@
 use std::collections::BTreeMap;
 
+use chrono::NaiveDate;
+use chrono::format::{Item, StrftimeItems};
 use comfy_table::{Attribute, Cell, CellAlignment, Color, Table, presets};
-use console;
-use rust_decimal::Decimal;
+use console::{self, Alignment};
+use rust_decimal::{Decimal, RoundingStrategy};
 use serde_json;
 
+use crate::amount::Amount;
 use crate::balance::Valuation;
 use crate::journal::AccName;
+use crate::misc;
 use crate::ntypes::{Basket, QValuable, Quantities, Valuable, Zero};
 use crate::quantity::Quantity;
+use crate::symbol::{Styles, Symbol};
+
+pub use accounts::print as accounts;
+pub use balance::BalanceLayout;
 pub use balance::TotalMode;
 pub use balance::print as bal;
+pub use budget::print as budget;
+pub use commodities::print as commodities;
+pub use gains::print as gains;
 pub use info::print as info;
+pub use lots::print as lots;
+pub use payees::print as payees;
 pub use print::print as prnt;
+pub use print::print_xact;
+pub use register::RegisterLayout;
 pub use register::print as reg;
 
+/// When the TTY reports are colored.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Only when stdout is a terminal.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Date format of the TTY reports when none is set.
+pub const DEFAULT_DATE_FORMAT: &str = "%Y/%m/%d";
+
+/// Whether `fmt` is a strftime format chrono can render for a date.
+/// Time fields like `%H` are rejected.
+pub fn is_valid_date_format(fmt: &str) -> bool {
+    use std::fmt::Write;
+
+    if StrftimeItems::new(fmt).any(|i| matches!(i, Item::Error)) {
+        return false;
+    }
+    let mut out = String::new();
+    write!(out, "{}", NaiveDate::MIN.format(fmt)).is_ok()
+}
+
+/// Columns the pretty Lisp output tries to fit its lines in.
+pub const LISP_WIDTH: usize = 80;
+
+/// How a report is written, beyond its format. Every report `print`
+/// takes one, so nothing of it outlives the report.
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// When the TTY reports are colored.
+    pub color: ColorChoice,
+    /// Whether the TTY reports write negative amounts in parentheses,
+    /// accounting style (`($ 100.00)`).
+    pub parens: bool,
+    /// strftime format of the dates in the TTY and HTML reports. Must
+    /// be valid, see [`is_valid_date_format`].
+    pub date_format: String,
+    /// Whether the Lisp output is indented, one element per line when
+    /// a list doesn't fit in [`LISP_WIDTH`] columns, instead of a
+    /// single line.
+    pub lisp_pretty: bool,
+    /// How the amounts of each commodity are written, usually the
+    /// styles of the journal the report comes from.
+    pub styles: Styles,
+    /// Decimal places kept on the gains the reports compute, see
+    /// [`PriceDB::set_valuation_precision`](crate::pricedb::PriceDB::set_valuation_precision).
+    pub valuation_precision: u32,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            color: ColorChoice::default(),
+            parens: false,
+            date_format: DEFAULT_DATE_FORMAT.to_owned(),
+            lisp_pretty: false,
+            styles: Styles::default(),
+            valuation_precision: misc::DEFAULT_VALUATION_PRECISION,
+        }
+    }
+}
+
+impl Options {
+    /// Formats `d` with [`Options::date_format`].
+    fn date_text(&self, d: NaiveDate) -> String {
+        d.format(&self.date_format).to_string()
+    }
+
+    /// Formats `q` for the TTY reports with the display precision of
+    /// its commodity, in parentheses when negative and
+    /// [`Options::parens`] is on. An amount that rounds to zero at
+    /// that precision is never in parentheses.
+    fn qty_text(&self, q: &Quantity) -> String {
+        let prec = self.styles.precision(q.s);
+        let shown =
+            q.q.round_dp_with_strategy(prec as u32, RoundingStrategy::MidpointAwayFromZero);
+        if shown < Decimal::ZERO && self.parens {
+            format!("({:.*})", prec, (-*q).display(&self.styles))
+        } else {
+            format!("{:.*}", prec, q.display(&self.styles))
+        }
+    }
+
+    /// Text of a zero balance. It has no commodity, so it is written
+    /// as the amounts without one are, with no decimals.
+    fn zero_text(&self) -> String {
+        self.styles
+            .get(Symbol::new(""))
+            .format
+            .format(Decimal::ZERO, 0)
+    }
+
+    /// Returns an empty table styled according to [`Options::color`].
+    fn new_table(&self) -> Table {
+        let mut table = Table::new();
+        match self.color {
+            ColorChoice::Always => {
+                table.enforce_styling();
+            }
+            ColorChoice::Never => {
+                table.force_no_tty();
+            }
+            ColorChoice::Auto => {}
+        }
+        table
+    }
+
+    /// Renders `styled`, colored or not as [`Options::color`] says.
+    fn paint<D: std::fmt::Display>(&self, styled: console::StyledObject<D>) -> String {
+        match self.color {
+            ColorChoice::Always => styled.force_styling(true),
+            ColorChoice::Never => styled.force_styling(false),
+            ColorChoice::Auto => styled,
+        }
+        .to_string()
+    }
+
+    /// `doc` as an S-expression, indented if [`Options::lisp_pretty`]
+    /// is on.
+    fn lisp_text(&self, doc: &impl serde::Serialize) -> serde_lexpr::Result<String> {
+        if self.lisp_pretty {
+            Ok(lisp_pretty(&serde_lexpr::to_value(doc)?))
+        } else {
+            serde_lexpr::to_string(doc)
+        }
+    }
+}
+
+/// Takes a closed output, e.g. the report piped into `head`, as a
+/// successful write.
+fn ignore_broken_pipe(res: std::io::Result<()>) -> std::io::Result<()> {
+    match res {
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        res => res,
+    }
+}
+
+/// Writes `v` over several lines: a list that fits in the rest of the
+/// line is kept whole, otherwise its elements go one per line,
+/// indented one column past its opening parenthesis. Only whitespace
+/// differs from the compact form, so both read back the same.
+pub fn lisp_pretty(v: &serde_lexpr::Value) -> String {
+    let mut out = String::new();
+    write_lisp_pretty(&mut out, v, 0);
+    out
+}
+
+fn write_lisp_pretty(out: &mut String, v: &serde_lexpr::Value, indent: usize) {
+    let compact = v.to_string();
+    let Some(cons) = v.as_cons() else {
+        out.push_str(&compact);
+        return;
+    };
+    if indent + compact.len() <= LISP_WIDTH {
+        out.push_str(&compact);
+        return;
+    }
+
+    let pad = " ".repeat(indent + 1);
+    out.push('(');
+    write_lisp_pretty(out, cons.car(), indent + 1);
+    let mut rest = cons.cdr();
+    loop {
+        match rest.as_cons() {
+            Some(cons) => {
+                out.push('\n');
+                out.push_str(&pad);
+                write_lisp_pretty(out, cons.car(), indent + 1);
+                rest = cons.cdr();
+            }
+            None if rest.is_null() => break,
+            None => {
+                out.push('\n');
+                out.push_str(&pad);
+                out.push_str(". ");
+                write_lisp_pretty(out, rest, indent + 3);
+                break;
+            }
+        }
+    }
+    out.push(')');
+}
+
 /// Wire format for the atom types (`Symbol`, `AccName`, `Quantity`,
 /// `Amount`).
 mod prims {
@@ -26,10 +227,9 @@ mod prims {
 
     use crate::amount::Amount;
     use crate::journal::AccName;
-    use crate::ntypes::Quantities;
+    use crate::ntypes::{Basket, Quantities};
     use crate::quantity::Quantity;
     use crate::symbol::Symbol;
-    use std::collections::BTreeMap;
 
     impl Serialize for Symbol {
         fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
@@ -102,13 +302,11 @@ mod prims {
 
     impl Serialize for Amount {
         fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
-            // Sort by symbol name so JSON/Lisp output is deterministic
-            // (Amount stores quantities in a HashMap internally).
-            let sorted: BTreeMap<String, _> =
-                self.quantities().map(|q| (q.s.name(), q.q)).collect();
-            let mut map = ser.serialize_map(Some(sorted.len()))?;
-            for (s, q) in &sorted {
-                map.serialize_entry(s, q)?;
+            // quantities come sorted by symbol name, so JSON/Lisp
+            // output is deterministic
+            let mut map = ser.serialize_map(Some(self.arity()))?;
+            for q in self.quantities() {
+                map.serialize_entry(&q.s.name(), &q.q)?;
             }
             map.end()
         }
@@ -170,7 +368,11 @@ mod prims {
 pub enum Fmt {
     Tty,
     Json,
+    /// One JSON document per line, written as the report is produced.
+    JsonLines,
     Lisp,
+    Html,
+    Csv,
 }
 
 /// Schema selector for the `schema` subcommand. Each variant maps 1:1
@@ -235,9 +437,11 @@ pub fn schema(mut out: impl std::io::Write, which: Option<Schema>) -> Result<(),
 pub mod balance {
     use std::io::{self, Write};
 
+    use chrono::NaiveDate;
+
     use super::*;
     use crate::account_view::{AccountView, ValuebleAccountView};
-    use crate::balance_view::BalanceView;
+    use crate::balance_view::{AccountOrder, BalanceView};
     use crate::holdings::Holdings;
     use crate::ntypes::{QValuable, TsBasket, Zero};
     use crate::tamount::TAmount;
@@ -253,6 +457,17 @@ pub mod balance {
         OnlyTotal,
     }
 
+    /// Optional parts of the tty balance layout. Other formats ignore
+    /// them.
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct BalanceLayout {
+        /// `--date-header`: a header row with the dates of the columns.
+        pub date_header: bool,
+        /// `--percent`: each amount followed by its share of the
+        /// parent's balance (of the grand total for root accounts).
+        pub percent: bool,
+    }
+
     impl TotalMode {
         pub fn show_tables(&self) -> bool {
             matches!(self, TotalMode::Full | TotalMode::NoTotal)
@@ -275,7 +490,7 @@ pub mod balance {
 
         use crate::account_view::AccountView;
         use crate::amount::Amount;
-        use crate::balance_view::BalanceView;
+        use crate::balance_view::{AccountOrder, BalanceView};
         use crate::holdings::{AvgPosition, Holdings};
         use crate::journal::AccName;
         use crate::ntypes::TsBasket;
@@ -304,6 +519,7 @@ pub mod balance {
             ///   intermediate single-child parents were collapsed into it
             ///   (e.g. a parent `Assets` may have a child named
             ///   `Provida:Dos` if `Assets:Provida` had only one child).
+            ///
             /// To reconstruct the full path under compact mode, concatenate
             /// the parent's full path with `:` and this `name`.
             pub name: &'a AccName,
@@ -399,9 +615,14 @@ pub mod balance {
             {
                 BalanceViewWired {
                     balance: total_mode.show_total().then(|| raw_balance(total)),
-                    accounts: total_mode
-                        .show_tables()
-                        .then(|| view.accounts().map(raw_account).collect()),
+                    accounts: total_mode.show_tables().then(|| {
+                        let order = view.order();
+                        order
+                            .sort(view.accounts())
+                            .into_iter()
+                            .map(|a| raw_account(a, order))
+                            .collect()
+                    }),
                 }
             }
 
@@ -417,9 +638,14 @@ pub mod balance {
             {
                 BalanceViewWired {
                     balance: total_mode.show_total().then(|| valued_balance(total)),
-                    accounts: total_mode
-                        .show_tables()
-                        .then(|| view.accounts().map(valued_account).collect()),
+                    accounts: total_mode.show_tables().then(|| {
+                        let order = view.order();
+                        order
+                            .sort(view.accounts())
+                            .into_iter()
+                            .map(|a| valued_account(a, order))
+                            .collect()
+                    }),
                 }
             }
         }
@@ -432,14 +658,18 @@ pub mod balance {
             )
         }
 
-        fn raw_account<T>(acc: &T) -> AccountWire<'_>
+        fn raw_account<T>(acc: &T, order: AccountOrder) -> AccountWire<'_>
         where
             T: AccountView<TsValue = TAmount<Holdings>>,
         {
             AccountWire {
                 name: acc.name(),
                 balance: raw_balance(acc.balance()),
-                sub_account: acc.sub_accounts().map(raw_account).collect(),
+                sub_account: order
+                    .sort(acc.sub_accounts())
+                    .into_iter()
+                    .map(|a| raw_account(a, order))
+                    .collect(),
             }
         }
 
@@ -451,7 +681,7 @@ pub mod balance {
             )
         }
 
-        fn valued_account<T>(acc: &T) -> AccountWire<'_>
+        fn valued_account<T>(acc: &T, order: AccountOrder) -> AccountWire<'_>
         where
             T: AccountView,
             T::TsValue: TsBasket<B = Amount>,
@@ -459,43 +689,91 @@ pub mod balance {
             AccountWire {
                 name: acc.name(),
                 balance: valued_balance(acc.balance()),
-                sub_account: acc.sub_accounts().map(valued_account).collect(),
+                sub_account: order
+                    .sort(acc.sub_accounts())
+                    .into_iter()
+                    .map(|a| valued_account(a, order))
+                    .collect(),
             }
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn print<T>(
         out: impl Write,
         balance: &BalanceView<T>,
         total_mode: TotalMode,
         show_detail: Option<Valuation>,
-        date_header: bool,
+        layout: BalanceLayout,
+        v: Valuation,
+        fmt: Fmt,
+        opts: &Options,
+    ) -> io::Result<()>
+    where
+        T: ValuebleAccountView<TsValue = TAmount<Holdings>> + Clone,
+    {
+        ignore_broken_pipe(print_fmt(
+            out,
+            balance,
+            total_mode,
+            show_detail,
+            layout,
+            v,
+            fmt,
+            opts,
+        ))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn print_fmt<T>(
+        out: impl Write,
+        balance: &BalanceView<T>,
+        total_mode: TotalMode,
+        show_detail: Option<Valuation>,
+        layout: BalanceLayout,
         v: Valuation,
         fmt: Fmt,
+        opts: &Options,
     ) -> io::Result<()>
     where
-        T: ValuebleAccountView<TsValue = TAmount<Holdings>>,
+        T: ValuebleAccountView<TsValue = TAmount<Holdings>> + Clone,
     {
         if let Fmt::Tty = fmt {
-            return print_tty(out, balance, total_mode, show_detail, date_header, v);
+            return print_tty(out, balance, total_mode, show_detail, layout, v, opts);
+        }
+        if let Fmt::Html = fmt {
+            return print_html(out, balance, total_mode, v, opts);
+        }
+        if let Fmt::Csv = fmt {
+            return print_csv(out, balance, total_mode, v);
+        }
+        if let Fmt::JsonLines = fmt {
+            return Err(unsupported_jsonl("balance"));
         }
         if show_detail.is_some() {
             let total = balance.balance();
             let doc = wire::BalanceViewWired::from_raw(balance, &total, total_mode);
-            write_doc(out, fmt, &doc)
+            write_doc(out, fmt, &doc, opts)
         } else {
             let valued = balance.valued_in(v);
             let total = valued.balance();
             let doc = wire::BalanceViewWired::from_valued(&valued, &total, total_mode);
-            write_doc(out, fmt, &doc)
+            write_doc(out, fmt, &doc, opts)
         }
     }
 
-    fn write_doc(mut out: impl Write, fmt: Fmt, doc: &impl serde::Serialize) -> io::Result<()> {
+    fn write_doc(
+        mut out: impl Write,
+        fmt: Fmt,
+        doc: &impl serde::Serialize,
+        opts: &Options,
+    ) -> io::Result<()> {
         match fmt {
             Fmt::Json => writeln!(out, "{}", serde_json::to_string(doc)?),
-            Fmt::Lisp => writeln!(out, "{}", serde_lexpr::to_string(doc)?),
-            Fmt::Tty => unreachable!("tty handled before dispatch"),
+            Fmt::Lisp => writeln!(out, "{}", opts.lisp_text(doc)?),
+            Fmt::Tty | Fmt::Html | Fmt::Csv | Fmt::JsonLines => {
+                unreachable!("tty, html, csv and jsonl handled before dispatch")
+            }
         }
     }
 
@@ -504,8 +782,9 @@ pub mod balance {
         balance: &BalanceView<T>,
         total_mode: TotalMode,
         show_detail: Option<Valuation>,
-        date_header: bool,
+        layout: BalanceLayout,
         v: Valuation,
+        opts: &Options,
     ) -> io::Result<()>
     where
         V: TsBasket<B: Valuable + QValuable>,
@@ -516,7 +795,7 @@ pub mod balance {
             .balance()
             .iter_baskets()
             .map(|(d, _)| {
-                Cell::new(d)
+                Cell::new(opts.date_text(d))
                     .add_attribute(Attribute::Bold)
                     .set_alignment(CellAlignment::Right)
             })
@@ -524,9 +803,9 @@ pub mod balance {
 
         let width = header.len();
 
-        let mut table = Table::new();
+        let mut table = opts.new_table();
         table.load_preset(presets::NOTHING);
-        if date_header {
+        if layout.date_header {
             table.set_header(header);
             table.add_row(vec![
                 Cell::new("---------------------")
@@ -537,8 +816,12 @@ pub mod balance {
         }
 
         if total_mode.show_tables() {
-            for p in balance.accounts() {
-                print_account_bal(&mut table, p, v, 0, width);
+            let order = balance.order();
+            let total = layout
+                .percent
+                .then(|| valued_baskets(&balance.balance(), v));
+            for p in order.sort(balance.accounts()) {
+                print_account_bal(&mut table, p, v, order, total.as_ref(), 0, width, opts);
             }
         }
 
@@ -555,7 +838,7 @@ pub mod balance {
             let mut vtot = vec![Cell::new(""); width];
             let tot = balance.balance();
             for (w, a) in tot.iter_baskets().map(|(_, amount)| amount).enumerate() {
-                vtot[w] = amount2(a, v, show_detail, CellAlignment::Right, 0)
+                vtot[w] = amount2(a, v, show_detail, CellAlignment::Right, 0, opts)
                     .add_attribute(Attribute::Bold);
             }
             table.add_row(vtot);
@@ -564,12 +847,161 @@ pub mod balance {
         writeln!(out, "{}", table)
     }
 
+    /// Writes the report as an HTML `<table>`. Each account is a row
+    /// whose `depth-N` class carries its nesting level, amounts hold
+    /// one `<div>` per commodity, and the total goes in `<tfoot>`.
+    /// Annotations (`--annotate`) are not rendered.
+    fn print_html<V, T>(
+        mut out: impl Write,
+        balance: &BalanceView<T>,
+        total_mode: TotalMode,
+        v: Valuation,
+        opts: &Options,
+    ) -> io::Result<()>
+    where
+        V: TsBasket<B: Valuable>,
+        T: ValuebleAccountView<TsValue = V>,
+    {
+        writeln!(out, "<table class=\"balance\">")?;
+        writeln!(out, "<thead>")?;
+        write!(out, "<tr>")?;
+        for (d, _) in balance.balance().iter_baskets() {
+            write!(out, "<th>{}</th>", opts.date_text(d))?;
+        }
+        writeln!(out, "<th>Account</th></tr>")?;
+        writeln!(out, "</thead>")?;
+
+        if total_mode.show_tables() {
+            writeln!(out, "<tbody>")?;
+            let order = balance.order();
+            for p in order.sort(balance.accounts()) {
+                print_account_html(&mut out, p, v, order, 0, opts)?;
+            }
+            writeln!(out, "</tbody>")?;
+        }
+
+        if total_mode.show_total() {
+            writeln!(out, "<tfoot>")?;
+            write!(out, "<tr class=\"total\">")?;
+            for (_, a) in balance.balance().iter_baskets() {
+                write!(out, "{}", html_amount(&a.valued_in(v), opts))?;
+            }
+            writeln!(out, "<td></td></tr>")?;
+            writeln!(out, "</tfoot>")?;
+        }
+
+        writeln!(out, "</table>")
+    }
+
+    /// `account,amount,commodity` rows, one per account and commodity,
+    /// commodities in name order. The report is written flat, each
+    /// account with its full name and its own amounts only, so the rows
+    /// add up to the total. The total is a last group of rows named
+    /// `Total`.
+    ///
+    /// A report over several dates (`--at` given more than once, or a
+    /// period) gets a leading `date` column and a group of rows per
+    /// date, so the rows of a date add up to its total.
+    fn print_csv<T>(
+        mut out: impl Write,
+        balance: &BalanceView<T>,
+        total_mode: TotalMode,
+        v: Valuation,
+    ) -> io::Result<()>
+    where
+        T: ValuebleAccountView<TsValue = TAmount<Holdings>> + Clone,
+    {
+        let dated = balance.balance().iter_baskets().nth(1).is_some();
+        if dated {
+            write!(out, "date,")?;
+        }
+        writeln!(out, "account,amount,commodity")?;
+        if total_mode.show_tables() {
+            let flat = balance.clone().to_flat();
+            let order = flat.order();
+            for accnt in order.sort(flat.accounts()) {
+                for (date, basket) in accnt.balance().iter_baskets() {
+                    let name = accnt.name().to_string();
+                    write_csv_rows(&mut out, dated.then_some(date), &name, basket, v)?;
+                }
+            }
+        }
+        if total_mode.show_total() {
+            for (date, basket) in balance.balance().iter_baskets() {
+                write_csv_rows(&mut out, dated.then_some(date), "Total", basket, v)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_csv_rows<B: Valuable>(
+        out: &mut impl Write,
+        date: Option<NaiveDate>,
+        name: &str,
+        basket: &B,
+        v: Valuation,
+    ) -> io::Result<()> {
+        let value = basket.valued_in(v);
+        let mut quantities: Vec<Quantity> = value.quantities().collect();
+        if quantities.is_empty() {
+            quantities.push(Quantity {
+                q: Decimal::ZERO,
+                s: Symbol::new(""),
+            });
+        }
+        for q in quantities {
+            if let Some(date) = date {
+                write!(out, "{date},")?;
+            }
+            writeln!(
+                out,
+                "{},{},{}",
+                csv_field(name),
+                q.q,
+                csv_field(&q.s.name())
+            )?;
+        }
+        Ok(())
+    }
+
+    fn print_account_html<V, T>(
+        out: &mut impl Write,
+        accnt: &T,
+        v: Valuation,
+        order: AccountOrder,
+        depth: usize,
+        opts: &Options,
+    ) -> io::Result<()>
+    where
+        V: TsBasket<B: Valuable>,
+        T: ValuebleAccountView<TsValue = V>,
+    {
+        write!(out, "<tr class=\"depth-{}\">", depth)?;
+        for (_, a) in accnt.balance().iter_baskets() {
+            write!(out, "{}", html_amount(&a.valued_in(v), opts))?;
+        }
+        writeln!(
+            out,
+            "<td class=\"account\">{}</td></tr>",
+            html_escape(accnt.name())
+        )?;
+
+        for sub in order.sort(accnt.sub_accounts()) {
+            print_account_html(out, sub, v, order, depth + 1, opts)?;
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn print_account_bal<V, T>(
         table: &mut Table,
         accnt: &T,
         v: Valuation,
+        order: AccountOrder,
+        parent: Option<&BTreeMap<NaiveDate, Amount>>,
         indent: usize,
         width: usize,
+        opts: &Options,
     ) where
         V: TsBasket<B: Valuable + QValuable>,
         T: ValuebleAccountView<TsValue = V>,
@@ -586,20 +1018,34 @@ pub mod balance {
             .unwrap();
 
         let mut rows = vec![vec![Cell::new(""); width + 1]; heigh];
-        for (w, amount) in accnt_v
-            .balance()
-            .iter_baskets()
-            .map(|(_, amount)| amount)
-            .enumerate()
-        {
+        for (w, (date, amount)) in accnt_v.balance().iter_baskets().enumerate() {
             if amount.is_zero() {
-                rows[0][w] =
-                    Cell::new(format!("{:>20.1}", 0.0)).set_alignment(CellAlignment::Right);
+                rows[0][w] = Cell::new(opts.zero_text()).set_alignment(CellAlignment::Right);
                 continue;
             }
 
+            // Under `--percent` every line carries the share, or N/A
+            // when it is not defined for mixed commodities.
+            let pct =
+                parent.map(
+                    |p| match p.get(&date).and_then(|whole| percent_of(amount, whole)) {
+                        Some(pct) => format!("{}%", pct.round()),
+                        None => "N/A".to_owned(),
+                    },
+                );
             for (h, a) in amount.quantities().enumerate() {
-                rows[h][w] = quantiry(a, CellAlignment::Right);
+                rows[h][w] = match &pct {
+                    Some(pct) => {
+                        let text = format!("{} ({pct})", opts.qty_text(&a));
+                        let cell = if a.q < Decimal::ZERO {
+                            Cell::new(text).fg(Color::DarkRed)
+                        } else {
+                            Cell::new(text)
+                        };
+                        cell.set_alignment(CellAlignment::Right)
+                    }
+                    None => quantiry(a, CellAlignment::Right, opts),
+                };
             }
         }
 
@@ -609,10 +1055,31 @@ pub mod balance {
             table.add_row(row);
         }
 
-        for sub in accnt.sub_accounts() {
-            print_account_bal(table, sub, v, indent + 1, width);
+        let this = parent.map(|_| valued_baskets(accnt.balance(), v));
+        for sub in order.sort(accnt.sub_accounts()) {
+            print_account_bal(table, sub, v, order, this.as_ref(), indent + 1, width, opts);
         }
     }
+
+    /// The baskets of `balance` valued in `v`, by date.
+    fn valued_baskets<V>(balance: &V, v: Valuation) -> BTreeMap<NaiveDate, Amount>
+    where
+        V: TsBasket<B: Valuable>,
+    {
+        balance
+            .iter_baskets()
+            .map(|(d, b)| (d, b.valued_in(v)))
+            .collect()
+    }
+
+    /// `part` as a percentage of `whole`. `None` unless both hold a
+    /// single commodity, the same one, and `whole` is not zero: a share
+    /// of a mixed-commodity balance has no meaning.
+    fn percent_of(part: &Amount, whole: &Amount) -> Option<Decimal> {
+        let part = part.to_quantity()?;
+        let whole = whole.to_quantity()?;
+        (whole.s == part.s && !whole.q.is_zero()).then(|| part.q * Decimal::ONE_HUNDRED / whole.q)
+    }
 }
 
 pub mod register {
@@ -624,13 +1091,54 @@ pub mod register {
     use crate::register::RegisterGroup;
     use crate::register::RegisterRow;
 
+    /// Shape of the register in the structured formats. The `tty`,
+    /// `html` and `csv` reports ignore it.
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct RegisterLayout {
+        /// `--by-xact`: one object per transaction with its tags and
+        /// its rows under `postings` (see [`wire::RegisterXactWire`]).
+        pub by_xact: bool,
+        /// `--reverse`: the transactions come latest first, so the
+        /// `tty` and `html` footers take the total of the first one.
+        pub reverse: bool,
+    }
+
     pub fn print<'a>(
+        out: impl Write,
+        reg: impl Iterator<Item = RegisterGroup<'a>>,
+        fmt: Fmt,
+        layout: RegisterLayout,
+        opts: &Options,
+    ) -> io::Result<()> {
+        ignore_broken_pipe(print_fmt(out, reg, fmt, layout, opts))
+    }
+
+    fn print_fmt<'a>(
         mut out: impl Write,
         reg: impl Iterator<Item = RegisterGroup<'a>>,
         fmt: Fmt,
+        layout: RegisterLayout,
+        opts: &Options,
     ) -> io::Result<()> {
         match fmt {
-            Fmt::Tty => print_tty(out, reg),
+            Fmt::Tty => print_tty(out, reg, layout, opts),
+            Fmt::Json if layout.by_xact => {
+                let groups: Vec<RegisterGroup<'a>> = reg.collect();
+                let doc: Vec<_> = groups.iter().map(wire::RegisterXactWire::from).collect();
+                writeln!(out, "{}", serde_json::to_string(&doc).unwrap())
+            }
+            Fmt::Lisp if layout.by_xact => {
+                let groups: Vec<RegisterGroup<'a>> = reg.collect();
+                let doc: Vec<_> = groups.iter().map(wire::RegisterXactWire::from).collect();
+                writeln!(out, "{}", opts.lisp_text(&doc).unwrap())
+            }
+            Fmt::JsonLines if layout.by_xact => {
+                for g in reg {
+                    let line = serde_json::to_string(&wire::RegisterXactWire::from(&g))?;
+                    writeln!(out, "{line}")?;
+                }
+                out.flush()
+            }
             Fmt::Json => {
                 let groups: Vec<RegisterGroup<'a>> = reg.collect();
                 let doc = wire::RegisterReport::from_groups(&groups);
@@ -639,9 +1147,66 @@ pub mod register {
             Fmt::Lisp => {
                 let groups: Vec<RegisterGroup<'a>> = reg.collect();
                 let doc = wire::RegisterReport::from_groups(&groups);
-                writeln!(out, "{}", serde_lexpr::to_string(&doc).unwrap())
+                writeln!(out, "{}", opts.lisp_text(&doc).unwrap())
+            }
+            Fmt::JsonLines => print_jsonl(out, reg),
+            Fmt::Html => print_html(out, reg, layout, opts),
+            Fmt::Csv => print_csv(out, reg),
+        }
+    }
+
+    /// One line per transaction, each holding the same object as an
+    /// element of the `--fmt json` array. Lines are written as the
+    /// register produces them, so the report is never held in memory.
+    fn print_jsonl<'a>(
+        mut out: impl Write,
+        reg: impl Iterator<Item = RegisterGroup<'a>>,
+    ) -> io::Result<()> {
+        for g in reg {
+            let line = serde_json::to_string(&wire::RegisterGroupWire::from(&g))?;
+            writeln!(out, "{line}")?;
+        }
+        out.flush()
+    }
+
+    /// One row per posting and commodity, with the columns
+    /// `date,payee,account,amount,commodity,running_total`. The
+    /// running total is the one of the row's commodity. The column set
+    /// is stable, scripts can rely on it.
+    fn print_csv<'a>(
+        mut out: impl Write,
+        reg: impl Iterator<Item = RegisterGroup<'a>>,
+    ) -> io::Result<()> {
+        writeln!(out, "date,payee,account,amount,commodity,running_total")?;
+        for r in reg {
+            for row in &r.rows {
+                let mut quantities: Vec<Quantity> = row.total.quantities().collect();
+                if quantities.is_empty() {
+                    quantities.push(Quantity {
+                        q: Decimal::ZERO,
+                        s: Symbol::new(""),
+                    });
+                }
+                for q in quantities {
+                    let running = row
+                        .running_total
+                        .quantities()
+                        .find(|rq| rq.s == q.s)
+                        .map_or(Decimal::ZERO, |rq| rq.q);
+                    writeln!(
+                        out,
+                        "{},{},{},{},{},{}",
+                        r.date,
+                        csv_field(&r.payee),
+                        csv_field(&row.acc_name),
+                        q.q,
+                        csv_field(&q.s.name()),
+                        running
+                    )?;
+                }
             }
         }
+        Ok(())
     }
 
     /// Stable JSON/Lisp shape for the `register` report.
@@ -652,6 +1217,7 @@ pub mod register {
 
         use crate::amount::Amount;
         use crate::journal::AccName;
+        use crate::printing::print::wire::ProvenanceWire;
         use crate::register::{RegisterGroup, RegisterRow};
 
         /// Top-level shape of the `register --fmt json` report.
@@ -690,8 +1256,8 @@ pub mod register {
             fn from(g: &'a RegisterGroup<'a>) -> Self {
                 RegisterGroupWire {
                     xact_id: g.id,
-                    date: g.date,
-                    payee: g.payee,
+                    date: &g.date,
+                    payee: &g.payee,
                     rows: g.rows.iter().map(RegisterRowWire::from).collect(),
                 }
             }
@@ -704,6 +1270,45 @@ pub mod register {
             pub acc_name: &'a AccName,
             pub total: &'a Amount,
             pub running_total: &'a Amount,
+            /// Running average per commodity. Only present under
+            /// `--average`.
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub average: Option<&'a Amount>,
+            /// Where the row comes from when not from an explicit
+            /// posting. Omitted otherwise.
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub provenance: Option<ProvenanceWire>,
+        }
+
+        /// A transaction of the register under `--by-xact`: its rows,
+        /// nested under `postings`, along with its tags.
+        #[derive(Serialize, JsonSchema)]
+        #[schemars(rename = "RegisterXact")]
+        pub struct RegisterXactWire<'a> {
+            /// Numeric id of the transaction.
+            #[serde(rename = "xact-id")]
+            pub xact_id: usize,
+            /// Transaction date.
+            pub date: &'a NaiveDate,
+            /// Transaction payee.
+            pub payee: &'a str,
+            /// Transaction-level tags as flat strings (e.g. `tag` or
+            /// `key:value`).
+            pub tags: Vec<String>,
+            /// Posting rows for this transaction, in display order.
+            pub postings: Vec<RegisterRowWire<'a>>,
+        }
+
+        impl<'a> From<&'a RegisterGroup<'a>> for RegisterXactWire<'a> {
+            fn from(g: &'a RegisterGroup<'a>) -> Self {
+                RegisterXactWire {
+                    xact_id: g.id,
+                    date: &g.date,
+                    payee: &g.payee,
+                    tags: g.tags.iter().map(|t| t.to_string()).collect(),
+                    postings: g.rows.iter().map(RegisterRowWire::from).collect(),
+                }
+            }
         }
 
         impl<'a> From<&'a RegisterRow> for RegisterRowWire<'a> {
@@ -712,16 +1317,80 @@ pub mod register {
                     acc_name: &r.acc_name,
                     total: &r.total,
                     running_total: &r.running_total,
+                    average: r.average.as_ref(),
+                    provenance: r.provenance.into(),
                 }
             }
         }
     }
 
+    /// Writes the report as an HTML `<table>`, one `<tr>` per row. The
+    /// date and payee are only filled in on the first row of each
+    /// transaction; the final running total goes in `<tfoot>`.
+    fn print_html<'a>(
+        mut out: impl Write,
+        reg: impl Iterator<Item = RegisterGroup<'a>>,
+        layout: RegisterLayout,
+        opts: &Options,
+    ) -> io::Result<()> {
+        writeln!(out, "<table class=\"register\">")?;
+        writeln!(out, "<thead>")?;
+        writeln!(
+            out,
+            "<tr><th>Date</th><th>Payee</th><th>Account</th><th>Amount</th><th>RunningTotal</th></tr>"
+        )?;
+        writeln!(out, "</thead>")?;
+        writeln!(out, "<tbody>")?;
+
+        let mut total = None;
+        for r in reg {
+            for (i, row) in r.rows.iter().enumerate() {
+                let (date, payee) = if i == 0 {
+                    (opts.date_text(r.date), html_escape(&r.payee))
+                } else {
+                    (String::new(), String::new())
+                };
+                writeln!(
+                    out,
+                    "<tr><td class=\"date\">{}</td><td class=\"payee\">{}</td><td class=\"account\">{}</td>{}{}</tr>",
+                    date,
+                    payee,
+                    html_escape(&row.acc_name),
+                    html_amount(&row.total, opts),
+                    html_amount(&row.running_total, opts),
+                )?;
+            }
+            if total.is_none() || !layout.reverse {
+                total = r.rows.last().map(|row| row.running_total.clone());
+            }
+        }
+        let total = total.unwrap_or_default();
+
+        writeln!(out, "</tbody>")?;
+        writeln!(out, "<tfoot>")?;
+        writeln!(
+            out,
+            "<tr class=\"total\"><td></td><td></td><td>Total</td><td></td>{}</tr>",
+            html_amount(&total, opts)
+        )?;
+        writeln!(out, "</tfoot>")?;
+        writeln!(out, "</table>")
+    }
+
     fn print_tty<'a>(
         mut out: impl Write,
         reg: impl Iterator<Item = RegisterGroup<'a>>,
+        layout: RegisterLayout,
+        opts: &Options,
     ) -> io::Result<()> {
-        let mut table = Table::new();
+        let mut reg = reg.peekable();
+        let average = reg
+            .peek()
+            .and_then(|g| g.rows.first())
+            .is_some_and(|r| r.average.is_some());
+        let ncols = if average { 7 } else { 6 };
+
+        let mut table = opts.new_table();
         table.load_preset(presets::NOTHING).set_header(
             [
                 "xact-id",
@@ -730,12 +1399,14 @@ pub mod register {
                 "Account",
                 "Amount",
                 "RunningTotal",
-            ]
-            .map(|s| {
-                Cell::new(s)
-                    .add_attribute(Attribute::Bold)
-                    .set_alignment(CellAlignment::Center)
-            }),
+                "Average",
+            ][..ncols]
+                .iter()
+                .map(|s| {
+                    Cell::new(s)
+                        .add_attribute(Attribute::Bold)
+                        .set_alignment(CellAlignment::Center)
+                }),
         );
 
         fn add_row_1(
@@ -744,72 +1415,96 @@ pub mod register {
             date: NaiveDate,
             payee: &str,
             entry: &RegisterRow,
+            opts: &Options,
         ) {
-            table.add_row(vec![
+            let mut row = vec![
                 Cell::new(id).set_alignment(CellAlignment::Right),
-                Cell::new(date.to_string()),
+                Cell::new(opts.date_text(date)),
                 Cell::new(payee),
-                accont_name(&entry.acc_name, 0, CellAlignment::Left),
-                amount(&entry.total, CellAlignment::Right, 0),
-                amount(
-                    &entry.running_total,
-                    CellAlignment::Right,
-                    if entry.total.is_zero() {
-                        0
-                    } else {
-                        &entry.total.arity() - 1
-                    },
-                ),
-            ]);
+            ];
+            row.extend(entry_cells(entry, opts));
+            table.add_row(row);
         }
 
-        fn add_row_2p(table: &mut Table, entry: &RegisterRow) {
-            table.add_row(vec![
-                Cell::new(""),
-                Cell::new(""),
-                Cell::new(""),
+        fn add_row_2p(table: &mut Table, entry: &RegisterRow, opts: &Options) {
+            let mut row = vec![Cell::new(""), Cell::new(""), Cell::new("")];
+            row.extend(entry_cells(entry, opts));
+            table.add_row(row);
+        }
+
+        /// Account, amount, running total and, under `--average`,
+        /// average cells of a row.
+        fn entry_cells(entry: &RegisterRow, opts: &Options) -> Vec<Cell> {
+            let voffset = if entry.total.is_zero() {
+                0
+            } else {
+                &entry.total.arity() - 1
+            };
+            let mut cells = vec![
                 accont_name(&entry.acc_name, 0, CellAlignment::Left),
-                amount(&entry.total, CellAlignment::Right, 0),
-                amount(
-                    &entry.running_total,
-                    CellAlignment::Right,
-                    if entry.total.is_zero() {
-                        0
-                    } else {
-                        &entry.total.arity() - 1
-                    },
-                ),
-            ]);
+                amount(&entry.total, CellAlignment::Right, 0, opts),
+                amount(&entry.running_total, CellAlignment::Right, voffset, opts),
+            ];
+            if let Some(avg) = &entry.average {
+                cells.push(amount(avg, CellAlignment::Right, voffset, opts));
+            }
+            cells
         }
 
+        let mut total = None;
         for r in reg {
             let (row, left_rows) = r.rows.split_first().unwrap();
 
-            add_row_1(&mut table, r.id, *r.date, r.payee, row);
+            add_row_1(&mut table, r.id, r.date, &r.payee, row, opts);
             for row in left_rows {
-                add_row_2p(&mut table, row);
+                add_row_2p(&mut table, row, opts);
+            }
+            if total.is_none() || !layout.reverse {
+                total = r.rows.last().map(|row| row.running_total.clone());
             }
         }
 
-        match writeln!(out, "{}", table) {
-            Ok(_) => Ok(()),
-            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
-            Err(e) => Err(e),
+        // Reconciliation footer: the latest running total, one line
+        // per commodity.
+        if let Some(total) = total {
+            let width = total
+                .quantities()
+                .map(|q| console::measure_text_width(&opts.qty_text(&q)))
+                .max()
+                .unwrap_or(3);
+            let mut sep = vec![Cell::new(""); ncols];
+            sep[5] = Cell::new("-".repeat(width))
+                .add_attribute(Attribute::Bold)
+                .set_alignment(CellAlignment::Right);
+            table.add_row(sep);
+
+            let mut footer = vec![Cell::new(""); ncols];
+            footer[3] = Cell::new("Total").add_attribute(Attribute::Bold);
+            footer[5] =
+                amount(&total, CellAlignment::Right, 0, opts).add_attribute(Attribute::Bold);
+            table.add_row(footer);
         }
+
+        writeln!(out, "{}", table)
     }
 }
 
-/// Returns a `Cell` displaying the account name indented
+/// Returns a `Cell` displaying the account name indented, dimmed
+/// below the top level.
 fn accont_name(n: &AccName, indent: usize, align: CellAlignment) -> Cell {
-    Cell::new(format!("{}{}", "  ".repeat(indent), n))
-        .fg(Color::DarkBlue)
-        .set_alignment(align)
+    let cell = Cell::new(format!("{}{}", "  ".repeat(indent), n)).fg(Color::DarkBlue);
+    let cell = if indent > 0 {
+        cell.add_attribute(Attribute::Dim)
+    } else {
+        cell
+    };
+    cell.set_alignment(align)
 }
 
 /// Returns a `Cell` displaying "{symbol} {value}", colored DarkRed if
 /// `q` is negative.
-fn quantiry(q: Quantity, align: CellAlignment) -> Cell {
-    let text = format!("{:>20.1}", q);
+fn quantiry(q: Quantity, align: CellAlignment, opts: &Options) -> Cell {
+    let text = opts.qty_text(&q);
     let cell = if q.q < Decimal::ZERO {
         Cell::new(text).fg(Color::DarkRed)
     } else {
@@ -819,12 +1514,12 @@ fn quantiry(q: Quantity, align: CellAlignment) -> Cell {
     cell.set_alignment(align)
 }
 
-fn amount<V>(amt: &V, align: CellAlignment, voffset: usize) -> Cell
+fn amount<V>(amt: &V, align: CellAlignment, voffset: usize, opts: &Options) -> Cell
 where
     V: Basket + Valuable,
 {
     let cell = if amt.is_zero() {
-        Cell::new(format!("{:>20.1}", 0.0))
+        Cell::new(opts.zero_text())
     } else {
         Cell::new(
             std::iter::repeat_n(String::new(), voffset)
@@ -834,9 +1529,9 @@ where
                         .collect::<BTreeMap<_, _>>() // to sort for name of commodity
                         .values()
                         .map(|q| {
-                            let qty = format!("{:>20.1}", q);
+                            let qty = opts.qty_text(q);
                             if q.q < Decimal::ZERO {
-                                console::style(qty).red().to_string()
+                                opts.paint(console::style(qty).red())
                             } else {
                                 qty
                             }
@@ -856,6 +1551,7 @@ fn amount2<V>(
     show_detail: Option<Valuation>,
     align: CellAlignment,
     voffset: usize,
+    opts: &Options,
 ) -> Cell
 where
     V: Basket + Valuable + QValuable,
@@ -863,7 +1559,7 @@ where
     let vamt = amt.valued_in(v);
     let cell = if vamt.is_zero() {
         // amt could be non zero, but amt.valuded_in(v) could be
-        Cell::new("0")
+        Cell::new(opts.zero_text())
     } else {
         Cell::new(
             std::iter::repeat_n(String::new(), voffset)
@@ -873,9 +1569,9 @@ where
                         .collect::<BTreeMap<_, _>>() // to sort for name of commodity
                         .values()
                         .map(|q| {
-                            let qty = format!("{:>20.1}", q);
+                            let qty = opts.qty_text(q);
                             let qty = if q.q < Decimal::ZERO {
-                                console::style(qty).red().to_string()
+                                opts.paint(console::style(qty).red())
                             } else {
                                 qty
                             };
@@ -893,9 +1589,9 @@ where
 
                                     g *= Decimal::from(100);
                                     if g > Decimal::ZERO {
-                                        console::style(format!("+{:.1}%", g)).green().to_string()
+                                        opts.paint(console::style(format!("+{:.1}%", g)).green())
                                     } else if g < Decimal::ZERO {
-                                        console::style(format!("{:.1}%", g)).red().to_string()
+                                        opts.paint(console::style(format!("{:.1}%", g)).red())
                                     } else {
                                         " 0.00%".to_string()
                                     }
@@ -906,13 +1602,18 @@ where
                                 .svalued_in(q.s, pv)
                                 .quantities()
                                 .filter(|b| b.s != q.s)
-                                .map(|b| format!("{:>20.1}", b))
+                                .map(|b| opts.qty_text(&b))
                                 .collect::<Vec<_>>()
                                 .join(", ");
 
-                            let price = console::style(price).true_color(128, 128, 128).to_string();
+                            let price = opts.paint(console::style(price).true_color(128, 128, 128));
 
-                            format!("{:>18} {:<5} {:>20}", price, gain, qty)
+                            format!(
+                                "{} {} {}",
+                                pad(&price, 18, Alignment::Right),
+                                pad(&gain, 5, Alignment::Left),
+                                pad(&qty, 20, Alignment::Right)
+                            )
                         }),
                 )
                 .collect::<Vec<_>>()
@@ -923,13 +1624,133 @@ where
     cell.set_alignment(align)
 }
 
+/// Error returned by reports that have no HTML rendering.
+fn unsupported_html(report: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("--fmt html is not supported by {report}"),
+    )
+}
+
+/// Error returned by reports that have no JSON Lines rendering.
+fn unsupported_jsonl(report: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("--fmt jsonl is not supported by {report}"),
+    )
+}
+
+/// Error returned by reports that have no JSON rendering.
+fn unsupported_json(report: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("--fmt json is not supported by {report}"),
+    )
+}
+
+/// Error returned by reports that have no Lisp rendering.
+fn unsupported_lisp(report: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("--fmt lisp is not supported by {report}"),
+    )
+}
+
+/// Error returned by reports that have no CSV rendering.
+fn unsupported_csv(report: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("--fmt csv is not supported by {report}"),
+    )
+}
+
+/// Quotes a CSV field as RFC 4180 asks: fields holding a comma, a
+/// double quote or a line break are enclosed in double quotes, with
+/// the inner quotes doubled.
+fn csv_field(s: &str) -> std::borrow::Cow<'_, str> {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\"")).into()
+    } else {
+        s.into()
+    }
+}
+
+/// Escapes the characters with special meaning in HTML text and
+/// attribute values.
+fn html_escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&#39;"),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// Returns a `<td>` holding one `<div>` per commodity of `amt`, sorted
+/// by commodity name. Negative quantities get the `negative` class,
+/// and so does the cell when any of them is negative.
+fn html_amount(amt: &Amount, opts: &Options) -> String {
+    if amt.is_zero() {
+        return format!("<td class=\"amount\"><div>{}</div></td>", opts.zero_text());
+    }
+
+    let qs = amt
+        .quantities()
+        .map(|q| (format!("{}", q.s), q))
+        .collect::<BTreeMap<_, _>>(); // to sort for name of commodity
+
+    let divs = qs
+        .values()
+        .map(|q| {
+            let class = if q.q < Decimal::ZERO {
+                " class=\"negative\""
+            } else {
+                ""
+            };
+            format!(
+                "<div{}>{}</div>",
+                class,
+                html_escape(&format!(
+                    "{:.*}",
+                    opts.styles.precision(q.s),
+                    q.display(&opts.styles)
+                ))
+            )
+        })
+        .collect::<String>();
+
+    if qs.values().any(|q| q.q < Decimal::ZERO) {
+        format!("<td class=\"amount negative\">{}</td>", divs)
+    } else {
+        format!("<td class=\"amount\">{}</td>", divs)
+    }
+}
+
+/// Pads `s` to `width` terminal columns. Unlike `format!("{:>w$}")`,
+/// this measures display width, so ANSI styling and commodities like
+/// `€` or `円` don't throw off column alignment.
+fn pad(s: &str, width: usize, align: Alignment) -> String {
+    console::pad_str(s, width, align, None).into_owned()
+}
+
 pub mod info {
     use std::io::{self, Write};
 
     use super::*;
     use crate::info::JnlInfo;
 
-    pub fn print(mut out: impl Write, report: &JnlInfo, fmt: Fmt) -> io::Result<()> {
+    pub fn print(
+        mut out: impl Write,
+        report: &JnlInfo,
+        fmt: Fmt,
+        opts: &Options,
+    ) -> io::Result<()> {
         match fmt {
             Fmt::Json => {
                 let doc = wire::InfoReport::from(report);
@@ -937,9 +1758,12 @@ pub mod info {
             }
             Fmt::Lisp => {
                 let doc = wire::InfoReport::from(report);
-                writeln!(out, "{}", serde_lexpr::to_string(&doc).unwrap())
+                writeln!(out, "{}", opts.lisp_text(&doc).unwrap())
             }
             Fmt::Tty => print_tty(out, report),
+            Fmt::Html => Err(unsupported_html("info")),
+            Fmt::Csv => Err(unsupported_csv("info")),
+            Fmt::JsonLines => Err(unsupported_jsonl("info")),
         }
     }
 
@@ -992,12 +1816,12 @@ pub mod info {
         for acc in &report.accounts {
             writeln!(out, "  {acc}")?;
         }
-        writeln!(out, "")?;
+        writeln!(out)?;
         writeln!(out, "Commodities:")?;
         for c in &report.commodities {
             writeln!(out, "  {c}")?;
         }
-        writeln!(out, "")?;
+        writeln!(out)?;
         writeln!(out, "Payees:")?;
         for p in &report.payees {
             writeln!(out, "  {p}")?;
@@ -1006,11 +1830,299 @@ pub mod info {
     }
 }
 
+pub mod accounts {
+    use std::io::{self, Write};
+
+    use super::*;
+    use crate::journal::AccName;
+
+    /// Writes the account names one per line (tty), or as a list of
+    /// strings (json, lisp).
+    pub fn print(
+        mut out: impl Write,
+        names: &[AccName],
+        fmt: Fmt,
+        opts: &Options,
+    ) -> io::Result<()> {
+        match fmt {
+            Fmt::Tty => {
+                for name in names {
+                    writeln!(out, "{}", name)?;
+                }
+                Ok(())
+            }
+            Fmt::Json => writeln!(out, "{}", serde_json::to_string(names)?),
+            Fmt::Lisp => writeln!(out, "{}", opts.lisp_text(&names)?),
+            Fmt::Html => Err(unsupported_html("accounts")),
+            Fmt::Csv => Err(unsupported_csv("accounts")),
+            Fmt::JsonLines => Err(unsupported_jsonl("accounts")),
+        }
+    }
+}
+
+/// Writes a list of names, preceded by their count when `count` is
+/// set. In json and lisp a counted row is `{"name": .., "count": ..}`.
+fn print_counted(
+    mut out: impl std::io::Write,
+    rows: &[(String, usize)],
+    count: bool,
+    fmt: Fmt,
+    report: &str,
+    opts: &Options,
+) -> std::io::Result<()> {
+    #[derive(serde::Serialize)]
+    struct Counted<'a> {
+        name: &'a str,
+        count: usize,
+    }
+
+    let counted = || -> Vec<Counted> {
+        rows.iter()
+            .map(|(name, count)| Counted {
+                name,
+                count: *count,
+            })
+            .collect()
+    };
+    let names = || -> Vec<&str> { rows.iter().map(|(name, _)| name.as_str()).collect() };
+
+    match (fmt, count) {
+        (Fmt::Tty, false) => {
+            for (name, _) in rows {
+                writeln!(out, "{}", name)?;
+            }
+            Ok(())
+        }
+        (Fmt::Tty, true) => {
+            let width = rows
+                .iter()
+                .map(|(_, c)| c.to_string().len())
+                .max()
+                .unwrap_or(0);
+            for (name, c) in rows {
+                writeln!(out, "{:>width$} {}", c, name)?;
+            }
+            Ok(())
+        }
+        (Fmt::Json, false) => writeln!(out, "{}", serde_json::to_string(&names())?),
+        (Fmt::Json, true) => writeln!(out, "{}", serde_json::to_string(&counted())?),
+        (Fmt::Lisp, false) => writeln!(out, "{}", opts.lisp_text(&names())?),
+        (Fmt::Lisp, true) => writeln!(out, "{}", opts.lisp_text(&counted())?),
+        (Fmt::Html, _) => Err(unsupported_html(report)),
+        (Fmt::Csv, _) => Err(unsupported_csv(report)),
+        (Fmt::JsonLines, _) => Err(unsupported_jsonl(report)),
+    }
+}
+
+pub mod commodities {
+    use std::io::{self, Write};
+
+    use super::*;
+    use crate::symbol::Symbol;
+
+    /// Writes the commodities one per line, with the number of
+    /// postings that use each one when `count` is set.
+    pub fn print(
+        out: impl Write,
+        rows: &[(Symbol, usize)],
+        count: bool,
+        fmt: Fmt,
+        opts: &Options,
+    ) -> io::Result<()> {
+        let rows: Vec<_> = rows.iter().map(|(s, c)| (s.name(), *c)).collect();
+        print_counted(out, &rows, count, fmt, "commodities", opts)
+    }
+}
+
+pub mod payees {
+    use std::io::{self, Write};
+
+    use super::*;
+
+    /// Writes the payees one per line, with the number of transactions
+    /// of each one when `count` is set.
+    pub fn print(
+        out: impl Write,
+        rows: &[(String, usize)],
+        count: bool,
+        fmt: Fmt,
+        opts: &Options,
+    ) -> io::Result<()> {
+        print_counted(out, rows, count, fmt, "payees", opts)
+    }
+}
+
+pub mod lots {
+    use std::io::{self, Write};
+
+    use super::*;
+    use crate::lots::Lot;
+
+    pub fn print(out: impl Write, lots: &[Lot], fmt: Fmt, opts: &Options) -> io::Result<()> {
+        match fmt {
+            Fmt::Tty => print_tty(out, lots, opts),
+            Fmt::Html => Err(unsupported_html("balance --lots")),
+            Fmt::Csv => Err(unsupported_csv("balance --lots")),
+            Fmt::JsonLines => Err(unsupported_jsonl("balance --lots")),
+            Fmt::Json => Err(unsupported_json("balance --lots")),
+            Fmt::Lisp => Err(unsupported_lisp("balance --lots")),
+        }
+    }
+
+    fn print_tty(mut out: impl Write, lots: &[Lot], opts: &Options) -> io::Result<()> {
+        let mut table = opts.new_table();
+        table.load_preset(presets::NOTHING).set_header(
+            ["Account", "Quantity", "Cost", "Market", "Gain"].map(|s| {
+                Cell::new(s)
+                    .add_attribute(Attribute::Bold)
+                    .set_alignment(CellAlignment::Center)
+            }),
+        );
+
+        for lot in lots {
+            let (market, gain) = match (lot.market, lot.gain(opts.valuation_precision)) {
+                (Some(m), Some(g)) => (
+                    quantiry(m, CellAlignment::Right, opts),
+                    quantiry(g, CellAlignment::Right, opts),
+                ),
+                // cost only, there is nothing to compare it with
+                _ => (
+                    Cell::new("no price").set_alignment(CellAlignment::Right),
+                    Cell::new(""),
+                ),
+            };
+
+            table.add_row(vec![
+                accont_name(&lot.acc_name, 0, CellAlignment::Left),
+                quantiry(lot.qty, CellAlignment::Right, opts),
+                quantiry(lot.cost, CellAlignment::Right, opts),
+                market,
+                gain,
+            ]);
+        }
+
+        match writeln!(out, "{}", table) {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+pub mod budget {
+    use std::io::{self, Write};
+
+    use super::*;
+    use crate::budget::BudgetRow;
+
+    pub fn print(out: impl Write, rows: &[BudgetRow], fmt: Fmt, opts: &Options) -> io::Result<()> {
+        match fmt {
+            Fmt::Tty => print_tty(out, rows, opts),
+            Fmt::Html => Err(unsupported_html("budget")),
+            Fmt::Csv => Err(unsupported_csv("budget")),
+            Fmt::JsonLines => Err(unsupported_jsonl("budget")),
+            Fmt::Json | Fmt::Lisp => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "budget only supports --fmt tty",
+            )),
+        }
+    }
+
+    fn print_tty(mut out: impl Write, rows: &[BudgetRow], opts: &Options) -> io::Result<()> {
+        let mut table = opts.new_table();
+        table.load_preset(presets::NOTHING).set_header(
+            ["Period", "Account", "Budgeted", "Actual", "Remaining"].map(|s| {
+                Cell::new(s)
+                    .add_attribute(Attribute::Bold)
+                    .set_alignment(CellAlignment::Center)
+            }),
+        );
+
+        for r in rows {
+            table.add_row(vec![
+                Cell::new(opts.date_text(r.period)),
+                accont_name(&r.acc_name, 0, CellAlignment::Left),
+                quantiry(r.budgeted, CellAlignment::Right, opts),
+                quantiry(r.actual, CellAlignment::Right, opts),
+                quantiry(r.remaining(), CellAlignment::Right, opts),
+            ]);
+        }
+
+        match writeln!(out, "{}", table) {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+pub mod gains {
+    use std::io::{self, Write};
+
+    use super::*;
+    use crate::gains::Realized;
+
+    pub fn print(
+        out: impl Write,
+        realized: &[Realized],
+        fmt: Fmt,
+        opts: &Options,
+    ) -> io::Result<()> {
+        match fmt {
+            Fmt::Tty => print_tty(out, realized, opts),
+            Fmt::Html => Err(unsupported_html("gains")),
+            Fmt::Csv => Err(unsupported_csv("gains")),
+            Fmt::JsonLines => Err(unsupported_jsonl("gains")),
+            Fmt::Json | Fmt::Lisp => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "gains only supports --fmt tty",
+            )),
+        }
+    }
+
+    fn print_tty(mut out: impl Write, realized: &[Realized], opts: &Options) -> io::Result<()> {
+        let mut table = opts.new_table();
+        table.load_preset(presets::NOTHING).set_header(
+            [
+                "Date", "Account", "Quantity", "Acquired", "Cost", "Proceeds", "Gain",
+            ]
+            .map(|s| {
+                Cell::new(s)
+                    .add_attribute(Attribute::Bold)
+                    .set_alignment(CellAlignment::Center)
+            }),
+        );
+
+        for r in realized {
+            let gain = match r.gain(opts.valuation_precision) {
+                Some(g) => quantiry(g, CellAlignment::Right, opts),
+                None => Cell::new(""),
+            };
+            table.add_row(vec![
+                Cell::new(opts.date_text(r.date)),
+                accont_name(&r.acc_name, 0, CellAlignment::Left),
+                quantiry(r.qty, CellAlignment::Right, opts),
+                Cell::new(opts.date_text(r.acquired)),
+                quantiry(r.cost, CellAlignment::Right, opts),
+                quantiry(r.proceeds, CellAlignment::Right, opts),
+                gain,
+            ]);
+        }
+
+        match writeln!(out, "{}", table) {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
 pub mod print {
     use std::io::{self, Write};
 
     use super::*;
-    use crate::journal::{Posting, State, Xact};
+    use crate::journal::{Posting, State, Virtuality, Xact};
+    use crate::pricedb::PriceType;
 
     /// Column at which posting amounts are aligned in the TTY output.
     const AMOUNT_COL: usize = 48;
@@ -1019,33 +2131,45 @@ pub mod print {
         mut out: impl Write,
         xacts: impl Iterator<Item = &'a Xact>,
         fmt: Fmt,
+        opts: &Options,
     ) -> io::Result<()> {
         match fmt {
-            Fmt::Tty => print_tty(out, xacts),
+            Fmt::Tty => print_tty(out, xacts, opts),
             Fmt::Json => {
                 let doc = wire::PrintReport::from_xacts(xacts);
                 writeln!(out, "{}", serde_json::to_string(&doc).unwrap())
             }
             Fmt::Lisp => {
                 let doc = wire::PrintReport::from_xacts(xacts);
-                writeln!(out, "{}", serde_lexpr::to_string(&doc).unwrap())
+                writeln!(out, "{}", opts.lisp_text(&doc).unwrap())
             }
+            Fmt::Html => Err(unsupported_html("print")),
+            Fmt::Csv => Err(unsupported_csv("print")),
+            Fmt::JsonLines => Err(unsupported_jsonl("print")),
         }
     }
 
-    fn print_tty<'a>(mut out: impl Write, xacts: impl Iterator<Item = &'a Xact>) -> io::Result<()> {
+    fn print_tty<'a>(
+        mut out: impl Write,
+        xacts: impl Iterator<Item = &'a Xact>,
+        opts: &Options,
+    ) -> io::Result<()> {
         let mut first = true;
         for x in xacts {
             if !first {
                 writeln!(out)?;
             }
             first = false;
-            write_xact(&mut out, x)?;
+            print_xact(&mut out, x, opts)?;
         }
         Ok(())
     }
 
-    fn write_xact(out: &mut impl Write, x: &Xact) -> io::Result<()> {
+    /// Writes `x` in ledger syntax: the header, then one posting per
+    /// line with amounts aligned at [`AMOUNT_COL`]. Inferred amounts
+    /// and prices are written explicitly, and comments (hence tags) are
+    /// kept, so the output parses back to the same transaction.
+    pub fn print_xact(out: &mut impl Write, x: &Xact, opts: &Options) -> io::Result<()> {
         write!(out, "{}", x.date.txdate)?;
         if let Some(ef) = x.date.efdate {
             write!(out, "={}", ef)?;
@@ -1061,48 +2185,102 @@ pub mod print {
         if !x.payee.is_empty() {
             write!(out, " {}", x.payee)?;
         }
-        if !x.comment.is_empty() {
-            write!(out, "  ; {}", x.comment)?;
-        }
-        writeln!(out)?;
+        write_comment(out, &x.comment, "    ")?;
 
         for p in &x.postings {
-            write_posting(out, p)?;
+            write_posting(out, p, opts)?;
         }
         Ok(())
     }
 
-    fn write_posting(out: &mut impl Write, p: &Posting) -> io::Result<()> {
-        let name = p.acc_name.to_string();
+    /// Ends the current line with the first comment line, if any, and
+    /// writes the remaining ones below it, each indented by `indent`.
+    fn write_comment(out: &mut impl Write, comment: &str, indent: &str) -> io::Result<()> {
+        let mut lines = comment.lines();
+        if let Some(first) = lines.next() {
+            write!(out, "  ; {}", first)?;
+        }
+        writeln!(out)?;
+        for line in lines {
+            writeln!(out, "{}; {}", indent, line)?;
+        }
+        Ok(())
+    }
+
+    fn write_posting(out: &mut impl Write, p: &Posting, opts: &Options) -> io::Result<()> {
+        let mut head = String::from("    ");
+        match p.state {
+            State::Cleared => head.push_str("* "),
+            State::Pending => head.push_str("! "),
+            State::None => {}
+        }
+        match p.virtuality {
+            Virtuality::Real => head.push_str(&p.acc_name),
+            Virtuality::Virtual => head.push_str(&format!("({})", p.acc_name)),
+            Virtuality::BalancedVirtual => head.push_str(&format!("[{}]", p.acc_name)),
+        }
         // The "    " prefix is four spaces (ledger requires postings
         // indented). Pad so the amount starts at AMOUNT_COL.
-        let head_len = 4 + name.len();
+        let head_len = console::measure_text_width(&head);
         let pad = AMOUNT_COL.saturating_sub(head_len).max(2);
-        write!(out, "    {}{}{}", name, " ".repeat(pad), p.quantity)?;
-
-        // Emit `{lot_uprice}` when it carries information not already
-        // expressed by `uprice`.
-        if p.lot_uprice.price != p.uprice {
-            write!(out, " {{{}}}", p.lot_uprice.price)?;
-        }
-
-        // Emit `@ uprice` only when the unit price introduces a new
-        // commodity (e.g. quantity is `10 AAPL` and uprice is in `$`),
-        // since the parser fills uprice with `1 quantity.s` otherwise.
-        if p.uprice.s != p.quantity.s {
-            write!(out, " @ {}", p.uprice)?;
+        // A quantity reduced by a conversion prints as written, its
+        // prices back per unit of it.
+        let (quantity, per_unit) = match p.written {
+            Some(w) => (w, p.quantity.q / w.q),
+            None => (p.quantity, Decimal::ONE),
+        };
+        write!(
+            out,
+            "{}{}{}",
+            head,
+            " ".repeat(pad),
+            quantity.to_ledger_string(&opts.styles)
+        )?;
+
+        // Emit the lot price as written when it carries information
+        // not already expressed by `uprice`.
+        if let Some(total) = p.total_lot_price {
+            write!(out, " {{{{{}}}}}", total.to_ledger_string(&opts.styles))?;
+        } else if p.lot_uprice.price != p.uprice || p.lot_uprice.ptype == PriceType::Static {
+            match p.lot_uprice.ptype {
+                PriceType::Static => write!(
+                    out,
+                    " {{={}}}",
+                    (p.lot_uprice.price * per_unit).to_ledger_string(&opts.styles)
+                )?,
+                PriceType::Floating => write!(
+                    out,
+                    " {{{}}}",
+                    (p.lot_uprice.price * per_unit).to_ledger_string(&opts.styles)
+                )?,
+            }
         }
-
+        // lot annotations go before the price
         if let Some(ld) = p.lot_date {
             write!(out, " [{}]", ld)?;
         }
         if !p.lot_note.is_empty() {
             write!(out, " ({})", p.lot_note)?;
         }
-        if !p.comment.is_empty() {
-            write!(out, "  ; {}", p.comment)?;
+
+        // Emit the price only when it introduces a new commodity (e.g.
+        // quantity is `10 AAPL` and uprice is in `$`), since the parser
+        // fills uprice with `1 quantity.s` otherwise. A total written
+        // with `@@` is kept as such.
+        if let Some(total) = p.total_price {
+            write!(out, " @@ {}", total.to_ledger_string(&opts.styles))?;
+        } else if p.uprice.s != p.quantity.s {
+            write!(
+                out,
+                " @ {}",
+                (p.uprice * per_unit).to_ledger_string(&opts.styles)
+            )?;
+        }
+
+        if let Some(a) = p.assertion {
+            write!(out, " = {}", a.to_ledger_string(&opts.styles))?;
         }
-        writeln!(out)
+        write_comment(out, &p.comment, "        ")
     }
 
     /// Stable JSON/Lisp shape for the `print` report.
@@ -1118,7 +2296,7 @@ pub mod print {
         use schemars::JsonSchema;
         use serde::Serialize;
 
-        use crate::journal::{AccName, Xact};
+        use crate::journal::{AccName, Provenance, Xact};
         use crate::quantity::Quantity;
 
         /// Top-level shape of the `print --fmt json` report.
@@ -1191,6 +2369,10 @@ pub mod print {
         pub struct PostingWire<'a> {
             /// Fully-qualified account name (colon-separated path).
             pub account: &'a AccName,
+            /// `virtual` for `(account)` postings, `balanced_virtual` for
+            /// `[account]` ones. Omitted for real postings.
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub virtuality: Option<VirtualityWire>,
             /// Clearance state **as declared on this posting line** (`*`,
             /// `!`, or none). This is *not* the effective state — no
             /// inheritance from the transaction is applied. To resolve
@@ -1215,12 +2397,23 @@ pub mod print {
             pub comment: &'a str,
             /// Posting-level tags as flat strings.
             pub tags: Vec<String>,
+            /// Where the posting comes from when not written in the
+            /// journal as is. Omitted for explicit postings.
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub provenance: Option<ProvenanceWire>,
         }
 
         impl<'a> From<&'a crate::journal::Posting> for PostingWire<'a> {
             fn from(p: &'a crate::journal::Posting) -> Self {
                 PostingWire {
                     account: &p.acc_name,
+                    virtuality: match p.virtuality {
+                        crate::journal::Virtuality::Real => None,
+                        crate::journal::Virtuality::Virtual => Some(VirtualityWire::Virtual),
+                        crate::journal::Virtuality::BalancedVirtual => {
+                            Some(VirtualityWire::BalancedVirtual)
+                        }
+                    },
                     state: p.state.into(),
                     quantity: p.quantity,
                     uprice: p.uprice,
@@ -1229,6 +2422,7 @@ pub mod print {
                     lot_note: &p.lot_note,
                     comment: &p.comment,
                     tags: p.tags.iter().map(|t| t.to_string()).collect(),
+                    provenance: p.provenance.into(),
                 }
             }
         }
@@ -1246,6 +2440,51 @@ pub mod print {
             Pending,
         }
 
+        /// Kind of a virtual posting.
+        #[derive(Serialize, JsonSchema, Clone, Copy)]
+        #[serde(rename_all = "snake_case")]
+        #[schemars(rename = "Virtuality")]
+        pub enum VirtualityWire {
+            /// `(account)` — left out of the balance check.
+            Virtual,
+            /// `[account]` — balances among the other bracketed postings.
+            BalancedVirtual,
+        }
+
+        /// Where a posting not written as is in the journal comes from.
+        #[derive(Serialize, JsonSchema, Clone, Copy)]
+        #[serde(rename_all = "snake_case")]
+        #[schemars(rename = "Provenance")]
+        pub enum ProvenanceWire {
+            /// Written without an amount, inferred to balance the
+            /// transaction.
+            Inferred,
+            /// Added by an automated transaction (`= EXPR`).
+            Automated,
+            /// Part of a transaction generated from a periodic one
+            /// (`~ PERIOD`).
+            Periodic,
+            /// Opens a balance carried over by the `equity` report.
+            Equity,
+            /// Change in market value of the holdings between two
+            /// dates (the `<Revalued>` rows of `register -V`).
+            Revaluation,
+        }
+
+        /// `None` for explicit postings, which carry no provenance.
+        impl From<Provenance> for Option<ProvenanceWire> {
+            fn from(p: Provenance) -> Self {
+                match p {
+                    Provenance::Explicit => None,
+                    Provenance::Inferred => Some(ProvenanceWire::Inferred),
+                    Provenance::Automated => Some(ProvenanceWire::Automated),
+                    Provenance::Periodic => Some(ProvenanceWire::Periodic),
+                    Provenance::Equity => Some(ProvenanceWire::Equity),
+                    Provenance::Revaluation => Some(ProvenanceWire::Revaluation),
+                }
+            }
+        }
+
         impl From<crate::journal::State> for StateWire {
             fn from(s: crate::journal::State) -> Self {
                 match s {
@@ -1257,3 +2496,139 @@ pub mod print {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::io::{self, Write};
+
+    use super::*;
+    use crate::journal::Provenance;
+    use crate::register::{RegisterGroup, RegisterRow};
+
+    /// A reader that hangs up after `left` bytes.
+    struct ClosesEarly {
+        left: usize,
+    }
+
+    impl Write for ClosesEarly {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.left == 0 {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            let n = buf.len().min(self.left);
+            self.left -= n;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn groups() -> Vec<RegisterGroup<'static>> {
+        let row = || RegisterRow {
+            acc_name: AccName::from("Expenses:Food"),
+            total: Amount::new(),
+            running_total: Amount::new(),
+            average: None,
+            provenance: Provenance::Explicit,
+        };
+        (0..50)
+            .map(|id| RegisterGroup {
+                id,
+                date: NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
+                payee: "Store".into(),
+                tags: &[],
+                rows: vec![row()],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn register_html_into_closed_reader_is_not_an_error() {
+        let out = ClosesEarly { left: 100 };
+        let layout = register::RegisterLayout::default();
+        assert!(
+            register::print(
+                out,
+                groups().into_iter(),
+                Fmt::Html,
+                layout,
+                &Options::default()
+            )
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn other_write_errors_are_reported() {
+        struct Fails;
+        impl Write for Fails {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::PermissionDenied.into())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        let layout = register::RegisterLayout::default();
+        assert!(
+            register::print(
+                Fails,
+                groups().into_iter(),
+                Fmt::Html,
+                layout,
+                &Options::default(),
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn parens_skip_amounts_that_round_to_zero() {
+        use crate::symbol::Style;
+        use rust_decimal::dec;
+
+        let usd = Symbol::new("$");
+        let mut styles = Styles::default();
+        styles.set(
+            usd,
+            Style {
+                places: 2,
+                suffix: false,
+                format: crate::parser_number::NumberFormat::Us,
+            },
+        );
+        let opts = Options {
+            parens: true,
+            styles,
+            ..Default::default()
+        };
+
+        let q = |q| Quantity { q, s: usd };
+        assert_eq!(opts.qty_text(&q(dec!(-0.001))), "$ 0.00");
+        assert_eq!(opts.qty_text(&q(dec!(-0.005))), "($ 0.01)");
+        assert_eq!(opts.qty_text(&q(dec!(-10))), "($ 10.00)");
+    }
+
+    #[test]
+    fn options_apply_to_one_report_only() {
+        let journal = crate::journal::Journal::parse(
+            "2025-01-01 Broker\n  Assets:Broker  10 AAPL\n  Equity\n",
+        )
+        .unwrap();
+        let print = |opts: &Options| {
+            let mut out = Vec::new();
+            print::print(&mut out, journal.xacts(), Fmt::Tty, opts).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let styled = Options {
+            styles: journal.styles().clone(),
+            ..Default::default()
+        };
+        assert!(print(&styled).contains("10 AAPL"));
+        assert!(print(&Options::default()).contains("AAPL 10"));
+        assert!(print(&styled).contains("10 AAPL"));
+    }
+}
@@ -1,4 +1,4 @@
-use rust_decimal::Decimal;
+use rust_decimal::{Decimal, RoundingStrategy};
 
 use std::fmt::{self, Debug, Display};
 use std::iter;
@@ -6,7 +6,25 @@ use std::ops::{Add, Div, DivAssign, Mul, MulAssign, Neg, Sub};
 
 use crate::amount::Amount;
 use crate::ntypes::Quantities;
-use crate::symbol::Symbol;
+use crate::symbol::{Style, Styles, Symbol};
+
+/// How [`Quantity::round`] breaks ties at the midpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundMode {
+    /// Banker's rounding: `0.005` becomes `0.00`, `0.015` becomes `0.02`.
+    HalfEven,
+    /// Away from zero: `0.005` becomes `0.01`, `-0.005` becomes `-0.01`.
+    HalfUp,
+}
+
+impl From<RoundMode> for RoundingStrategy {
+    fn from(mode: RoundMode) -> Self {
+        match mode {
+            RoundMode::HalfEven => RoundingStrategy::MidpointNearestEven,
+            RoundMode::HalfUp => RoundingStrategy::MidpointAwayFromZero,
+        }
+    }
+}
 
 /// A quantity of a specific commodity.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -42,6 +60,68 @@ impl Quantity {
             s: self.s,
         }
     }
+
+    pub fn is_zero(&self) -> bool {
+        self.q.is_zero()
+    }
+
+    /// Divides by `d`, or `None` if `d` is zero or the result
+    /// overflows.
+    pub fn checked_div(self, d: Decimal) -> Option<Quantity> {
+        Some(Quantity {
+            q: self.q.checked_div(d)?,
+            s: self.s,
+        })
+    }
+
+    /// Rounds to `dp` decimal places, breaking ties as `mode` says.
+    pub fn round(self, dp: u32, mode: RoundMode) -> Quantity {
+        Quantity {
+            q: self.q.round_dp_with_strategy(dp, mode.into()),
+            s: self.s,
+        }
+    }
+
+    /// Rounds half up to exactly `dp` decimal places, padding with
+    /// zeros if it has fewer.
+    pub fn rescale(self, dp: u32) -> Quantity {
+        let mut q = self.round(dp, RoundMode::HalfUp).q;
+        q.rescale(dp);
+        Quantity { q, s: self.s }
+    }
+
+    /// Displays the quantity as the amounts of its symbol are written
+    /// in the journal whose `styles` are given: the symbol before or
+    /// after the amount, the same separators and, unless the
+    /// formatter's precision overrides it, the same decimal places.
+    pub fn display<'a>(&'a self, styles: &'a Styles) -> QuantityDisplay<'a> {
+        QuantityDisplay { q: self, styles }
+    }
+
+    /// Formats the quantity in journal syntax, as
+    /// [`Quantity::display`] does but never with fewer decimal places
+    /// than the amount has, so parsing the text back gives an equal
+    /// quantity.
+    pub fn to_ledger_string(&self, styles: &Styles) -> String {
+        let places = styles
+            .precision(self.s)
+            .max(self.q.normalize().scale() as usize);
+        format!("{:.*}", places, self.display(styles))
+    }
+
+    fn write_styled(&self, f: &mut fmt::Formatter, style: Style, places: usize) -> fmt::Result {
+        let q = style.format.format(self.q, places);
+
+        if self.s.is_empty() {
+            return write!(f, "{}", q);
+        }
+
+        if style.suffix {
+            write!(f, "{} {}", q, self.s)
+        } else {
+            write!(f, "{} {}", self.s, q)
+        }
+    }
 }
 
 impl Neg for Quantity {
@@ -110,56 +190,29 @@ impl MulAssign<Decimal> for Quantity {
     }
 }
 
+/// Writes the amount in the [`Style::default`] placement and
+/// separators, with the decimal places it has unless the formatter's
+/// precision overrides them. Reports write it in the style of their
+/// journal instead, see [`Quantity::display`].
 impl Display for Quantity {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let pre = f.precision().unwrap_or(3);
-        let q = utils::format_decimal_manual(self.q, pre);
-
-        if self.s.is_empty() {
-            return write!(f, "{}", q);
-        }
-
-        write!(f, "{} {}", self.s, q)
+        let places = f.precision().unwrap_or(self.q.scale() as usize);
+        self.write_styled(f, Style::default(), places)
     }
 }
 
-mod utils {
-    use rust_decimal::Decimal;
-    /// naive formatting. TODO: [DECIMAL] handle formatting
-    pub fn format_decimal_manual(value: Decimal, precision: usize) -> String {
-        let formatted = format!("{:.prec$}", value, prec = precision);
-
-        let parts: Vec<&str> = formatted.split('.').collect();
-        let integer_part = parts[0];
-        let decimal_part = parts.get(1).unwrap_or(&"");
-
-        let formatted_integer = add_thousands_separator(integer_part);
-
-        if decimal_part.is_empty() {
-            formatted_integer
-        } else {
-            format!("{}.{}", formatted_integer, decimal_part)
-        }
-    }
-
-    fn add_thousands_separator(s: &str) -> String {
-        let (sign, num) = if s.starts_with('-') {
-            ("-", &s[1..])
-        } else {
-            ("", s)
-        };
-
-        let chars: Vec<char> = num.chars().collect();
-        let mut result = String::new();
-
-        for (i, c) in chars.iter().enumerate() {
-            if i > 0 && (chars.len() - i) % 3 == 0 {
-                result.push(',');
-            }
-            result.push(*c);
-        }
+/// A [`Quantity`] displayed in the style of a journal, see
+/// [`Quantity::display`].
+pub struct QuantityDisplay<'a> {
+    q: &'a Quantity,
+    styles: &'a Styles,
+}
 
-        format!("{}{}", sign, result)
+impl Display for QuantityDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let style = self.styles.get(self.q.s);
+        let places = f.precision().unwrap_or(style.places as usize);
+        self.q.write_styled(f, style, places)
     }
 }
 
@@ -167,10 +220,41 @@ mod utils {
 mod test {
     use rust_decimal::dec;
 
+    use super::RoundMode;
     use crate::ntypes::Basket;
     use crate::quantity;
     use crate::symbol::Symbol;
 
+    #[test]
+    fn checked_div_by_zero() {
+        assert_eq!(quantity!(5, "$").checked_div(dec!(0)), None);
+        assert_eq!(
+            quantity!(5, "$").checked_div(dec!(2)),
+            Some(quantity!(2.5, "$"))
+        );
+    }
+
+    #[test]
+    fn round_half_even_vs_half_up() {
+        let q = quantity!(0.005, "$");
+        assert_eq!(q.round(2, RoundMode::HalfEven), quantity!(0.00, "$"));
+        assert_eq!(q.round(2, RoundMode::HalfUp), quantity!(0.01, "$"));
+        assert_eq!((-q).round(2, RoundMode::HalfUp), quantity!(-0.01, "$"));
+        assert_eq!(
+            quantity!(0.015, "$").round(2, RoundMode::HalfEven),
+            quantity!(0.02, "$")
+        );
+    }
+
+    #[test]
+    fn rescale_pads_and_rounds() {
+        let q = quantity!(1.5, "$").rescale(3);
+        assert_eq!(q.q.to_string(), "1.500");
+        assert_eq!(q.s, Symbol::new("$"));
+        assert_eq!(quantity!(0.005, "$").rescale(2).q.to_string(), "0.01");
+        assert!(quantity!(0.004, "$").rescale(2).is_zero());
+    }
+
     #[test]
     fn add_same_symbol() {
         let a = quantity!(10, "$");
@@ -0,0 +1,234 @@
+use regex::Regex;
+
+use crate::journal::{Posting, Xact};
+use crate::limit::Limit;
+use crate::tags::TagQuery;
+
+/// A report query: a predicate over the postings of a journal, built
+/// from the positional arguments of `balance` and `register`.
+///
+/// Each token is one term:
+///
+/// ```text
+/// regex       account name matches
+/// @regex      transaction payee matches
+/// %tag        tag query, see [`TagQuery`]
+/// expr EXPR   amount predicate, same syntax as `--limit`
+/// ```
+///
+/// Account terms are alternatives, the rest must all hold, so
+/// `Food Rent @Store %trip` reads as
+/// `(Food or Rent) and @Store and %trip`.
+///
+/// ```
+/// use ledger::journal::Journal;
+/// use ledger::query::Query;
+///
+/// let journal = Journal::parse(
+///     "2025-01-01 Store\n  Expenses:Food  $10\n  Assets:Cash\n",
+/// )
+/// .unwrap();
+/// let xact = journal.xacts().next().unwrap();
+///
+/// let query = Query::parse(["Food", "@Store"]).unwrap();
+/// assert!(query.eval(xact, &xact.postings[0]));
+/// assert!(!query.eval(xact, &xact.postings[1]));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub enum Query {
+    /// Matches every posting.
+    #[default]
+    All,
+    /// Matches the postings whose account name matches.
+    Account(Regex),
+    /// Matches the postings of the transactions whose payee matches.
+    Payee(Regex),
+    /// Matches the postings the tag query holds for, see
+    /// [`TagQuery::matches_posting`].
+    Tag(TagQuery),
+    /// Matches the postings whose amount satisfies the predicate.
+    Amount(Limit),
+    /// Matches when every query does.
+    And(Vec<Query>),
+    /// Matches when any query does.
+    Or(Vec<Query>),
+}
+
+impl Query {
+    /// Parses report query tokens, see [`Query`].
+    pub fn parse<S: AsRef<str>>(tokens: impl IntoIterator<Item = S>) -> Result<Query, String> {
+        let mut accounts = Vec::new();
+        let mut terms = Vec::new();
+        let mut tokens = tokens.into_iter();
+        while let Some(token) = tokens.next() {
+            let token = token.as_ref();
+            if let Some(p) = token.strip_prefix('@') {
+                let re = Regex::new(p)
+                    .map_err(|err| format!("invalid payee pattern '{token}': {err}"))?;
+                terms.push(Query::Payee(re));
+            } else if let Some(t) = token.strip_prefix('%') {
+                let tag = TagQuery::parse(t)
+                    .map_err(|err| format!("invalid tag query '{token}': {err}"))?;
+                terms.push(Query::Tag(tag));
+            } else if token == "expr" {
+                let expr = tokens
+                    .next()
+                    .ok_or_else(|| "missing expression after 'expr'".to_owned())?;
+                let limit = expr
+                    .as_ref()
+                    .parse()
+                    .map_err(|err| format!("invalid expression: {err}"))?;
+                terms.push(Query::Amount(limit));
+            } else {
+                let re = Regex::new(token)
+                    .map_err(|err| format!("invalid account pattern '{token}': {err}"))?;
+                accounts.push(re);
+            }
+        }
+
+        if !accounts.is_empty() {
+            terms.insert(0, Query::accounts(accounts));
+        }
+        Ok(match terms.len() {
+            0 => Query::All,
+            1 => terms.pop().unwrap(),
+            _ => Query::And(terms),
+        })
+    }
+
+    /// A query matching the postings whose account name matches any of
+    /// `patterns`, or every posting when there are none.
+    pub fn accounts(patterns: impl IntoIterator<Item = Regex>) -> Query {
+        let mut terms: Vec<_> = patterns.into_iter().map(Query::Account).collect();
+        match terms.len() {
+            0 => Query::All,
+            1 => terms.pop().unwrap(),
+            _ => Query::Or(terms),
+        }
+    }
+
+    /// Whether the query matches every posting.
+    pub fn is_all(&self) -> bool {
+        matches!(self, Query::All)
+    }
+
+    /// Whether posting `p` of `xact` matches.
+    pub fn eval(&self, xact: &Xact, p: &Posting) -> bool {
+        self.eval_with(&|q| match q {
+            Query::Account(re) => re.is_match(&p.acc_name),
+            Query::Payee(re) => re.is_match(&xact.payee),
+            Query::Tag(tag) => tag.matches_posting(xact, p),
+            Query::Amount(limit) => limit.matches(&p.quantity),
+            _ => true,
+        })
+    }
+
+    /// Whether any posting of `xact` matches.
+    pub fn matches_xact(&self, xact: &Xact) -> bool {
+        self.is_all() || xact.postings.iter().any(|p| self.eval(xact, p))
+    }
+
+    /// Evaluates only the account terms against `name`, taking every
+    /// other term as matching. Used where there is an account but no
+    /// single posting, e.g. after aggregating by depth.
+    pub fn eval_account(&self, name: &str) -> bool {
+        self.eval_with(&|q| match q {
+            Query::Account(re) => re.is_match(name),
+            _ => true,
+        })
+    }
+
+    /// Like [`Query::eval`], but taking the account terms as matching.
+    pub fn eval_ignoring_accounts(&self, xact: &Xact, p: &Posting) -> bool {
+        self.eval_with(&|q| match q {
+            Query::Account(_) => true,
+            _ => Query::eval(q, xact, p),
+        })
+    }
+
+    /// Combines the terms with `and`/`or`, asking `leaf` about the rest.
+    fn eval_with(&self, leaf: &dyn Fn(&Query) -> bool) -> bool {
+        match self {
+            Query::All => true,
+            Query::And(qs) => qs.iter().all(|q| q.eval_with(leaf)),
+            Query::Or(qs) => qs.iter().any(|q| q.eval_with(leaf)),
+            _ => leaf(self),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::journal::Journal;
+
+    fn journal() -> Journal {
+        Journal::parse(
+            "\
+2025-01-01 Store  ; :trip:
+  Expenses:Food    $10
+  Expenses:Rent    $500
+  Assets:Cash
+",
+        )
+        .unwrap()
+    }
+
+    fn matching(query: &Query, journal: &Journal) -> Vec<String> {
+        journal
+            .xacts()
+            .flat_map(|x| x.postings.iter().map(move |p| (x, p)))
+            .filter(|(x, p)| query.eval(x, p))
+            .map(|(_, p)| p.acc_name.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn account_terms_are_alternatives() {
+        let journal = journal();
+        let query = Query::parse(["Food", "Rent"]).unwrap();
+        assert_eq!(
+            matching(&query, &journal),
+            vec!["Expenses:Food", "Expenses:Rent"]
+        );
+    }
+
+    #[test]
+    fn other_terms_must_all_hold() {
+        let journal = journal();
+        let query = Query::parse(["Expenses", "@Store", "%trip", "expr", "amount > 100"]).unwrap();
+        assert_eq!(matching(&query, &journal), vec!["Expenses:Rent"]);
+
+        let query = Query::parse(["@Store", "@Other"]).unwrap();
+        assert!(matching(&query, &journal).is_empty());
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        let journal = journal();
+        let query = Query::parse(Vec::<String>::new()).unwrap();
+        assert!(query.is_all());
+        assert_eq!(matching(&query, &journal).len(), 3);
+    }
+
+    #[test]
+    fn projections_skip_the_other_terms() {
+        let journal = journal();
+        let xact = journal.xacts().next().unwrap();
+        let query = Query::parse(["Food", "expr", "amount > 100"]).unwrap();
+
+        assert!(query.eval_account("Expenses:Food"));
+        assert!(!query.eval_account("Expenses"));
+        assert!(query.eval_ignoring_accounts(xact, &xact.postings[1]));
+        assert!(!query.eval_ignoring_accounts(xact, &xact.postings[0]));
+    }
+
+    #[test]
+    fn invalid_tokens_are_rejected() {
+        assert!(Query::parse(["("]).is_err());
+        assert!(Query::parse(["@("]).is_err());
+        assert!(Query::parse(["%k=("]).is_err());
+        assert!(Query::parse(["expr"]).is_err());
+        assert!(Query::parse(["expr", "price > 1"]).is_err());
+    }
+}
@@ -1,18 +1,59 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use std::iter;
+use std::mem;
+
 use chrono::NaiveDate;
-use regex::Regex;
+use rust_decimal::Decimal;
 
 use crate::{
     account_view::AccountView,
     amount::Amount,
     balance::{Balance, Valuation},
     holdings::Holdings,
-    iter::WithNext,
-    journal::{AccName, Xact},
-    misc,
-    ntypes::{Valuable, Zero},
+    journal::{AccName, Posting, Provenance, Xact},
+    ledger::{Ledger, PostingCtx, PostingFilter},
+    misc::{self, Step},
+    ntypes::{Quantities, Valuable, Zero},
     pricedb::PriceDB,
+    quantity::Quantity,
+    query,
+    symbol::Symbol,
+    tags::Tag,
 };
 
+/// Postings that become rows of the register: those that pass
+/// `postings` and match `terms`.
+///
+/// With `related` set the match is inverted: the rows are the
+/// counterpart postings, those not matching `terms`. Callers are
+/// expected to pass only transactions with a matching posting.
+#[derive(Debug, Clone, Copy)]
+pub struct RowFilter<'a> {
+    pub terms: &'a query::Query,
+    pub related: bool,
+    pub postings: PostingFilter<'a>,
+}
+
+impl RowFilter<'_> {
+    fn matches(&self, xact: &Xact, p: &Posting) -> bool {
+        self.postings.matches(&PostingCtx { xact, posting: p })
+            && (self.terms.is_all() || self.terms.eval(xact, p) != self.related)
+    }
+
+    fn matches_name(&self, name: &str) -> bool {
+        self.terms.is_all() || self.terms.eval_account(name) != self.related
+    }
+
+    /// Whether `p` may go into a depth-aggregated row; its account is
+    /// checked later, on the truncated name.
+    fn matches_aggregated(&self, ctx: &PostingCtx) -> bool {
+        self.postings.matches(ctx)
+            && (self.related || self.terms.eval_ignoring_accounts(ctx.xact, ctx.posting))
+    }
+}
+
 /// The portion of the register report produced by a single transaction.
 ///
 /// Each `RegisterGroup` corresponds to a single `Xact` and carries the
@@ -22,10 +63,16 @@ use crate::{
 pub struct RegisterGroup<'a> {
     /// Id of the transaction
     pub id: usize,
-    /// Transaction date (`Xact::date::txdate`).
-    pub date: &'a NaiveDate,
-    /// Transaction payee.
-    pub payee: &'a str,
+    /// Transaction date, see [`XactDate::date`], or the period
+    /// start for groups built by [`by_period`].
+    ///
+    /// [`XactDate::date`]: crate::journal::XactDate::date
+    pub date: NaiveDate,
+    /// Transaction payee, or `- <period end>` for groups built by
+    /// [`by_period`].
+    pub payee: Cow<'a, str>,
+    /// Transaction tags, empty for groups built by [`by_period`].
+    pub tags: &'a [Tag],
     /// Rows emitted for this transaction, in display order. Each row
     /// is derived from one posting (no depth limit) or from a group
     /// of postings that share the same truncated account name (under
@@ -49,6 +96,42 @@ pub struct RegisterRow {
     /// Cumulative sum of `total` across every row emitted so far,
     /// including rows from earlier transactions in the report.
     pub running_total: Amount,
+    /// Running average of `total` per commodity, filled in by
+    /// [`with_average`] for `--average`; `None` otherwise.
+    pub average: Option<Amount>,
+    /// Where the row comes from: its posting's provenance, or
+    /// [`Provenance::Revaluation`] for the `<Revalued>` rows. Rows
+    /// merging postings of different provenance (`--depth`,
+    /// `--collapse`, periods) are [`Provenance::Explicit`].
+    pub provenance: Provenance,
+}
+
+/// One register row before accumulation: the account, its value, its
+/// quantity and its provenance.
+type Entry = (AccName, Amount, Amount, Provenance);
+
+/// Secondary order applied to transactions (and their rows) that share
+/// the same date. Days themselves always stay in chronological order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortWithinDay {
+    /// Keep file order.
+    #[default]
+    None,
+    /// Smallest amount first. Commodities are not converted: the key
+    /// is the sum of the row's raw quantities.
+    Amount,
+    /// Account name, alphabetically.
+    Account,
+}
+
+impl SortWithinDay {
+    fn cmp(&self, a: &Entry, b: &Entry) -> Ordering {
+        match self {
+            SortWithinDay::None => Ordering::Equal,
+            SortWithinDay::Amount => amount_key(&a.1).cmp(&amount_key(&b.1)),
+            SortWithinDay::Account => a.0.cmp(&b.0),
+        }
+    }
 }
 
 /// Turns transactions into register rows. One `RegisterGroup` per
@@ -65,62 +148,305 @@ pub struct RegisterRow {
 /// the last xact, `at` if supplied, otherwise the greater of the
 /// xact's date and today. No drift, no row.
 ///
-/// Transaction selection is done upstream (see [`Journal::xact_filter_by`]):
-/// only pass in the transactions you want reported. Within each
-/// transaction, `query` controls which postings become rows — postings
-/// whose account name matches none of the patterns are dropped. Date
-/// filtering is entirely upstream; this function does not drop any
-/// transaction based on date.
+/// Transaction selection is done upstream, e.g. with
+/// [`Journal::xact_filter_by_date`] and [`query::Query::matches_xact`]:
+/// only pass in the transactions you want reported. This function
+/// does not drop any transaction based on its date. Within each
+/// transaction, `filter` controls which postings become rows, see
+/// [`RowFilter`].
 ///
 /// Parameters:
 ///
 /// - `xacts`: the transactions to report.
-/// - `query`: account-name patterns. A posting is included only when
-///   at least one pattern matches its account name. An empty slice
-///   includes all postings.
+/// - `filter`: the report query and the posting filters (state,
+///   `--limit`, `--real`, ...). A posting becomes a row only when it
+///   passes both; [`query::Query::All`] keeps every posting.
 /// - `at`: reference date for the trailing revaluation after the last
 ///   xact. `None` means open-ended; the revaluation then falls back to
-///   the greater of the last xact's date and today.
+///   the greater of the last xact's date and `now`.
+/// - `now`: the date taken as today (`--now`).
 /// - `vtype`: how to value postings. The market case is the only one
 ///   that triggers the revaluation logic above.
 /// - `depth`: `0` means one row per posting, no collapsing. Positive
 ///   values truncate account names and merge whatever shares the
 ///   prefix.
+/// - `sort`: stable secondary order within each day. Rows inside a
+///   transaction are sorted by it, and same-day transactions are
+///   ordered by their first row. The running total follows the
+///   resulting display order.
 /// - `price_db`: where prices come from. Used for historical and
 ///   market valuation; ignored otherwise.
-pub fn register<'a>(
-    xacts: impl Iterator<Item = &'a Xact>,
-    query: &'a [Regex],
+///
+/// [`Journal::xact_filter_by_date`]: crate::journal::Journal::xact_filter_by_date
+#[allow(clippy::too_many_arguments)]
+pub fn register<'a: 'p, 'p>(
+    xacts: impl Iterator<Item = &'a Xact> + 'p,
+    filter: RowFilter<'a>,
     at: Option<NaiveDate>,
+    now: NaiveDate,
     vtype: Valuation,
     depth: usize,
-    price_db: &PriceDB,
+    sort: SortWithinDay,
+    price_db: &'p PriceDB,
 ) -> impl Iterator<Item = RegisterGroup<'a>> {
     let mut accum = Accum::default();
-    WithNext::new(xacts)
-        .map(move |(xact, next)| {
-            let mut rows = Vec::new();
+    let mut xacts = sort_within_day(xacts, filter, vtype, depth, sort, price_db).peekable();
+    iter::from_fn(move || {
+        let (xact, entries) = xacts.next()?;
+        let next = xacts.peek().map(|&(x, _)| x);
+        let mut rows = Vec::new();
 
-            for (name, value, qty) in xact_entries(xact, query, vtype, price_db, depth) {
-                rows.push(accum.record_entry(name, value, qty));
-            }
+        for (name, value, qty, provenance) in entries {
+            rows.push(accum.record_entry(name, value, qty, provenance));
+        }
+
+        if matches!(vtype, Valuation::Market)
+            && !filter.postings.actual
+            && let Some(d) = revaluation_anchor(xact, next, at, now)
+            && let Some(row) = accum.record_revaluation(d, price_db)
+        {
+            rows.push(row);
+        }
+
+        Some(RegisterGroup {
+            id: xact.id,
+            date: xact.date.date(),
+            payee: Cow::Borrowed(&xact.payee),
+            tags: &xact.tags,
+            rows,
+        })
+    })
+    .filter(|r| !r.rows.is_empty())
+}
 
-            if matches!(vtype, Valuation::Market) {
-                if let Some(d) = revaluation_anchor(xact, next, at) {
-                    if let Some(row) = accum.record_revaluation(d, price_db) {
-                        rows.push(row);
+/// Collapses the register into one group per period of kind `step`,
+/// as `register -M` does. The count in `step` is ignored.
+///
+/// Within a period, rows are summed per account, in the order the
+/// accounts first appear. The running total keeps accumulating across
+/// periods. Each group is dated at the period start, carries the id of
+/// the period's first transaction and shows the period's last day as
+/// its payee (`- 2026-01-31`). `groups` must be in date order.
+pub fn by_period<'a>(
+    groups: impl Iterator<Item = RegisterGroup<'a>>,
+    step: Step,
+) -> impl Iterator<Item = RegisterGroup<'a>> {
+    let one = match step {
+        Step::Days(_) => Step::Days(1),
+        Step::Weeks(_) => Step::Weeks(1),
+        Step::Months(_) => Step::Months(1),
+        Step::Quarters(_) => Step::Quarters(1),
+        Step::Years(_) => Step::Years(1),
+    };
+    let groups: Vec<_> = groups.collect();
+    let mut running = Amount::new();
+
+    let periods: Vec<_> = groups
+        .chunk_by(|a, b| misc::period_start(a.date, one) == misc::period_start(b.date, one))
+        .map(|period| {
+            let start = misc::period_start(period[0].date, one);
+            let end = misc::iter_dates(start, one)
+                .nth(1)
+                .and_then(|next| next.pred_opt())
+                .unwrap();
+
+            let mut rows: Vec<RegisterRow> = Vec::new();
+            for row in period.iter().flat_map(|g| &g.rows) {
+                match rows.iter_mut().find(|r| r.acc_name == row.acc_name) {
+                    Some(r) => {
+                        r.total += &row.total;
+                        r.provenance = merged_provenance(r.provenance, row.provenance);
                     }
+                    None => rows.push(RegisterRow {
+                        acc_name: row.acc_name.clone(),
+                        total: row.total.clone(),
+                        running_total: Amount::new(),
+                        average: None,
+                        provenance: row.provenance,
+                    }),
                 }
             }
+            for r in &mut rows {
+                running += &r.total;
+                r.running_total = running.clone();
+            }
 
             RegisterGroup {
-                id: xact.id,
-                date: &xact.date.txdate,
-                payee: &xact.payee,
+                id: period[0].id,
+                date: start,
+                payee: Cow::Owned(format!("- {end}")),
+                tags: &[],
                 rows,
             }
         })
-        .filter(|r| !r.rows.is_empty())
+        .collect();
+
+    periods.into_iter()
+}
+
+/// Collapses each group into a single row holding the net of its
+/// rows, as `register --collapse` does. The row is named after the
+/// deepest account shared by all the rows, or `<Total>` when they
+/// share none. The running total keeps accumulating across groups.
+pub fn collapse<'a>(
+    groups: impl Iterator<Item = RegisterGroup<'a>>,
+) -> impl Iterator<Item = RegisterGroup<'a>> {
+    let mut running = Amount::new();
+    groups.map(move |mut g| {
+        let mut rows = g.rows.drain(..);
+        let first = rows.next().expect("register groups are never empty");
+        let init = (first.acc_name, first.total, first.provenance);
+        let (name, total, provenance) = rows.fold(init, |(name, total, provenance), r| {
+            (
+                name.common_prefix(&r.acc_name),
+                total + r.total,
+                merged_provenance(provenance, r.provenance),
+            )
+        });
+        running += &total;
+        g.rows = vec![RegisterRow {
+            acc_name: if name.is_empty() {
+                AccName::from("<Total>")
+            } else {
+                name
+            },
+            total,
+            running_total: running.clone(),
+            average: None,
+            provenance,
+        }];
+        g
+    })
+}
+
+/// The provenance of a row merging rows of provenance `a` and `b`.
+fn merged_provenance(a: Provenance, b: Provenance) -> Provenance {
+    if a == b { a } else { Provenance::Explicit }
+}
+
+/// Reorders the register rows by the magnitude of their amount
+/// (see [`Amount::size_key`]), largest first when `descending`. Ties
+/// keep their order. Each row becomes a group of its own, keeping its
+/// transaction's id, date and payee, and the running total is
+/// recomputed to follow the new order.
+pub fn sort_by_amount<'a>(
+    groups: impl Iterator<Item = RegisterGroup<'a>>,
+    descending: bool,
+) -> impl Iterator<Item = RegisterGroup<'a>> {
+    let mut rows: Vec<_> = groups
+        .flat_map(|g| {
+            let (id, date, payee, tags) = (g.id, g.date, g.payee, g.tags);
+            g.rows
+                .into_iter()
+                .map(move |r| (id, date, payee.clone(), tags, r))
+        })
+        .collect();
+    rows.sort_by_cached_key(|(.., r)| {
+        let m = r.total.size_key();
+        if descending { -m } else { m }
+    });
+
+    let mut running = Amount::new();
+    rows.into_iter()
+        .map(move |(id, date, payee, tags, mut row)| {
+            running += &row.total;
+            row.running_total = running.clone();
+            RegisterGroup {
+                id,
+                date,
+                payee,
+                tags,
+                rows: vec![row],
+            }
+        })
+}
+
+/// Negates the amounts of every row, running totals and averages
+/// included, as `register --invert` does.
+pub fn invert<'a>(
+    groups: impl Iterator<Item = RegisterGroup<'a>>,
+) -> impl Iterator<Item = RegisterGroup<'a>> {
+    groups.map(|mut g| {
+        for row in &mut g.rows {
+            row.total = -mem::take(&mut row.total);
+            row.running_total = -mem::take(&mut row.running_total);
+            row.average = row.average.take().map(|a| -a);
+        }
+        g
+    })
+}
+
+/// Converts every row into `target`, as `register -X` does. Amounts
+/// and running totals are exchanged at the rate in effect on the
+/// group's date (see [`PriceDB::convert`]), so a mixed running total
+/// shows its worth on each row's day.
+///
+/// On failure the `(from, to)` pairs without a rate are returned,
+/// sorted by commodity name.
+pub fn exchange<'a>(
+    groups: impl Iterator<Item = RegisterGroup<'a>>,
+    target: Symbol,
+    price_db: &PriceDB,
+) -> Result<Vec<RegisterGroup<'a>>, Vec<(Symbol, Symbol)>> {
+    let mut missing = BTreeSet::new();
+    let groups: Vec<_> = groups
+        .map(|mut g| {
+            let at = misc::to_datetime(g.date);
+            let mut exchange = |amt: &Amount| {
+                let mut total = Decimal::ZERO;
+                for q in amt.quantities() {
+                    match price_db.convert(q, target, at) {
+                        Some(c) => total += c.q,
+                        None => {
+                            missing.insert((q.s.name(), q.s));
+                        }
+                    }
+                }
+                Amount::from_quantity(Quantity {
+                    q: total.round_dp(price_db.valuation_precision()),
+                    s: target,
+                })
+            };
+            for row in &mut g.rows {
+                row.total = exchange(&row.total);
+                row.running_total = exchange(&row.running_total);
+            }
+            g
+        })
+        .collect();
+
+    if !missing.is_empty() {
+        return Err(missing.into_iter().map(|(_, s)| (s, target)).collect());
+    }
+    Ok(groups)
+}
+
+/// Fills in [`RegisterRow::average`] for `--average`. For each
+/// commodity the average is the sum of its amounts so far divided by
+/// the number of rows that carried it, so multi-commodity streams get
+/// one average per commodity. Values are rounded to `dp` places,
+/// usually [`PriceDB::valuation_precision`].
+pub fn with_average<'a>(
+    groups: impl Iterator<Item = RegisterGroup<'a>>,
+    dp: u32,
+) -> impl Iterator<Item = RegisterGroup<'a>> {
+    let mut sums = HashMap::<Symbol, (Decimal, usize)>::new();
+    groups.map(move |mut g| {
+        for row in &mut g.rows {
+            for q in row.total.quantities() {
+                let (sum, n) = sums.entry(q.s).or_default();
+                *sum += q.q;
+                *n += 1;
+            }
+            let mut average = Amount::new();
+            for (&s, &(sum, n)) in &sums {
+                let q = (sum / Decimal::from(n)).round_dp(dp);
+                average += Quantity { q, s };
+            }
+            row.average = Some(average);
+        }
+        g
+    })
 }
 
 #[derive(Default)]
@@ -130,18 +456,26 @@ struct Accum {
 }
 
 impl Accum {
-    fn record_entry(&mut self, name: AccName, value: Amount, qty: Amount) -> RegisterRow {
+    fn record_entry(
+        &mut self,
+        name: AccName,
+        value: Amount,
+        qty: Amount,
+        provenance: Provenance,
+    ) -> RegisterRow {
         self.value += &value;
         self.qty += &qty;
         RegisterRow {
             acc_name: name,
             total: value,
             running_total: self.value.clone(),
+            average: None,
+            provenance,
         }
     }
 
     fn record_revaluation(&mut self, at: NaiveDate, price_db: &PriceDB) -> Option<RegisterRow> {
-        let revalued = price_db.value_as_of(at, self.qty.clone()).unwrap();
+        let revalued = price_db.value_or_quantity(at, self.qty.clone());
         let diff = revalued - self.value.clone();
 
         if diff.is_zero() {
@@ -153,6 +487,8 @@ impl Accum {
             acc_name: AccName::from("<Revalued>"),
             total: diff,
             running_total: self.value.clone(),
+            average: None,
+            provenance: Provenance::Revaluation,
         })
     }
 }
@@ -165,9 +501,8 @@ impl Accum {
 /// `quantity` is the underlying commodity amount (used later to
 /// compute market-price revaluations between transactions).
 ///
-/// Only postings (or depth-aggregated accounts) whose name matches at
-/// least one pattern in `query` are returned. An empty `query` passes
-/// all postings through.
+/// Only postings (or depth-aggregated accounts) passing `filter` are
+/// returned.
 ///
 /// The shape of the entries depends on `depth`:
 ///
@@ -177,64 +512,129 @@ impl Accum {
 ///   `valuation`.
 /// - `depth > 0`: postings are collapsed by truncating account names
 ///   to the first `depth` components, going through a balance view
-///   that aggregates holdings before valuation. The `query` filter is
-///   applied after aggregation, on the truncated name.
+///   that aggregates holdings before valuation. The account terms of
+///   `filter` are applied after aggregation, on the truncated name.
 fn xact_entries<'a>(
     xact: &'a Xact,
-    query: &'a [Regex],
+    filter: RowFilter<'a>,
     valuation: Valuation,
     price_db: &'a PriceDB,
     depth: usize,
-) -> Box<dyn Iterator<Item = (AccName, Amount, Amount)> + 'a> {
+) -> Box<dyn Iterator<Item = Entry> + 'a> {
     if depth == 0 {
         Box::new(
             xact.postings
                 .iter()
-                .filter(move |p| query.is_empty() || query.iter().any(|r| r.is_match(&p.acc_name)))
+                .filter(move |p| filter.matches(xact, p))
                 .map(move |p| {
                     let value = match valuation {
                         Valuation::Quantity => p.quantity.to_amount(),
                         Valuation::Basis | Valuation::Market => p.book_value().to_amount(),
                         Valuation::Historical => match p.lot_date {
-                            Some(date) => price_db.value_as_of(date, p.quantity).unwrap(),
+                            Some(date) => p.historical_value(date, price_db).to_amount(),
                             None => p.book_value().to_amount(),
                         },
                     };
-                    (p.acc_name.clone(), value, p.quantity.to_amount())
+                    (
+                        p.acc_name.clone(),
+                        value,
+                        p.quantity.to_amount(),
+                        p.provenance,
+                    )
                 }),
         )
     } else {
+        let ledger =
+            Ledger::from_xacts([xact]).filter_postings(|ctx| filter.matches_aggregated(ctx));
         Box::new(
-            Balance::from_xact(xact)
-                .to_balance_view_as_of::<Holdings>(xact.date.txdate, price_db)
+            Balance::from_ledger(&ledger, &query::Query::All)
+                .to_balance_view_as_of::<Holdings>(xact.date.date(), price_db)
                 .limit_accounts_depth(depth)
                 .to_flat()
                 .into_accounts()
-                .filter(move |p| query.is_empty() || query.iter().any(|r| r.is_match(p.name())))
+                .filter(move |p| filter.matches_name(p.name()))
                 .map(move |p| {
                     let (_, holding) = p.balance().clone().into_iter().next().unwrap();
                     (
                         p.name().clone(),
                         holding.valued_in(valuation),
                         holding.valued_in(Valuation::Quantity),
+                        Provenance::Explicit,
                     )
                 }),
         )
     }
 }
 
+/// Like [`xact_entries`], but with the entries ordered by `sort`.
+fn sorted_entries(
+    xact: &Xact,
+    filter: RowFilter,
+    valuation: Valuation,
+    price_db: &PriceDB,
+    depth: usize,
+    sort: SortWithinDay,
+) -> Vec<Entry> {
+    let mut entries: Vec<_> = xact_entries(xact, filter, valuation, price_db, depth).collect();
+    entries.sort_by(|a, b| sort.cmp(a, b));
+    entries
+}
+
+/// Pairs each transaction with its [`sorted_entries`] and reorders
+/// transactions that share the same date according to `sort`, each
+/// one keyed by its first row. The sort is stable, so ties keep file
+/// order. With [`SortWithinDay::None`] the input keeps its order and
+/// is not collected.
+fn sort_within_day<'a: 'b, 'b>(
+    xacts: impl Iterator<Item = &'a Xact> + 'b,
+    filter: RowFilter<'a>,
+    valuation: Valuation,
+    depth: usize,
+    sort: SortWithinDay,
+    price_db: &'b PriceDB,
+) -> Box<dyn Iterator<Item = (&'a Xact, Vec<Entry>)> + 'b> {
+    let with_entries = xacts.map(move |x| {
+        (
+            x,
+            sorted_entries(x, filter, valuation, price_db, depth, sort),
+        )
+    });
+    if sort == SortWithinDay::None {
+        return Box::new(with_entries);
+    }
+
+    let mut keyed: Vec<_> = with_entries.collect();
+    keyed
+        .chunk_by_mut(|(a, _), (b, _)| a.date.date() == b.date.date())
+        .for_each(|day| {
+            day.sort_by(|(_, a), (_, b)| match (a.first(), b.first()) {
+                (Some(a), Some(b)) => sort.cmp(a, b),
+                (a, b) => a.is_some().cmp(&b.is_some()),
+            })
+        });
+
+    Box::new(keyed.into_iter())
+}
+
+/// Scalar used to order rows by amount: the sum of the raw quantities,
+/// regardless of commodity.
+fn amount_key(amt: &Amount) -> Decimal {
+    amt.quantities().map(|q| q.q).sum()
+}
+
 /// Picks the date used to revalue holdings after `xact`.
 ///
 /// Uses `next`'s date for every transaction except the last. For the
 /// last one, uses `at` if supplied, otherwise the greater of `xact`'s
-/// date and today.
+/// date and `now`.
 fn revaluation_anchor(
     xact: &Xact,
     next: Option<&Xact>,
     at: Option<NaiveDate>,
+    now: NaiveDate,
 ) -> Option<NaiveDate> {
     match next {
-        Some(n) => Some(n.date.txdate),
-        None => at.or_else(|| Some(misc::today()).filter(|&today| today > xact.date.txdate)),
+        Some(n) => Some(n.date.date()),
+        None => at.or_else(|| Some(now).filter(|&now| now > xact.date.date())),
     }
 }
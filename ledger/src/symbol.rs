@@ -1,45 +1,251 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::fmt::{self, Debug, Display};
+use std::hash::{Hash, Hasher};
 use std::sync::RwLock;
 
 use lazy_static::lazy_static;
+use rust_decimal::Decimal;
 
 use crate::interner::Interner;
+use crate::parser_number::NumberFormat;
+use crate::quantity::Quantity;
 
 lazy_static! {
     static ref INTERNER: RwLock<Interner> = RwLock::new(Interner::with_capacity(1024));
 }
 
-type Id = usize;
+/// Longest chain of conversions followed, in case a journal declares
+/// a cycle.
+const MAX_CONVERSION_CHAIN: usize = 16;
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Ord, PartialOrd)]
-pub struct Symbol(Id);
+/// Decimal places a symbol is displayed with when none was seen.
+pub const DEFAULT_DISPLAY_PRECISION: usize = 1;
+
+/// How the amounts of a symbol are written, as seen in the journal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Style {
+    /// Decimal places.
+    pub places: u32,
+    /// Whether the symbol goes after the amount (`10 AAPL`) rather
+    /// than before it (`$10`).
+    pub suffix: bool,
+    /// Separators of the amount.
+    pub format: NumberFormat,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            places: DEFAULT_DISPLAY_PRECISION as u32,
+            suffix: false,
+            format: NumberFormat::Us,
+        }
+    }
+}
+
+/// Style of a symbol and whether a directive fixed it.
+#[derive(Clone, Copy, Debug)]
+struct Styled {
+    style: Style,
+    /// set by a `commodity ... format` or `D` directive, observed
+    /// amounts no longer change it
+    fixed: bool,
+}
+
+/// How the amounts of each symbol of a journal are displayed, as
+/// seen in its amounts and directives. Every journal has its own, so
+/// reading one doesn't change how another is printed.
+#[derive(Clone, Debug, Default)]
+pub struct Styles(HashMap<Symbol, Styled>);
+
+impl Styles {
+    /// How the amounts of `s` are displayed: the style set by a
+    /// directive (see [`Styles::set`]), or else the one seen in the
+    /// journal amounts. Defaults to [`Style::default`].
+    pub fn get(&self, s: Symbol) -> Style {
+        self.0.get(&s).map_or(Style::default(), |s| s.style)
+    }
+
+    /// Decimal places `s` is displayed with, see [`Styles::get`].
+    pub fn precision(&self, s: Symbol) -> usize {
+        self.get(s).places as usize
+    }
+
+    /// Records an amount of `s` written as in `style`. The placement
+    /// and the separators are those of the first amount seen; the
+    /// precision grows to the largest seen.
+    pub fn observe(&mut self, s: Symbol, style: Style) {
+        let s = self.0.entry(s).or_insert(Styled {
+            style,
+            fixed: false,
+        });
+        if !s.fixed {
+            s.style.places = s.style.places.max(style.places);
+        }
+    }
+
+    /// Fixes the style of `s`, as a `commodity ... format` directive
+    /// does. Observed amounts no longer change it.
+    pub fn set(&mut self, s: Symbol, style: Style) {
+        self.0.insert(s, Styled { style, fixed: true });
+    }
+
+    /// Adds the styles of a journal read after this one: its fixed
+    /// styles win, its observed ones are merged as
+    /// [`Styles::observe`] does.
+    pub fn extend(&mut self, other: Styles) {
+        for (s, styled) in other.0 {
+            if styled.fixed {
+                self.0.insert(s, styled);
+            } else {
+                self.observe(s, styled.style);
+            }
+        }
+    }
+}
+
+/// The unit conversions of a journal, as declared by directives like
+/// `C 1.00 Kg = 1000.00 g`. Unlike market prices, conversions don't
+/// change over time: the parser writes the amounts of a symbol in the
+/// last unit of its chain (`Kg` -> `g`), see [`Conversions::reduce`].
+#[derive(Clone, Debug, Default)]
+pub struct Conversions(HashMap<Symbol, Quantity>);
+
+impl Conversions {
+    /// Declares that one unit of `s` is worth `per_unit`.
+    pub fn set(&mut self, s: Symbol, per_unit: Quantity) {
+        self.0.insert(s, per_unit);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// One unit of `s` in the last unit of its chain of conversions,
+    /// or `1 s` if it has none.
+    pub fn unit(&self, s: Symbol) -> Quantity {
+        let mut res = Quantity { q: Decimal::ONE, s };
+        for _ in 0..MAX_CONVERSION_CHAIN {
+            let Some(per_unit) = self.0.get(&res.s) else {
+                break;
+            };
+            res = *per_unit * res.q;
+        }
+        res
+    }
+
+    /// `q` in the last unit of its chain of conversions, or as is if
+    /// its symbol has none.
+    pub fn reduce(&self, q: Quantity) -> Quantity {
+        self.unit(q.s) * q.q
+    }
+
+    /// Adds the conversions of a journal read after this one.
+    pub fn extend(&mut self, other: Conversions) {
+        self.0.extend(other.0);
+    }
+}
+
+/// An interned commodity name. It holds the name itself, so reading
+/// and comparing names takes no lock; symbols are equal when they are
+/// the same interned string.
+#[derive(Clone, Copy)]
+pub struct Symbol(&'static str);
 
 impl Symbol {
+    /// Interns `n`. Surrounding double quotes are stripped, so
+    /// `"AAPL"` and `AAPL` are the same symbol.
     pub fn new(n: &str) -> Symbol {
+        let n = n
+            .strip_prefix('"')
+            .and_then(|n| n.strip_suffix('"'))
+            .unwrap_or(n);
         let mut iner = INTERNER.write().unwrap();
-        let n = iner.intern(n);
-        Symbol(n)
+        let id = iner.intern(n);
+        Symbol(iner.name(id))
     }
 
     pub fn is_empty(&self) -> bool {
-        self.0 == 0
+        self.0.is_empty()
     }
 
-    pub(crate) fn name(&self) -> String {
+    /// Number of distinct symbols interned so far, the empty symbol
+    /// left out.
+    pub fn interned_count() -> usize {
+        INTERNER.read().unwrap().len() - 1
+    }
+
+    /// Every symbol interned so far, ordered by name. The empty symbol
+    /// is left out. Handy to spot the same commodity written two ways,
+    /// like `US$` and `USD`.
+    pub fn all_interned() -> Vec<Symbol> {
         let iner = INTERNER.read().unwrap();
-        iner.name(self.0).to_owned()
+        let mut all: Vec<Symbol> = (1..iner.len()).map(|id| Symbol(iner.name(id))).collect();
+        all.sort();
+        all
+    }
+
+    pub(crate) fn name(&self) -> String {
+        self.0.to_owned()
     }
 }
 
+/// Whether `name` can only be written quoted in a journal, e.g.
+/// `"Vanguard 500"`, because it holds whitespace, digits or characters
+/// the grammar treats as delimiters.
+fn needs_quotes(name: &str) -> bool {
+    name.chars()
+        .any(|c| c.is_whitespace() || c.is_ascii_digit() || ".,;:?!-+*/^&|={}[]<>()@".contains(c))
+}
+
+/// Writes the name, quoted when needed so the output reads back as
+/// the same symbol.
 impl Display for Symbol {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.name())
+        let name = self.name();
+        if needs_quotes(&name) {
+            write!(f, "\"{}\"", name)
+        } else {
+            write!(f, "{}", name)
+        }
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.0, other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.as_ptr().hash(state)
+    }
+}
+
+/// Symbols are ordered by name, not by the order they were interned
+/// in, so reports list commodities the same way on every run.
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self == other {
+            return Ordering::Equal;
+        }
+        self.0.cmp(other.0)
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
 impl Debug for Symbol {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "({} :: {})", self.name(), self.0)
+        write!(f, "Symbol({:?})", self.0)
     }
 }
 
@@ -65,6 +271,73 @@ mod test {
         assert!(!s.is_empty());
     }
 
+    #[test]
+    fn symbols_are_ordered_by_name() {
+        let z = Symbol::new("ORD-Z");
+        let a = Symbol::new("ORD-A");
+        assert!(a < z);
+        assert_eq!(a.cmp(&Symbol::new("ORD-A")), Ordering::Equal);
+    }
+
+    #[test]
+    fn all_interned_lists_every_symbol_once() {
+        let a = Symbol::new("INTERNED-A");
+        let b = Symbol::new("INTERNED-B");
+        Symbol::new("INTERNED-A");
+        let all = Symbol::all_interned();
+        assert!(all.len() <= Symbol::interned_count());
+        assert_eq!(all.iter().filter(|s| **s == a).count(), 1);
+        assert!(all.contains(&b));
+        assert!(!all.iter().any(|s| s.is_empty()));
+        assert!(all.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    fn style(places: u32, suffix: bool) -> Style {
+        Style {
+            places,
+            suffix,
+            format: NumberFormat::Us,
+        }
+    }
+
+    #[test]
+    fn precision_is_the_largest_seen_unless_fixed() {
+        let mut styles = Styles::default();
+        let s = Symbol::new("PRECISION-OBSERVED");
+        assert_eq!(styles.precision(s), DEFAULT_DISPLAY_PRECISION);
+        styles.observe(s, style(2, false));
+        styles.observe(s, style(0, false));
+        assert_eq!(styles.precision(s), 2);
+
+        let s = Symbol::new("PRECISION-FIXED");
+        styles.observe(s, style(8, false));
+        styles.set(s, style(3, false));
+        styles.observe(s, style(5, false));
+        assert_eq!(styles.precision(s), 3);
+    }
+
+    #[test]
+    fn placement_is_the_first_seen() {
+        let mut styles = Styles::default();
+        let s = Symbol::new("PLACEMENT-OBSERVED");
+        styles.observe(s, style(0, true));
+        styles.observe(s, style(0, false));
+        assert!(styles.get(s).suffix);
+    }
+
+    #[test]
+    fn styles_are_per_journal() {
+        let s = Symbol::new("STYLES-PER-JOURNAL");
+        let mut first = Styles::default();
+        first.observe(s, style(4, true));
+        assert_eq!(Styles::default().get(s), Style::default());
+
+        let mut second = Styles::default();
+        second.set(s, style(2, false));
+        first.extend(second);
+        assert_eq!(first.get(s), style(2, false));
+    }
+
     #[test]
     fn same_name_same_symbol() {
         let a = Symbol::new("USD");
@@ -72,10 +345,87 @@ mod test {
         assert_eq!(a, b);
     }
 
+    #[test]
+    fn quoted_name_same_symbol() {
+        let a = Symbol::new("\"Vanguard 500\"");
+        let b = Symbol::new("Vanguard 500");
+        assert_eq!(a, b);
+        assert_eq!(Symbol::new("\"AAPL\""), Symbol::new("AAPL"));
+    }
+
+    #[test]
+    fn display_quotes_names_with_spaces() {
+        let s = Symbol::new("Vanguard 500");
+        assert_eq!(s.to_string(), "\"Vanguard 500\"");
+        assert_eq!(Symbol::new("USD").to_string(), "USD");
+    }
+
     #[test]
     fn different_name_different_symbol() {
         let a = Symbol::new("USD");
         let b = Symbol::new("EUR");
         assert_ne!(a, b);
     }
+
+    #[test]
+    fn ordered_by_name_not_interning_order() {
+        let z = Symbol::new("ZZZ-ord");
+        let a = Symbol::new("AAA-ord");
+        assert!(a < z);
+        assert_eq!(a.cmp(&Symbol::new("AAA-ord")), Ordering::Equal);
+    }
+
+    #[test]
+    fn reduce_follows_the_chain_of_conversions() {
+        use rust_decimal::dec;
+
+        let lb = Symbol::new("lb");
+        let oz = Symbol::new("oz");
+        let dr = Symbol::new("dr");
+        let mut conversions = Conversions::default();
+        conversions.set(lb, Quantity { q: dec!(16), s: oz });
+        conversions.set(oz, Quantity { q: dec!(16), s: dr });
+
+        assert_eq!(
+            conversions.reduce(Quantity { q: dec!(2), s: lb }),
+            Quantity {
+                q: dec!(512),
+                s: dr
+            }
+        );
+        let q = Quantity { q: dec!(3), s: dr };
+        assert_eq!(conversions.reduce(q), q);
+    }
+
+    #[test]
+    fn reduce_stops_on_conversion_cycles() {
+        use rust_decimal::dec;
+
+        let a = Symbol::new("a");
+        let b = Symbol::new("b");
+        let mut conversions = Conversions::default();
+        conversions.set(a, Quantity { q: dec!(1), s: b });
+        conversions.set(b, Quantity { q: dec!(1), s: a });
+
+        assert_eq!(conversions.reduce(Quantity { q: dec!(5), s: a }).q, dec!(5));
+    }
+
+    #[test]
+    fn conversions_are_per_journal() {
+        use rust_decimal::dec;
+
+        let kg = Symbol::new("Kg");
+        let mut conversions = Conversions::default();
+        conversions.set(
+            kg,
+            Quantity {
+                q: dec!(1000),
+                s: Symbol::new("g"),
+            },
+        );
+
+        let q = Quantity { q: dec!(2), s: kg };
+        assert_eq!(conversions.reduce(q).q, dec!(2000));
+        assert_eq!(Conversions::default().reduce(q), q);
+    }
 }
@@ -1,9 +1,12 @@
+use std::collections::HashMap;
 use std::fmt::{self, Debug, Display};
 use std::sync::RwLock;
 
 use lazy_static::lazy_static;
+use regex::Regex;
 
 use crate::interner::Interner;
+use crate::journal::{Posting, Xact};
 
 lazy_static! {
     static ref INTERNER: RwLock<Interner> = RwLock::new(Interner::with_capacity(1024));
@@ -39,6 +42,48 @@ impl Debug for Tag {
     }
 }
 
+/// A `%tag` or `%key=value` report query token, without the `%`.
+///
+/// `tag` matches a plain tag (`:tag:`) or a value tag with that key.
+/// `key=value` matches only value tags, and the value side is a regex
+/// that must match the whole value, so a literal matches exactly.
+#[derive(Debug, Clone)]
+pub struct TagQuery {
+    tag: Tag,
+    value: Option<Regex>,
+}
+
+impl TagQuery {
+    /// Parses `tag` or `key=value`. Fails when the value is not a
+    /// valid regex.
+    pub fn parse(s: &str) -> Result<TagQuery, regex::Error> {
+        match s.split_once('=') {
+            Some((key, value)) => Ok(TagQuery {
+                tag: Tag::new(key),
+                value: Some(Regex::new(&format!("^(?:{value})$"))?),
+            }),
+            None => Ok(TagQuery {
+                tag: Tag::new(s),
+                value: None,
+            }),
+        }
+    }
+
+    /// Whether the query matches the given tags and value tags.
+    pub fn matches(&self, tags: &[Tag], vtags: &HashMap<Tag, String>) -> bool {
+        match &self.value {
+            Some(re) => vtags.get(&self.tag).is_some_and(|v| re.is_match(v)),
+            None => tags.contains(&self.tag) || vtags.contains_key(&self.tag),
+        }
+    }
+
+    /// Whether posting `p` matches, either through its own tags or
+    /// through those of its transaction `xact`.
+    pub fn matches_posting(&self, xact: &Xact, p: &Posting) -> bool {
+        self.matches(&p.tags, &p.vtags) || self.matches(&xact.tags, &xact.vtags)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -74,4 +119,20 @@ mod test {
         let b = Tag::new("note");
         assert_ne!(a, b);
     }
+
+    #[test]
+    fn tag_query_matches_tags_and_values() {
+        let tags = vec![Tag::new("reconciled")];
+        let vtags = HashMap::from([(Tag::new("project"), "home-2026".to_owned())]);
+
+        let q = |s: &str| TagQuery::parse(s).unwrap().matches(&tags, &vtags);
+        assert!(q("reconciled"));
+        assert!(q("project"));
+        assert!(q("project=home-2026"));
+        assert!(q("project=home-.*"));
+        assert!(!q("project=home"));
+        assert!(!q("reconciled=yes"));
+        assert!(!q("missing"));
+        assert!(TagQuery::parse("project=(").is_err());
+    }
 }
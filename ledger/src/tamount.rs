@@ -2,7 +2,7 @@ use chrono::NaiveDate;
 use std::collections::BTreeMap;
 use std::iter::Sum;
 
-use std::ops::{Add, AddAssign, Sub, SubAssign};
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
 
 use crate::ntypes::{Arithmetic, Basket, TsBasket, Zero};
 
@@ -23,6 +23,16 @@ where
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Returns the change between each date and the previous one. The
+    /// earliest date only serves as the starting point and is dropped.
+    pub fn deltas(&self) -> Self {
+        self.ts
+            .iter()
+            .zip(self.ts.iter().skip(1))
+            .map(|((_, prev), (d, curr))| (*d, curr.clone() - prev.clone()))
+            .collect()
+    }
 }
 
 impl<V> Arithmetic for TAmount<V> where V: Arithmetic + Basket {}
@@ -124,6 +134,16 @@ where
     }
 }
 
+impl<V> Neg for TAmount<V>
+where
+    V: Basket + Arithmetic + Neg<Output = V>,
+{
+    type Output = TAmount<V>;
+    fn neg(self) -> Self::Output {
+        self.ts.into_iter().map(|(d, v)| (d, -v)).collect()
+    }
+}
+
 impl<V> Sum<TAmount<V>> for TAmount<V>
 where
     V: Basket + Arithmetic,
@@ -138,6 +158,7 @@ where
 
 #[cfg(test)]
 mod tests {
+    use super::TAmount;
     use crate::misc::today;
     use crate::tamount;
     use chrono::NaiveDate;
@@ -147,6 +168,13 @@ mod tests {
         NaiveDate::from_ymd_opt(y, m, day).unwrap()
     }
 
+    #[test]
+    fn neg_each_date() {
+        let a = tamount!(d(2026, 1, 1), 10, "$") + tamount!(d(2026, 2, 1), -4, "$");
+        let expected = tamount!(d(2026, 1, 1), -10, "$") + tamount!(d(2026, 2, 1), 4, "$");
+        assert_eq!(-a, expected);
+    }
+
     #[test]
     fn sub_assign_same_dates() {
         let mut a = tamount!(100, "$");
@@ -174,6 +202,22 @@ mod tests {
         assert_eq!(c, tamount!(30, "$"));
     }
 
+    #[test]
+    fn deltas_between_consecutive_dates() {
+        let a = tamount!(d(2025, 1, 1), 10, "$")
+            + tamount!(d(2025, 2, 1), 25, "$")
+            + tamount!(d(2025, 3, 1), 20, "$");
+        assert_eq!(
+            a.deltas(),
+            tamount!(d(2025, 2, 1), 15, "$") + tamount!(d(2025, 3, 1), -5, "$")
+        );
+    }
+
+    #[test]
+    fn deltas_of_single_date_is_empty() {
+        assert_eq!(tamount!(d(2025, 1, 1), 10, "$").deltas(), TAmount::new());
+    }
+
     #[test]
     fn sub_to_zero_leaves_zero_entry() {
         let a = tamount!(50, "$");
@@ -1,11 +1,38 @@
-use crate::journal::{self, Journal, JrnIO};
+use crate::journal::{self, Journal, JrnIO, ParseOptions};
+use crate::parser_number::NumberFormat;
 use crate::pricedb::{self, PriceDB};
+use std::fmt;
 use std::io::BufRead;
 
 #[derive(Debug)]
 pub enum ReadDbError {
     JournalError(journal::JournalError),
-    PriceDBError(pricedb::ParseError),
+    /// A malformed line of the price db, `line` is 1-based.
+    PriceDBError {
+        line: usize,
+        err: pricedb::ParseError,
+    },
+    PriceDBIoError(std::io::Error),
+}
+
+impl fmt::Display for ReadDbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadDbError::JournalError(err) => write!(f, "{err}"),
+            ReadDbError::PriceDBError { line, err } => write!(f, "price db line {line}: {err}"),
+            ReadDbError::PriceDBIoError(err) => write!(f, "price db: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ReadDbError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReadDbError::JournalError(err) => Some(err),
+            ReadDbError::PriceDBError { err, .. } => Some(err),
+            ReadDbError::PriceDBIoError(err) => Some(err),
+        }
+    }
 }
 
 use pricedb::ReadItem;
@@ -31,31 +58,106 @@ use pricedb::ReadItem;
 /// In short: for a given commodity on a given date, the market price
 /// is whatever the journal's postings recorded, unless a `P` entry —
 /// in the journal itself or in the price-db file — supplies one.
+///
+/// The price-db file holds only `P` lines, blank lines and comments; a
+/// malformed line fails with [`ReadDbError::PriceDBError`] carrying
+/// its line number.
 pub fn read_journal_and_price_db(
     journal: JrnIO,
     pricedb: Option<Box<dyn BufRead>>,
 ) -> Result<(journal::Journal, pricedb::PriceDB), ReadDbError> {
-    let journal = match Journal::new(journal) {
+    read_journal(
+        journal,
+        pricedb,
+        false,
+        NumberFormat::Us,
+        ParseOptions::default(),
+    )
+}
+
+/// Like [`read_journal_and_price_db`], but with `strict` set every
+/// posting must go to an account declared with an `account`
+/// directive; otherwise it fails with
+/// [`journal::ParseError::UndeclaredAccount`]. Journal amounts are
+/// parsed in `number_format`, the transactions read as `opts` says.
+pub fn read_journal(
+    journal: JrnIO,
+    pricedb: Option<Box<dyn BufRead>>,
+    strict: bool,
+    number_format: NumberFormat,
+    opts: ParseOptions,
+) -> Result<(journal::Journal, pricedb::PriceDB), ReadDbError> {
+    let journal = match Journal::with_options(journal, number_format, opts) {
         Ok(journal) => journal,
         Err(err) => {
             return Err(ReadDbError::JournalError(err));
         }
     };
 
+    if strict && let Err(err) = journal.check_declared_accounts() {
+        return Err(ReadDbError::JournalError(err.into()));
+    }
+
     let mut price_db = PriceDB::from_journal(&journal);
     let Some(reader) = pricedb else {
         return Ok((journal, price_db));
     };
 
-    pricedb::read_price_db(reader).for_each(|item| match item {
-        ReadItem::Price(p) => price_db.upsert_price(p.sym, p.date_time, p.price),
-        ReadItem::ParseError(e) => {
-            eprintln!("Error parsing price db line: {:?}", e);
-        }
-        ReadItem::IoError(e) => {
-            eprint!("Error reading price db file: {:?}", e);
+    for item in pricedb::read_price_db(reader) {
+        match item {
+            ReadItem::Price(p) => price_db.upsert_price(p.sym, p.date_time, p.price),
+            ReadItem::ParseError { line, err } => {
+                return Err(ReadDbError::PriceDBError { line, err });
+            }
+            ReadItem::IoError(err) => return Err(ReadDbError::PriceDBIoError(err)),
         }
-    });
+    }
 
     Ok((journal, price_db))
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::quantity;
+    use crate::symbol::Symbol;
+
+    const JOURNAL: &str = "\
+2025-01-01 buy
+    Assets:Broker    10 AAPL @ $150.00
+    Assets:Cash
+";
+
+    fn read(db: &'static str) -> Result<(Journal, PriceDB), ReadDbError> {
+        read_journal_and_price_db(
+            JrnIO::Reader(Box::new(JOURNAL.as_bytes())),
+            Some(Box::new(db.as_bytes())),
+        )
+    }
+
+    #[test]
+    fn price_db_is_merged_with_journal_prices() {
+        let (_, db) = read("; quotes\n\nP 2025/02/01 AAPL $ 170.00\n").unwrap();
+        let aapl = Symbol::new("AAPL");
+        let d = |m| {
+            NaiveDate::from_ymd_opt(2025, m, 15)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+        };
+        assert_eq!(db.uprice_as_of(aapl, d(1)), Some(quantity!(150.00, "$")));
+        assert_eq!(db.uprice_as_of(aapl, d(2)), Some(quantity!(170.00, "$")));
+    }
+
+    #[test]
+    fn malformed_price_db_line_fails_with_its_number() {
+        let res = read("P 2025/02/01 AAPL $ 170.00\n; ok\nP AAPL $ 1\n");
+        assert!(matches!(
+            res,
+            Err(ReadDbError::PriceDBError { line: 3, .. })
+        ));
+    }
+}